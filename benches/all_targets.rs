@@ -0,0 +1,25 @@
+//! Times converting a large array to every default target.
+//!
+//! `cargo bench --bench all_targets --features "csv serde_yaml toml"` runs
+//! the sequential path; add `rayon` to compare the parallel one.
+
+use std::time::Instant;
+
+use llmkit::{convert_value_to_formats_with_targets, TargetFormat};
+use serde_json::{Value, json};
+
+fn main() {
+    let rows: Vec<Value> = (0..50_000)
+        .map(|i| json!({"id": i, "name": format!("user{i}"), "active": i % 2 == 0, "score": i as f64 / 3.0}))
+        .collect();
+    let value = Value::Array(rows);
+    let targets = ["json", "yaml", "toml", "csv", "markdown_table"].map(TargetFormat::from_name);
+
+    let runs = 5;
+    let start = Instant::now();
+    for _ in 0..runs {
+        std::hint::black_box(convert_value_to_formats_with_targets(&value, &targets));
+    }
+    let mode = if cfg!(feature = "rayon") { "parallel" } else { "sequential" };
+    println!("{mode}: {:?} per conversion", start.elapsed() / runs);
+}
@@ -0,0 +1,283 @@
+//! Carrying JSONC comments over to YAML and TOML output.
+
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+/// Comment lines keyed by the object-key path they sit above.
+type Comments = HashMap<Vec<String>, Vec<String>>;
+
+enum Frame {
+    /// An object, with the key whose value is being read.
+    Object { key: Option<String>, expect_key: bool },
+    Array,
+}
+
+/// Collects the comments of JSONC text `s`, each attached to the key after
+/// it, or to the key before it when it trails a value on the same line.
+/// Comments on their own line inside an array or before a closing brace
+/// have no key to follow (and no place in the output), so they are dropped.
+fn collect(s: &str) -> Comments {
+    let mut comments = Comments::new();
+    let mut stack: Vec<Frame> = Vec::new();
+    let mut pending: Vec<String> = Vec::new();
+    let mut last_key: Option<Vec<String>> = None;
+    let mut line_has_token = false;
+    let mut chars = s.chars().peekable();
+
+    // The key path of the value being read, if no array is on the way there.
+    let path = |stack: &[Frame]| -> Option<Vec<String>> {
+        stack
+            .iter()
+            .map(|frame| match frame {
+                Frame::Object { key, .. } => key.clone(),
+                Frame::Array => None,
+            })
+            .collect()
+    };
+
+    while let Some(c) = chars.next() {
+        let text = match c {
+            '\n' => {
+                line_has_token = false;
+                continue;
+            }
+            c if c.is_whitespace() => continue,
+            '/' if chars.peek() == Some(&'/') => {
+                let mut line = String::new();
+                while let Some(ch) = chars.next_if(|&ch| ch != '\n') {
+                    line.push(ch);
+                }
+                vec![line.trim_start_matches('/').trim().to_string()]
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut body = String::new();
+                while let Some(ch) = chars.next() {
+                    if ch == '*' && chars.peek() == Some(&'/') {
+                        chars.next();
+                        break;
+                    }
+                    body.push(ch);
+                }
+                body.lines()
+                    .map(|line| line.trim().trim_start_matches('*').trim().to_string())
+                    .filter(|line| !line.is_empty())
+                    .collect()
+            }
+            '"' => {
+                let mut literal = String::from('"');
+                while let Some(ch) = chars.next() {
+                    literal.push(ch);
+                    match ch {
+                        '\\' => literal.extend(chars.next()),
+                        '"' => break,
+                        _ => {}
+                    }
+                }
+                line_has_token = true;
+                while chars.next_if(|ch| ch.is_whitespace() && *ch != '\n').is_some() {}
+                let is_key = chars.peek() == Some(&':')
+                    && matches!(stack.last(), Some(Frame::Object { expect_key: true, .. }));
+                if !is_key {
+                    pending.clear();
+                    continue;
+                }
+                let key = serde_json::from_str::<String>(&literal).unwrap_or_default();
+                if let Some(Frame::Object { key: current, expect_key }) = stack.last_mut() {
+                    *current = Some(key);
+                    *expect_key = false;
+                }
+                last_key = path(&stack);
+                if let Some(path) = &last_key
+                    && !pending.is_empty()
+                {
+                    comments.entry(path.clone()).or_default().append(&mut pending);
+                }
+                pending.clear();
+                continue;
+            }
+            open @ ('{' | '[') => {
+                line_has_token = true;
+                stack.push(match open {
+                    '{' => Frame::Object { key: None, expect_key: true },
+                    _ => Frame::Array,
+                });
+                // Comments ahead of the outermost `{` belong to its first key.
+                if stack.len() > 1 {
+                    pending.clear();
+                }
+                continue;
+            }
+            '}' | ']' => {
+                line_has_token = true;
+                stack.pop();
+                pending.clear();
+                continue;
+            }
+            ',' => {
+                if let Some(Frame::Object { expect_key, .. }) = stack.last_mut() {
+                    *expect_key = true;
+                }
+                continue;
+            }
+            _ => {
+                line_has_token = true;
+                pending.clear();
+                continue;
+            }
+        };
+        match &last_key {
+            Some(path) if line_has_token => comments.entry(path.clone()).or_default().extend(text),
+            _ => pending.extend(text),
+        }
+    }
+    comments
+}
+
+/// Copies the comments of JSONC `source` into the `yaml` and `toml` entries
+/// of a conversion map.
+pub(crate) fn annotate_targets(out: &mut Map<String, Value>, source: &str) {
+    let comments = collect(source);
+    if comments.is_empty() {
+        return;
+    }
+    if let Some(Value::String(yaml)) = out.get_mut("yaml") {
+        *yaml = annotate_yaml(yaml, &comments);
+    }
+    if let Some(Value::String(toml)) = out.get_mut("toml") {
+        *toml = annotate_toml(toml, &comments);
+    }
+}
+
+/// Inserts `# comment` lines above the YAML keys they belong to. Keys inside
+/// sequences and block scalars are left alone.
+fn annotate_yaml(yaml: &str, comments: &Comments) -> String {
+    let mut out = String::new();
+    let mut keys: Vec<(usize, String)> = Vec::new();
+    // Lines indented past this belong to a sequence or block scalar.
+    let mut opaque_below: Option<usize> = None;
+    for line in yaml.lines() {
+        let content = line.trim_start();
+        let indent = line.len() - content.len();
+        if let Some(floor) = opaque_below {
+            if indent > floor || (indent == floor && content.starts_with('-')) {
+                out.push_str(line);
+                out.push('\n');
+                continue;
+            }
+            opaque_below = None;
+        }
+        keys.retain(|(depth, _)| *depth < indent);
+        if content.starts_with('-') {
+            opaque_below = Some(indent);
+        } else if let Some((key, rest)) = yaml_key(content) {
+            let mut path: Vec<String> = keys.iter().map(|(_, k)| k.clone()).collect();
+            path.push(key.clone());
+            for comment in comments.get(&path).into_iter().flatten() {
+                out.push_str(&format!("{}# {comment}\n", " ".repeat(indent)));
+            }
+            if rest.trim_start().starts_with(['|', '>']) {
+                opaque_below = Some(indent);
+            }
+            keys.push((indent, key));
+        }
+        out.push_str(line);
+        out.push('\n');
+    }
+    out
+}
+
+/// The key of a `key: value` line and the text after the colon.
+fn yaml_key(content: &str) -> Option<(String, &str)> {
+    let end = match content.chars().next()? {
+        '"' => {
+            let mut escaped = false;
+            content[1..].find(|c| {
+                let close = c == '"' && !escaped;
+                escaped = c == '\\' && !escaped;
+                close
+            })? + 2
+        }
+        '\'' => {
+            let bytes = content.as_bytes();
+            let mut i = 1;
+            loop {
+                match bytes.get(i)? {
+                    b'\'' if bytes.get(i + 1) == Some(&b'\'') => i += 2,
+                    b'\'' => break i + 1,
+                    _ => i += 1,
+                }
+            }
+        }
+        _ => content.find(": ").or_else(|| content.strip_suffix(':').map(str::len))?,
+    };
+    let rest = content[end..].strip_prefix(':')?;
+    let raw = &content[..end];
+    let key = match raw.chars().next()? {
+        '"' => serde_json::from_str(raw).ok()?,
+        '\'' => raw[1..raw.len() - 1].replace("''", "'"),
+        _ => raw.to_string(),
+    };
+    Some((key, rest))
+}
+
+/// Inserts `# comment` lines above the TOML keys and `[table]` headers they
+/// belong to. Keys under `[[array]]` tables are left alone.
+fn annotate_toml(toml: &str, comments: &Comments) -> String {
+    let mut out = String::new();
+    let mut table: Option<Vec<String>> = Some(Vec::new());
+    let mut in_multiline = false;
+    for line in toml.lines() {
+        let content = line.trim_start();
+        let path = if in_multiline {
+            None
+        } else if content.starts_with("[[") {
+            table = None;
+            None
+        } else if let Some(header) = content.strip_prefix('[').and_then(|h| h.strip_suffix(']')) {
+            table = toml_dotted(header);
+            table.clone()
+        } else {
+            let key = content.split_once(" = ").and_then(|(key, _)| toml_dotted(key));
+            table.as_ref().zip(key).map(|(table, key)| [table.clone(), key].concat())
+        };
+        for comment in path.and_then(|path| comments.get(&path)).into_iter().flatten() {
+            out.push_str(&format!("# {comment}\n"));
+        }
+        if (line.matches("\"\"\"").count() + line.matches("'''").count()) % 2 == 1 {
+            in_multiline = !in_multiline;
+        }
+        out.push_str(line);
+        out.push('\n');
+    }
+    out
+}
+
+/// Splits a TOML dotted key (`a."b.c".d`) into its parts.
+fn toml_dotted(key: &str) -> Option<Vec<String>> {
+    let mut parts = Vec::new();
+    let mut rest = key.trim();
+    loop {
+        let (part, after) = match rest.chars().next()? {
+            '"' => {
+                let end = rest[1..].find('"')? + 2;
+                (serde_json::from_str(&rest[..end]).ok()?, &rest[end..])
+            }
+            '\'' => {
+                let end = rest[1..].find('\'')? + 2;
+                (rest[1..end - 1].to_string(), &rest[end..])
+            }
+            _ => {
+                let end = rest.find('.').unwrap_or(rest.len());
+                (rest[..end].trim().to_string(), &rest[end..])
+            }
+        };
+        parts.push(part);
+        rest = after.trim_start();
+        match rest.strip_prefix('.') {
+            Some(after) => rest = after.trim_start(),
+            None if rest.is_empty() => return Some(parts),
+            None => return None,
+        }
+    }
+}
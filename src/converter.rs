@@ -0,0 +1,197 @@
+//! A reusable converter bound to one set of options.
+
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+use crate::ConvertOptions;
+
+/// Converts inputs with a fixed [`ConvertOptions`], for servers that handle
+/// many documents with the same settings. Build one with [`Converter::new`]
+/// or [`Converter::builder`]; compiled fence patterns are shared process-wide,
+/// so a conversion costs one method call.
+///
+/// ```
+/// let converter = llmkit::Converter::builder().indent(4).targets(&["json"]).build();
+/// let map = converter.convert(br#"{"a":1}"#);
+/// assert_eq!(map["json"], "{\n    \"a\": 1\n}");
+/// ```
+///
+/// With the `cache` feature, `with_cache` keeps the most recent envelopes so
+/// repeated identical inputs skip parsing entirely.
+pub struct Converter {
+    opts: ConvertOptions,
+    #[cfg(feature = "cache")]
+    cache: Option<cache::Cache>,
+}
+
+impl Converter {
+    pub fn new(opts: ConvertOptions) -> Self {
+        Self {
+            opts,
+            #[cfg(feature = "cache")]
+            cache: None,
+        }
+    }
+
+    pub fn builder() -> ConverterBuilder {
+        ConverterBuilder::default()
+    }
+
+    pub fn options(&self) -> &ConvertOptions {
+        &self.opts
+    }
+
+    /// Runs `f` on every input after decoding and BOM removal, before fence
+    /// stripping and detection, for domain-specific cleanup such as dropping
+    /// a known prefix or fixing a typo a model keeps making.
+    pub fn with_preprocessor(mut self, f: impl Fn(&str) -> String + Send + Sync + 'static) -> Self {
+        self.opts.preprocessor = Some(crate::Preprocessor::new(f));
+        self
+    }
+
+    /// Same as [`convert_map_with`](crate::convert_map_with) with this
+    /// converter's options.
+    pub fn convert(&self, input: &[u8]) -> BTreeMap<String, Value> {
+        #[cfg(feature = "cache")]
+        if let Some(cache) = &self.cache {
+            return cache.get_or_insert(input, || crate::convert_map_with(input, &self.opts));
+        }
+        crate::convert_map_with(input, &self.opts)
+    }
+
+    /// Keeps up to `capacity` envelopes, evicting the least recently used.
+    /// The cache is shared safely across threads using this converter.
+    #[cfg(feature = "cache")]
+    pub fn with_cache(mut self, capacity: usize) -> Self {
+        self.cache = std::num::NonZeroUsize::new(capacity).map(cache::Cache::new);
+        self
+    }
+
+    /// Conversions answered from the cache so far.
+    #[cfg(feature = "cache")]
+    pub fn cache_hits(&self) -> u64 {
+        self.cache.as_ref().map_or(0, |c| c.hits.load(std::sync::atomic::Ordering::Relaxed))
+    }
+}
+
+impl Default for Converter {
+    fn default() -> Self {
+        Self::new(ConvertOptions::default())
+    }
+}
+
+/// Step-by-step configuration for a [`Converter`]; anything not set keeps its
+/// [`ConvertOptions`] default.
+#[derive(Debug, Clone, Default)]
+pub struct ConverterBuilder {
+    opts: ConvertOptions,
+    #[cfg(feature = "cache")]
+    cache_capacity: usize,
+}
+
+impl ConverterBuilder {
+    /// Starts from `opts` instead of the defaults, for settings the builder
+    /// has no method for.
+    pub fn options(mut self, opts: ConvertOptions) -> Self {
+        self.opts = opts;
+        self
+    }
+
+    /// Spaces per level in pretty JSON.
+    pub fn indent(mut self, spaces: usize) -> Self {
+        self.opts.indent = spaces;
+        self
+    }
+
+    /// One tab per level in pretty JSON, overriding [`indent`](Self::indent).
+    pub fn indent_tabs(mut self, on: bool) -> Self {
+        self.opts.indent_tabs = on;
+        self
+    }
+
+    pub fn permissive(mut self, on: bool) -> Self {
+        self.opts.allow_permissive = on;
+        self
+    }
+
+    pub fn max_bytes(mut self, limit: Option<usize>) -> Self {
+        self.opts.max_bytes = limit;
+        self
+    }
+
+    /// Alphabetical object keys in every output; see [`ConvertOptions::sort_keys`].
+    pub fn sort_keys(mut self, on: bool) -> Self {
+        self.opts.sort_keys = on;
+        self
+    }
+
+    pub fn targets<S: AsRef<str>>(mut self, targets: &[S]) -> Self {
+        self.opts = self.opts.targets(targets);
+        self
+    }
+
+    /// Same as [`Converter::with_cache`].
+    #[cfg(feature = "cache")]
+    pub fn cache(mut self, capacity: usize) -> Self {
+        self.cache_capacity = capacity;
+        self
+    }
+
+    pub fn build(self) -> Converter {
+        let converter = Converter::new(self.opts);
+        #[cfg(feature = "cache")]
+        let converter = converter.with_cache(self.cache_capacity);
+        converter
+    }
+}
+
+#[cfg(feature = "cache")]
+mod cache {
+    use lru::LruCache;
+    use serde_json::Value;
+    use std::collections::BTreeMap;
+    use std::hash::{DefaultHasher, Hash, Hasher};
+    use std::num::NonZeroUsize;
+    use std::sync::Mutex;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    /// The input a cached envelope was produced from, and the envelope.
+    type Entry = (Vec<u8>, BTreeMap<String, Value>);
+
+    /// Envelopes keyed by input hash. The input is stored too, so a hash
+    /// collision is a miss rather than someone else's result.
+    pub(super) struct Cache {
+        entries: Mutex<LruCache<u64, Entry>>,
+        pub(super) hits: AtomicU64,
+    }
+
+    impl Cache {
+        pub(super) fn new(capacity: NonZeroUsize) -> Self {
+            Self { entries: Mutex::new(LruCache::new(capacity)), hits: AtomicU64::new(0) }
+        }
+
+        pub(super) fn get_or_insert(
+            &self,
+            input: &[u8],
+            convert: impl FnOnce() -> BTreeMap<String, Value>,
+        ) -> BTreeMap<String, Value> {
+            let mut hasher = DefaultHasher::new();
+            input.hash(&mut hasher);
+            let key = hasher.finish();
+
+            if let Ok(mut entries) = self.entries.lock()
+                && let Some((cached_input, map)) = entries.get(&key)
+                && cached_input == input
+            {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                return map.clone();
+            }
+            // Convert without holding the lock so other threads aren't blocked.
+            let map = convert();
+            if let Ok(mut entries) = self.entries.lock() {
+                entries.put(key, (input.to_vec(), map.clone()));
+            }
+            map
+        }
+    }
+}
@@ -0,0 +1,54 @@
+//! Reformatting of datetime strings (`ConvertOptions::datetime_format`).
+
+use chrono::{DateTime, FixedOffset, NaiveDateTime};
+use serde_json::Value;
+use std::fmt::Write;
+
+/// Rewrites every string in `v` that parses as an RFC 3339 or local
+/// (`2024-01-02T03:04:05`) datetime using the strftime `pattern`. Offset
+/// datetimes are first shifted to `timezone` (`UTC`, `Z`, `+05:30`) when
+/// given. An unknown timezone leaves values untouched.
+pub(crate) fn normalize_datetimes(v: &mut Value, pattern: &str, timezone: Option<&str>) {
+    let tz = match timezone.map(parse_timezone) {
+        Some(None) => return,
+        Some(Some(tz)) => Some(tz),
+        None => None,
+    };
+    walk(v, pattern, tz);
+}
+
+fn walk(v: &mut Value, pattern: &str, tz: Option<FixedOffset>) {
+    match v {
+        Value::String(s) => {
+            if let Some(formatted) = reformat(s, pattern, tz) {
+                *s = formatted;
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(|item| walk(item, pattern, tz)),
+        Value::Object(map) => map.values_mut().for_each(|item| walk(item, pattern, tz)),
+        _ => {}
+    }
+}
+
+fn parse_timezone(name: &str) -> Option<FixedOffset> {
+    match name.trim() {
+        "UTC" | "utc" | "Z" => FixedOffset::east_opt(0),
+        offset => offset.parse().ok(),
+    }
+}
+
+fn reformat(s: &str, pattern: &str, tz: Option<FixedOffset>) -> Option<String> {
+    // `write!` rather than `to_string()`: chrono reports bad patterns (or `%z`
+    // on a local time) as a fmt error, which `to_string()` would panic on.
+    let mut out = String::new();
+    if let Ok(dt) = DateTime::parse_from_rfc3339(s) {
+        let dt = tz.map_or(dt, |tz| dt.with_timezone(&tz));
+        write!(out, "{}", dt.format(pattern)).ok()?;
+        return Some(out);
+    }
+    let naive = ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M:%S%.f"]
+        .iter()
+        .find_map(|fmt| NaiveDateTime::parse_from_str(s, fmt).ok())?;
+    write!(out, "{}", naive.format(pattern)).ok()?;
+    Some(out)
+}
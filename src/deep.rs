@@ -0,0 +1,131 @@
+//! Parsing and recursion-free serialization of very deep JSON values.
+
+use serde::Deserialize;
+use serde_json::Value;
+use std::io::{self, Write};
+
+/// Deepest JSON [`from_str_deep`] reads, as for UBJSON. Parsing past
+/// serde_json's own limit of 128 grows the stack on the heap, but the YAML,
+/// TOML and XML writers and dropping a value still recurse, so nesting stays
+/// bounded.
+const MAX_DEPTH: usize = 512;
+
+/// Parses `s` as JSON like `serde_json::from_str`, but without its recursion
+/// limit of 128, so legitimately deep documents (up to `MAX_DEPTH`) parse.
+pub(crate) fn from_str_deep(s: &str) -> Option<Value> {
+    if json_nesting(s) > MAX_DEPTH {
+        return None;
+    }
+    let mut de = serde_json::Deserializer::from_str(s);
+    de.disable_recursion_limit();
+    let value = Value::deserialize(serde_stacker::Deserializer::new(&mut de)).ok()?;
+    de.end().ok()?;
+    Some(value)
+}
+
+/// Deepest `[`/`{` nesting in JSON text, ignoring brackets inside strings.
+fn json_nesting(s: &str) -> usize {
+    let (mut depth, mut max, mut in_string, mut escaped) = (0usize, 0, false, false);
+    for b in s.bytes() {
+        match b {
+            _ if escaped => escaped = false,
+            b'\\' if in_string => escaped = true,
+            b'"' => in_string = !in_string,
+            b'[' | b'{' if !in_string => {
+                depth += 1;
+                max = max.max(depth);
+            }
+            b']' | b'}' if !in_string => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+    max
+}
+
+/// Nesting depth of `v` (scalars are 0), measured without recursion.
+pub(crate) fn value_depth(v: &Value) -> usize {
+    let mut max = 0;
+    let mut stack = vec![(v, 0usize)];
+    while let Some((v, depth)) = stack.pop() {
+        max = max.max(depth);
+        match v {
+            Value::Array(items) => stack.extend(items.iter().map(|c| (c, depth + 1))),
+            Value::Object(obj) => stack.extend(obj.values().map(|c| (c, depth + 1))),
+            _ => {}
+        }
+    }
+    max
+}
+
+enum Frame<'a> {
+    Value(&'a Value),
+    Array { items: std::slice::Iter<'a, Value>, first: bool },
+    Object { entries: serde_json::map::Iter<'a>, first: bool },
+}
+
+/// Writes `v` as JSON using a heap stack instead of the call stack, producing
+/// byte-for-byte the same output as `serde_json::to_writer{,_pretty}` (pretty
+/// output indented by `pad` per level).
+pub(crate) fn write_json_iterative<W: Write>(v: &Value, pretty: bool, pad: &[u8], w: &mut W) -> io::Result<()> {
+    let newline = |w: &mut W, depth: usize| -> io::Result<()> {
+        if pretty {
+            w.write_all(b"\n")?;
+            for _ in 0..depth {
+                w.write_all(pad)?;
+            }
+        }
+        Ok(())
+    };
+    let mut depth = 0;
+    let mut stack = vec![Frame::Value(v)];
+    while let Some(frame) = stack.pop() {
+        match frame {
+            Frame::Value(Value::Array(items)) if items.is_empty() => w.write_all(b"[]")?,
+            Frame::Value(Value::Object(obj)) if obj.is_empty() => w.write_all(b"{}")?,
+            Frame::Value(Value::Array(items)) => {
+                w.write_all(b"[")?;
+                depth += 1;
+                stack.push(Frame::Array { items: items.iter(), first: true });
+            }
+            Frame::Value(Value::Object(obj)) => {
+                w.write_all(b"{")?;
+                depth += 1;
+                stack.push(Frame::Object { entries: obj.iter(), first: true });
+            }
+            Frame::Value(scalar) => serde_json::to_writer(&mut *w, scalar)?,
+            Frame::Array { mut items, first } => match items.next() {
+                Some(item) => {
+                    if !first {
+                        w.write_all(b",")?;
+                    }
+                    newline(w, depth)?;
+                    stack.push(Frame::Array { items, first: false });
+                    stack.push(Frame::Value(item));
+                }
+                None => {
+                    depth -= 1;
+                    newline(w, depth)?;
+                    w.write_all(b"]")?;
+                }
+            },
+            Frame::Object { mut entries, first } => match entries.next() {
+                Some((key, item)) => {
+                    if !first {
+                        w.write_all(b",")?;
+                    }
+                    newline(w, depth)?;
+                    serde_json::to_writer(&mut *w, key)?;
+                    w.write_all(if pretty { b": " } else { b":" })?;
+                    stack.push(Frame::Object { entries, first: false });
+                    stack.push(Frame::Value(item));
+                }
+                None => {
+                    depth -= 1;
+                    newline(w, depth)?;
+                    w.write_all(b"}")?;
+                }
+            },
+        }
+    }
+    Ok(())
+}
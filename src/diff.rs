@@ -0,0 +1,58 @@
+//! Structural comparison of two values.
+
+use serde_json::Value;
+
+/// Lists every place where `a` and `b` differ, as `path: reason` lines in
+/// document order, e.g. `user.tags[1]: "x" vs "y"` or `id: only in a`.
+/// Paths use the `a.b[0]` style of conversion warnings; the root is
+/// `(root)`. Equal values give an empty list, so a lossless round trip is
+/// `value_diff(&before, &after).is_empty()`.
+pub fn value_diff(a: &Value, b: &Value) -> Vec<String> {
+    let mut out = Vec::new();
+    diff(a, b, String::new(), &mut out);
+    out
+}
+
+fn diff(a: &Value, b: &Value, path: String, out: &mut Vec<String>) {
+    if a == b {
+        return;
+    }
+    let shown = || if path.is_empty() { "(root)".to_string() } else { path.clone() };
+    match (a, b) {
+        (Value::Object(left), Value::Object(right)) => {
+            let child = |k: &str| if path.is_empty() { k.to_string() } else { format!("{path}.{k}") };
+            for (k, l) in left {
+                match right.get(k) {
+                    Some(r) => diff(l, r, child(k), out),
+                    None => out.push(format!("{}: only in a", child(k))),
+                }
+            }
+            for k in right.keys().filter(|k| !left.contains_key(*k)) {
+                out.push(format!("{}: only in b", child(k)));
+            }
+        }
+        (Value::Array(left), Value::Array(right)) => {
+            for i in 0..left.len().max(right.len()) {
+                let child = format!("{path}[{i}]");
+                match (left.get(i), right.get(i)) {
+                    (Some(l), Some(r)) => diff(l, r, child, out),
+                    (Some(_), None) => out.push(format!("{child}: only in a")),
+                    _ => out.push(format!("{child}: only in b")),
+                }
+            }
+        }
+        _ if kind(a) != kind(b) => out.push(format!("{}: {} vs {}", shown(), kind(a), kind(b))),
+        _ => out.push(format!("{}: {a} vs {b}", shown())),
+    }
+}
+
+fn kind(v: &Value) -> &'static str {
+    match v {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
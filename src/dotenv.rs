@@ -0,0 +1,108 @@
+//! `.env` files: `KEY=value` lines with optional `export` and `#` comments.
+
+use serde_json::{Map, Value};
+
+use crate::{ConvertError, ConvertOptions, NestedMode};
+
+/// Parses a `.env` file into a flat object of strings.
+///
+/// Every non-blank, non-comment line must be `KEY=value` (optionally
+/// `export KEY=value`) with an identifier key, so prose and other `=`-bearing
+/// formats aren't taken. Values lose surrounding quotes; double-quoted ones
+/// also unescape `\n`, `\"` and `\\`, and unquoted ones drop a ` # comment`.
+pub(crate) fn parse_dotenv(s: &str) -> Option<Value> {
+    let mut obj = Map::new();
+    for line in s.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").map_or(line, str::trim_start);
+        let (key, value) = line.split_once('=')?;
+        if !is_env_key(key) {
+            return None;
+        }
+        obj.insert(key.to_string(), Value::String(parse_value(value.trim())?));
+    }
+    (!obj.is_empty()).then_some(Value::Object(obj))
+}
+
+fn is_env_key(key: &str) -> bool {
+    let mut chars = key.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+}
+
+fn parse_value(raw: &str) -> Option<String> {
+    if let Some(inner) = raw.strip_prefix('"') {
+        let mut out = String::new();
+        let mut chars = inner.chars();
+        loop {
+            match chars.next()? {
+                '"' => break,
+                '\\' => match chars.next()? {
+                    'n' => out.push('\n'),
+                    other => out.push(other),
+                },
+                c => out.push(c),
+            }
+        }
+        return Some(out);
+    }
+    if let Some(inner) = raw.strip_prefix('\'') {
+        return inner.split_once('\'').map(|(value, _)| value.to_string());
+    }
+    let value = raw.split_once(" #").map_or(raw, |(value, _)| value);
+    Some(value.trim_end().to_string())
+}
+
+/// Emits a flat object as `KEY=value` lines that are safe to `source`: any
+/// value with a character outside `[A-Za-z0-9_./:@%+,-]` is double-quoted
+/// with `$`, `` ` ``, `"` and `\` escaped, so the shell never expands it.
+/// Nested values are flattened or rejected per `opts.dotenv_nested`.
+pub(crate) fn to_dotenv(v: &Value, opts: &ConvertOptions) -> Result<Option<String>, ConvertError> {
+    if !v.is_object() {
+        return Ok(None);
+    }
+    let flat;
+    let v = match opts.dotenv_nested {
+        NestedMode::Flatten => {
+            flat = crate::flatten::flatten(v, &opts.dotenv_separator);
+            &flat
+        }
+        NestedMode::Error => v,
+    };
+    let Some(obj) = v.as_object() else {
+        return Ok(None);
+    };
+    let mut out = String::new();
+    for (k, v) in obj {
+        let unsupported = |reason: String| ConvertError::Unsupported { target: "dotenv".into(), reason };
+        let k = if opts.dotenv_uppercase_keys { k.to_uppercase() } else { k.clone() };
+        if !is_env_key(&k) {
+            return Err(unsupported(format!("'{k}' is not a valid variable name")));
+        }
+        let text = match v {
+            Value::Null => String::new(),
+            Value::String(s) => s.clone(),
+            Value::Object(_) | Value::Array(_) => return Err(unsupported(format!("nested value at '{k}'"))),
+            other => other.to_string(),
+        };
+        if !text.chars().all(|c| c.is_ascii_alphanumeric() || "_./:@%+,-".contains(c)) {
+            let mut escaped = String::with_capacity(text.len() + 2);
+            for c in text.chars() {
+                match c {
+                    '$' | '`' | '"' | '\\' => {
+                        escaped.push('\\');
+                        escaped.push(c);
+                    }
+                    '\n' => escaped.push_str("\\n"),
+                    c => escaped.push(c),
+                }
+            }
+            out.push_str(&format!("{k}=\"{escaped}\"\n"));
+        } else {
+            out.push_str(&format!("{k}={text}\n"));
+        }
+    }
+    Ok(Some(out))
+}
@@ -0,0 +1,95 @@
+use std::fmt;
+use std::time::Duration;
+
+use crate::DataFormat;
+
+/// Errors raised while converting a parsed value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConvertError {
+    /// A target's rendered output grew past `ConvertOptions::max_output_bytes`.
+    OutputTooLarge { target: String, limit: usize },
+    /// The value has a shape the target can't express.
+    Unsupported { target: String, reason: String },
+    /// Input text failed to parse as `format` (1-based line/column). For
+    /// binary UBJSON, `line` is 0 and `column` the byte offset.
+    Parse { format: DataFormat, line: usize, column: usize, message: String },
+    /// Reading or writing a stream failed.
+    Io(String),
+    /// Input bytes aren't valid UTF-8; the first `valid_up_to` bytes are.
+    Utf8 { valid_up_to: usize },
+    /// The requested input encoding is unknown or not compiled in.
+    Encoding(String),
+    /// The conversion ran past `ConvertOptions::timeout`.
+    Timeout(Duration),
+    /// No parser accepted the input. `attempted` lists the formats tried, in
+    /// order (empty for blank input); `cause` is the most relevant parser's
+    /// error (a repeated CSV header under `DuplicateHeaders::Error`, JSON for
+    /// `{`/`[` input, else YAML, then TOML), usually a `Parse`.
+    Unrecognized { attempted: Vec<DataFormat>, cause: Option<Box<ConvertError>> },
+}
+
+impl ConvertError {
+    /// Maps a serde_json error, shifting its line by `line_offset` for
+    /// documents that start part-way through a larger input.
+    pub(crate) fn from_json(format: DataFormat, err: &serde_json::Error, line_offset: usize) -> Self {
+        let full = err.to_string();
+        // serde_json appends " at line L column C"; keep just the reason.
+        let message = match full.rfind(" at line ") {
+            Some(idx) => full[..idx].to_string(),
+            None => full,
+        };
+        Self::Parse { format, line: err.line() + line_offset, column: err.column(), message }
+    }
+
+    /// Maps a serde_yaml error; errors without a location report line 0.
+    #[cfg(feature = "serde_yaml")]
+    pub(crate) fn from_yaml(err: &serde_yaml::Error) -> Self {
+        let full = err.to_string();
+        let message = match full.find(" at line ") {
+            Some(idx) => full[..idx].to_string(),
+            None => full,
+        };
+        let (line, column) = err.location().map_or((0, 0), |loc| (loc.line(), loc.column()));
+        Self::Parse { format: DataFormat::Yaml, line, column, message }
+    }
+
+    /// Maps a toml error, locating its span in `source`.
+    #[cfg(feature = "toml")]
+    pub(crate) fn from_toml(err: &toml::de::Error, source: &str) -> Self {
+        let (line, column) = err.span().map_or((0, 0), |span| {
+            let before = &source[..span.start.min(source.len())];
+            let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+            (before.matches('\n').count() + 1, before[line_start..].chars().count() + 1)
+        });
+        Self::Parse { format: DataFormat::Toml, line, column, message: err.message().to_string() }
+    }
+}
+
+impl fmt::Display for ConvertError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::OutputTooLarge { target, limit } => {
+                write!(f, "{target} output exceeds {limit} bytes")
+            }
+            Self::Unsupported { target, reason } => write!(f, "{target}: {reason}"),
+            Self::Parse { format, line, column, message } => {
+                write!(f, "{} parse error at line {line}, column {column}: {message}", format.as_str())
+            }
+            Self::Io(message) => write!(f, "io error: {message}"),
+            Self::Utf8 { valid_up_to } => write!(f, "invalid UTF-8 after byte {valid_up_to}"),
+            Self::Encoding(message) => write!(f, "encoding error: {message}"),
+            Self::Timeout(limit) => write!(f, "conversion timed out after {limit:?}"),
+            Self::Unrecognized { attempted, .. } if attempted.is_empty() => write!(f, "input is empty"),
+            Self::Unrecognized { attempted, cause } => {
+                let names: Vec<&str> = attempted.iter().map(DataFormat::as_str).collect();
+                write!(f, "input format not recognized (tried {})", names.join(", "))?;
+                match cause {
+                    Some(e) => write!(f, "; {e}"),
+                    None => Ok(()),
+                }
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConvertError {}
@@ -0,0 +1,156 @@
+//! Conversion between nested values and single-level objects with joined keys.
+
+use serde_json::{Map, Value};
+
+use crate::ConvertError;
+
+/// Flattens `value` into one object whose keys are the paths to each leaf,
+/// joined with `separator` (`{"a":{"b":[1]}}` → `{"a.b.0":1}`). Array indices
+/// become path segments; empty objects and arrays are kept as leaves so
+/// nothing is lost. A scalar root is stored under the empty key.
+pub fn flatten(value: &Value, separator: &str) -> Value {
+    let mut out = Map::new();
+    flatten_into(value, String::new(), separator, false, &mut out);
+    Value::Object(out)
+}
+
+/// [`flatten`] for keys that may contain `separator` themselves: inside a
+/// key, the separator is written as `\` + separator and a backslash as `\\`,
+/// so `{"user.email":1}` becomes `{"user\\.email":1}` rather than a path.
+/// Undo with [`unflatten_escaped`].
+pub fn flatten_escaped(value: &Value, separator: &str) -> Value {
+    let mut out = Map::new();
+    flatten_into(value, String::new(), separator, true, &mut out);
+    Value::Object(out)
+}
+
+fn flatten_into(value: &Value, path: String, separator: &str, escape: bool, out: &mut Map<String, Value>) {
+    let join = |key: &str| {
+        let key = if escape { escape_key(key, separator) } else { key.to_string() };
+        if path.is_empty() { key } else { format!("{path}{separator}{key}") }
+    };
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            for (k, v) in map {
+                flatten_into(v, join(k), separator, escape, out);
+            }
+        }
+        Value::Array(items) if !items.is_empty() => {
+            for (i, v) in items.iter().enumerate() {
+                flatten_into(v, join(&i.to_string()), separator, escape, out);
+            }
+        }
+        leaf => {
+            out.insert(path, leaf.clone());
+        }
+    }
+}
+
+/// Rebuilds nested structure from keys joined with `separator`, the inverse of
+/// [`flatten`]: `{"a.b.0":1}` → `{"a":{"b":[1]}}`. An object whose keys are
+/// exactly `0..n` becomes an array. A key that is both a value and a prefix of
+/// another key (`a` and `a.b`) is an error. Non-object input is returned as is.
+pub fn unflatten(value: &Value, separator: &str) -> Result<Value, ConvertError> {
+    unflatten_with(value, separator, false)
+}
+
+/// Inverse of [`flatten_escaped`]: only unescaped separators split a key,
+/// so literal separators inside keys survive the round trip.
+pub fn unflatten_escaped(value: &Value, separator: &str) -> Result<Value, ConvertError> {
+    unflatten_with(value, separator, true)
+}
+
+fn unflatten_with(value: &Value, separator: &str, escaped: bool) -> Result<Value, ConvertError> {
+    let Value::Object(flat) = value else {
+        return Ok(value.clone());
+    };
+    if let Some(root) = flat.get("")
+        && flat.len() == 1
+    {
+        return Ok(root.clone());
+    }
+    let mut root = Map::new();
+    for (key, leaf) in flat {
+        let segments: Vec<String> = match separator {
+            "" => vec![key.clone()],
+            _ if escaped => split_escaped(key, separator),
+            _ => key.split(separator).map(String::from).collect(),
+        };
+        let (last, parents) = segments.split_last().map_or(("", &[][..]), |(l, p)| (l.as_str(), p));
+        let mut node = &mut root;
+        for (depth, segment) in parents.iter().enumerate() {
+            let child = node.entry(segment.as_str()).or_insert_with(|| Value::Object(Map::new()));
+            node = match child {
+                Value::Object(map) => map,
+                _ => return Err(conflict(&segments[..=depth].join(separator), &format!("'{key}'"))),
+            };
+        }
+        match (node.get_mut(last), leaf) {
+            (None, leaf) => {
+                node.insert(last.to_string(), leaf.clone());
+            }
+            (Some(Value::Object(existing)), Value::Object(more)) => {
+                existing.extend(more.clone());
+            }
+            (Some(_), _) => return Err(conflict(key, "another key")),
+        }
+    }
+    let mut out = Value::Object(root);
+    arrays_from_indices(&mut out);
+    Ok(out)
+}
+
+fn escape_key(key: &str, separator: &str) -> String {
+    if separator.is_empty() {
+        return key.to_string();
+    }
+    key.replace('\\', "\\\\").replace(separator, &format!("\\{separator}"))
+}
+
+/// Splits `key` on separators not preceded by a backslash, unescaping as it
+/// goes. A trailing lone backslash is kept as is.
+fn split_escaped(key: &str, separator: &str) -> Vec<String> {
+    let mut segments = Vec::new();
+    let mut current = String::new();
+    let mut rest = key;
+    while let Some(c) = rest.chars().next() {
+        let after = &rest[c.len_utf8()..];
+        if c == '\\' && after.starts_with(separator) {
+            current.push_str(separator);
+            rest = &after[separator.len()..];
+        } else if c == '\\' && let Some(next) = after.chars().next() {
+            current.push(next);
+            rest = &after[next.len_utf8()..];
+        } else if rest.starts_with(separator) {
+            segments.push(std::mem::take(&mut current));
+            rest = &rest[separator.len()..];
+        } else {
+            current.push(c);
+            rest = after;
+        }
+    }
+    segments.push(current);
+    segments
+}
+
+fn conflict(prefix: &str, other: &str) -> ConvertError {
+    ConvertError::Unsupported {
+        target: "unflatten".into(),
+        reason: format!("'{prefix}' is both a value and a prefix of {other}"),
+    }
+}
+
+fn arrays_from_indices(v: &mut Value) {
+    match v {
+        Value::Object(map) => {
+            map.values_mut().for_each(arrays_from_indices);
+            let dense = !map.is_empty() && (0..map.len()).all(|i| map.contains_key(&i.to_string()));
+            if dense {
+                let mut map = std::mem::take(map);
+                *v = Value::Array((0..map.len()).filter_map(|i| map.remove(&i.to_string())).collect());
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(arrays_from_indices),
+        _ => {}
+    }
+}
@@ -0,0 +1,62 @@
+//! Gzip-compressed JSONL (`.jsonl.gz`).
+
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use serde_json::Value;
+use std::io::{Read, Write};
+
+use crate::ConvertError;
+use crate::ndjson::{self, Records};
+
+const MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// How far gzip input inflates when `max_bytes` isn't set.
+pub(crate) const MAX_INFLATED: usize = 256 * 1024 * 1024;
+
+pub(crate) fn is_gzip(input: &[u8]) -> bool {
+    input.starts_with(&MAGIC)
+}
+
+/// Decompresses a gzip buffer, reading at most `limit` bytes of output
+/// ([`MAX_INFLATED`] when `None`) so a small bomb can't expand without bound.
+/// The flag reports whether the inflated data was cut at the limit.
+pub(crate) fn gunzip(input: &[u8], limit: Option<usize>) -> Option<(Vec<u8>, bool)> {
+    let limit = limit.unwrap_or(MAX_INFLATED);
+    let mut out = Vec::new();
+    // One byte past the limit tells "exactly n" apart from "more than n".
+    GzDecoder::new(input).take(limit as u64 + 1).read_to_end(&mut out).ok()?;
+    let truncated = out.len() > limit;
+    out.truncate(limit);
+    Some((out, truncated))
+}
+
+/// Writes `v` as gzip-compressed NDJSON.
+pub(crate) fn write_jsonl_gz<W: Write>(v: &Value, w: W) -> std::io::Result<()> {
+    let mut enc = GzEncoder::new(w, Compression::default());
+    ndjson::write_ndjson(v, &mut enc)?;
+    enc.finish()?;
+    Ok(())
+}
+
+/// Streams records out of a `.jsonl.gz` reader, decompressing line by line
+/// instead of inflating the whole stream up front.
+pub fn read_jsonl_gz<R: Read>(reader: R) -> impl Iterator<Item = Result<Value, ConvertError>> {
+    Records::new(GzDecoder::new(reader))
+}
+
+/// Streams `records` into `writer` as gzip-compressed JSONL, returning the
+/// writer once the gzip trailer is written.
+pub fn write_jsonl_gz_records<'a, W, I>(writer: W, records: I) -> Result<W, ConvertError>
+where
+    W: Write,
+    I: IntoIterator<Item = &'a Value>,
+{
+    let io_err = |e: std::io::Error| ConvertError::Io(e.to_string());
+    let mut enc = GzEncoder::new(writer, Compression::default());
+    for record in records {
+        serde_json::to_writer(&mut enc, record).map_err(|e| ConvertError::Io(e.to_string()))?;
+        enc.write_all(b"\n").map_err(io_err)?;
+    }
+    enc.finish().map_err(io_err)
+}
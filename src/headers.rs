@@ -0,0 +1,79 @@
+//! HTTP-header-style `Key: Value` blocks.
+
+use serde_json::{Map, Value};
+
+use crate::ConvertError;
+
+/// Parses a block where every non-blank line is `Header-Name: value`.
+///
+/// Kept conservative so plain YAML mappings aren't stolen: keys must be
+/// Header-Case tokens (`Content-Type`, `X-Id`), at least one key must contain a
+/// hyphen, and nothing may be indented. Key casing is preserved and repeated
+/// headers collect into arrays.
+pub(crate) fn parse_headers(s: &str) -> Option<Value> {
+    let mut obj = Map::new();
+    let mut hyphenated = false;
+    for line in s.lines().filter(|l| !l.trim().is_empty()) {
+        if line.starts_with(char::is_whitespace) {
+            return None;
+        }
+        let (key, value) = line.split_once(':')?;
+        if !is_header_name(key) {
+            return None;
+        }
+        hyphenated |= key.contains('-');
+        let value = Value::String(value.trim().to_string());
+        match obj.get_mut(key) {
+            Some(Value::Array(items)) => items.push(value),
+            Some(existing) => {
+                let first = existing.take();
+                *existing = Value::Array(vec![first, value]);
+            }
+            None => {
+                obj.insert(key.to_string(), value);
+            }
+        }
+    }
+    if obj.is_empty() || !hyphenated {
+        return None;
+    }
+    Some(Value::Object(obj))
+}
+
+fn is_header_name(key: &str) -> bool {
+    !key.is_empty()
+        && key.split('-').all(|seg| {
+            let mut chars = seg.chars();
+            chars.next().is_some_and(|c| c.is_ascii_uppercase())
+                && chars.all(|c| c.is_ascii_alphanumeric())
+        })
+}
+
+/// Emits a flat object as `Key: value` lines; arrays repeat the header.
+pub(crate) fn to_headers(v: &Value) -> Result<Option<String>, ConvertError> {
+    let Some(obj) = v.as_object() else {
+        return Ok(None);
+    };
+    let mut out = String::new();
+    for (k, v) in obj {
+        let values = match v {
+            Value::Array(items) => items.iter().collect::<Vec<_>>(),
+            other => vec![other],
+        };
+        for item in values {
+            let text = match item {
+                Value::Null => String::new(),
+                Value::String(s) => s.clone(),
+                Value::Object(_) | Value::Array(_) => {
+                    return Err(ConvertError::Unsupported {
+                        target: "headers".into(),
+                        reason: format!("nested value at '{k}'"),
+                    });
+                }
+                other => other.to_string(),
+            };
+            out.push_str(&format!("{k}: {text}\n"));
+        }
+    }
+    Ok(Some(out))
+}
@@ -0,0 +1,54 @@
+//! INI-style config: `[section]` headers over `key = value` lines.
+
+use serde_json::{Map, Value};
+
+/// Parses INI text into an object of sections, each an object of string
+/// values. Keys before the first section sit at the top level.
+///
+/// Lines starting with `;` or `#` are comments. Every other non-blank line
+/// must be a section header or contain `=`, and at least one section is
+/// required, so bare `KEY=value` files and prose aren't taken. Section names
+/// can't contain brackets (`[[x]]` is a TOML array table). Values are
+/// trimmed and lose one pair of surrounding quotes; a repeated section is
+/// merged, a repeated key keeps its last value.
+pub(crate) fn parse_ini(s: &str) -> Option<Value> {
+    let mut root = Map::new();
+    let mut section: Option<String> = None;
+    for line in s.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with([';', '#']) {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            let name = name.trim();
+            if name.is_empty() || name.contains(['[', ']']) {
+                return None;
+            }
+            match root.entry(name).or_insert_with(|| Value::Object(Map::new())) {
+                Value::Object(_) => section = Some(name.to_string()),
+                _ => return None,
+            }
+            continue;
+        }
+        let (key, value) = line.split_once('=')?;
+        let key = key.trim();
+        if key.is_empty() {
+            return None;
+        }
+        let target = match &section {
+            Some(name) => root.get_mut(name)?.as_object_mut()?,
+            None => &mut root,
+        };
+        target.insert(key.to_string(), Value::String(unquote(value.trim()).to_string()));
+    }
+    // Only sections are objects, so one is present iff some value is.
+    root.values().any(Value::is_object).then_some(Value::Object(root))
+}
+
+fn unquote(value: &str) -> &str {
+    for quote in ['"', '\''] {
+        if let Some(inner) = value.strip_prefix(quote).and_then(|v| v.strip_suffix(quote)) {
+            return inner;
+        }
+    }
+    value
+}
@@ -0,0 +1,54 @@
+//! JSON5 output for human-edited configs.
+
+use serde_json::Value;
+
+/// Renders `v` as pretty JSON5: two-space indent, identifier keys unquoted,
+/// and a trailing comma after the last entry of every multi-line container.
+pub(crate) fn to_json5(v: &Value) -> String {
+    let mut out = String::new();
+    write_value(v, 0, &mut out);
+    out
+}
+
+fn write_value(v: &Value, indent: usize, out: &mut String) {
+    match v {
+        Value::Object(map) if !map.is_empty() => {
+            out.push_str("{\n");
+            for (k, item) in map {
+                push_indent(indent + 1, out);
+                if is_identifier(k) {
+                    out.push_str(k);
+                } else {
+                    out.push_str(&Value::String(k.clone()).to_string());
+                }
+                out.push_str(": ");
+                write_value(item, indent + 1, out);
+                out.push_str(",\n");
+            }
+            push_indent(indent, out);
+            out.push('}');
+        }
+        Value::Array(items) if !items.is_empty() => {
+            out.push_str("[\n");
+            for item in items {
+                push_indent(indent + 1, out);
+                write_value(item, indent + 1, out);
+                out.push_str(",\n");
+            }
+            push_indent(indent, out);
+            out.push(']');
+        }
+        scalar => out.push_str(&scalar.to_string()),
+    }
+}
+
+fn push_indent(level: usize, out: &mut String) {
+    out.extend(std::iter::repeat_n("  ", level));
+}
+
+/// ASCII subset of an ECMAScript IdentifierName, which JSON5 allows unquoted.
+fn is_identifier(key: &str) -> bool {
+    let mut chars = key.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == '$')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$')
+}
@@ -0,0 +1,86 @@
+//! Conversion results whose targets are rendered on first access.
+
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+
+use crate::{ConvertOptions, TargetFormat};
+
+/// Like the [`convert_map_with`](crate::convert_map_with) result, but each
+/// target is only rendered when [`get`](Self::get) first asks for it, so
+/// passing no `targets` costs nothing for the formats you never read.
+///
+/// `Stats`, `Warnings` and `Errors` depend on running every conversion and are
+/// not produced; a target that fails reads as `Null`.
+pub struct LazyConversions {
+    base: BTreeMap<String, Value>,
+    value: Option<Value>,
+    opts: ConvertOptions,
+    targets: Vec<(String, TargetFormat, OnceLock<Value>)>,
+}
+
+/// Detects and parses `input` now, deferring every target conversion.
+pub fn convert_lazy(input: &[u8], opts: &ConvertOptions) -> LazyConversions {
+    let base_opts = ConvertOptions {
+        targets: Some(Vec::new()),
+        include_stats: false,
+        include_warnings: false,
+        ..opts.clone()
+    };
+    let base = crate::convert_map_with(input, &base_opts);
+    // `normal` is the parsed value as compact JSON, so re-reading it is cheap
+    // and exact; it's only meaningful once a format was detected.
+    let value = (crate::detected_format(&base) != crate::DataFormat::Unknown)
+        .then(|| crate::get_conversion(&base, "normal"))
+        .flatten()
+        .and_then(|normal| serde_json::from_str::<Value>(normal).ok());
+    let targets = match (&opts.targets, &value) {
+        (Some(list), _) => list.iter().map(|s| TargetFormat::from_name(s)).collect(),
+        (None, Some(v)) => crate::default_targets(v, opts),
+        (None, None) => Vec::new(),
+    };
+    LazyConversions {
+        base,
+        value,
+        opts: opts.clone(),
+        targets: targets.into_iter().map(|t| (t.name(), t, OnceLock::new())).collect(),
+    }
+}
+
+impl LazyConversions {
+    /// An envelope key (`Format`, `normal`, ...) or a target, rendering the
+    /// target on first call.
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        if let Some(v) = self.base.get(key) {
+            return Some(v);
+        }
+        let (_, target, cell) = self.targets.iter().find(|(name, ..)| name == key)?;
+        Some(cell.get_or_init(|| {
+            self.value
+                .as_ref()
+                .and_then(|v| crate::convert_value_to_target(v, target, &self.opts).ok())
+                .unwrap_or(Value::Null)
+        }))
+    }
+
+    /// Whether `target` has been rendered yet.
+    pub fn is_evaluated(&self, target: &str) -> bool {
+        self.targets.iter().any(|(name, _, cell)| name == target && cell.get().is_some())
+    }
+
+    /// Names of the targets available through [`get`](Self::get).
+    pub fn target_names(&self) -> impl Iterator<Item = &str> {
+        self.targets.iter().map(|(name, ..)| name.as_str())
+    }
+
+    /// Renders any remaining targets and returns the eager map.
+    pub fn into_map(self) -> BTreeMap<String, Value> {
+        let mut map = self.base.clone();
+        for (name, ..) in &self.targets {
+            if let Some(v) = self.get(name) {
+                map.insert(name.clone(), v.clone());
+            }
+        }
+        map
+    }
+}
@@ -1,44 +1,173 @@
 use serde_json::{Map, Value};
+use std::borrow::Cow;
 use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::sync::OnceLock;
+
+use regex::Regex;
+
+#[cfg(feature = "chrono")]
+mod datetime;
+mod comments;
+mod converter;
+mod deep;
+mod diff;
+mod dotenv;
+mod error;
+mod flatten;
+#[cfg(feature = "flate2")]
+mod gzip;
+mod headers;
+mod ini;
+mod json5;
+mod lazy;
+mod lossy;
+mod markdown;
+mod ndjson;
+mod options;
+mod permissive;
+mod pretty;
+#[cfg(feature = "form_urlencoded")]
+mod query;
+mod schema;
+mod sniff;
+mod stream;
+mod table;
+mod transpose;
+#[cfg(feature = "ubjson")]
+mod ubjson;
+#[cfg(feature = "xml")]
+mod xml;
+#[cfg(feature = "serde_yaml")]
+mod yaml;
+
+pub use converter::{Converter, ConverterBuilder};
+pub use diff::value_diff;
+pub use error::ConvertError;
+pub use flatten::{flatten, flatten_escaped, unflatten, unflatten_escaped};
+pub use lazy::{convert_lazy, LazyConversions};
+#[cfg(feature = "flate2")]
+pub use gzip::{read_jsonl_gz, write_jsonl_gz_records};
+#[cfg(feature = "ubjson")]
+pub use ubjson::read_ubjson;
+pub use options::{
+    ConvertOptions, CsvEmpty, DuplicateHeaders, MarkdownObjectMode, NestedMode, Preprocessor, RaggedRows,
+    ScalarMode, SortOrder, UnknownFallback,
+};
+pub use ndjson::ndjson_records;
+pub use pretty::write_beautified;
+pub use schema::infer_schema;
+pub use sniff::{sniff_format, sniff_format_bytes, PeekReader};
+pub use stream::convert_stream;
+pub use transpose::transpose;
 
 /* ================= Public API ================= */
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum DataFormat {
     Unknown,
     Json,
     Ndjson,
+    /// Concatenated JSON documents with no delimiter.
+    JsonStream,
     Yaml,
     Toml,
     Csv,
     MarkdownTable,
+    Query,
+    Headers,
+    /// EDN / Clojure literal, read in permissive mode with the `edn` feature.
+    Edn,
+    /// XML document, with the `xml` feature.
+    Xml,
+    /// INI config: `[section]` headers over `key = value` lines.
+    Ini,
+    /// `.env` file: `KEY=value` lines. Lines TOML also reads (numbers,
+    /// booleans, quoted strings) detect as TOML with typed values; an
+    /// `export` prefix or a bare string value makes the input `.env`.
+    Dotenv,
+    /// Undetectable input wrapped as a single string (`ConvertOptions::wrap_as_string`).
+    Text,
+    /// Universal Binary JSON, as named in `read_ubjson` errors. Binary, so
+    /// never detected; as `ConvertOptions::input_format` nothing is accepted.
+    Ubjson,
+    /// Not a format: as `ConvertOptions::input_format` it means "run
+    /// detection". Never reported as a detected format.
+    Auto,
 }
 
 impl DataFormat {
+    /// Inverse of [`as_str`](Self::as_str); unrecognized names are `Unknown`.
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "json" => Self::Json,
+            "ndjson" => Self::Ndjson,
+            "json_stream" => Self::JsonStream,
+            "yaml" => Self::Yaml,
+            "toml" => Self::Toml,
+            "csv" => Self::Csv,
+            "markdown_table" => Self::MarkdownTable,
+            "query" => Self::Query,
+            "headers" => Self::Headers,
+            "edn" => Self::Edn,
+            "xml" => Self::Xml,
+            "ini" => Self::Ini,
+            "dotenv" => Self::Dotenv,
+            "text" => Self::Text,
+            "ubjson" => Self::Ubjson,
+            "auto" => Self::Auto,
+            _ => Self::Unknown,
+        }
+    }
+
     pub fn as_str(&self) -> &'static str {
         match self {
             Self::Unknown => "unknown",
             Self::Json => "json",
             Self::Ndjson => "ndjson",
+            Self::JsonStream => "json_stream",
             Self::Yaml => "yaml",
             Self::Toml => "toml",
             Self::Csv => "csv",
             Self::MarkdownTable => "markdown_table",
+            Self::Query => "query",
+            Self::Headers => "headers",
+            Self::Edn => "edn",
+            Self::Xml => "xml",
+            Self::Ini => "ini",
+            Self::Dotenv => "dotenv",
+            Self::Text => "text",
+            Self::Ubjson => "ubjson",
+            Self::Auto => "auto",
         }
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum TargetFormat {
     Json,
     Yaml,
     Toml,
     Csv,
     MarkdownTable,
+    Ndjson,
+    /// Gzip-compressed NDJSON; binary, so base64-encoded in the output map.
+    JsonlGz,
+    /// `KEY=value` lines from a flat object.
+    Dotenv,
+    /// Universal Binary JSON; binary, so base64-encoded in the output map.
+    Ubjson,
+    /// XML document from an object with a single root key.
+    Xml,
     Other(String),
 }
 
 impl TargetFormat {
+    /// Looks up a target by the name used in `ConvertOptions::targets`.
+    pub fn from_name(name: &str) -> Self {
+        to_target(name)
+    }
+
     pub fn name(&self) -> String {
         match self {
             TargetFormat::Json => "json".into(),
@@ -46,31 +175,628 @@ impl TargetFormat {
             TargetFormat::Toml => "toml".into(),
             TargetFormat::Csv => "csv".into(),
             TargetFormat::MarkdownTable => "markdown_table".into(),
+            TargetFormat::Ndjson => "ndjson".into(),
+            TargetFormat::JsonlGz => "jsonl.gz".into(),
+            TargetFormat::Dotenv => "dotenv".into(),
+            TargetFormat::Ubjson => "ubjson".into(),
+            TargetFormat::Xml => "xml".into(),
             TargetFormat::Other(s) => s.clone(),
         }
     }
+
+    /// Whether the target produces bytes rather than text.
+    pub fn is_binary(&self) -> bool {
+        matches!(self, TargetFormat::JsonlGz | TargetFormat::Ubjson)
+    }
+}
+
+/// The crate version (`CARGO_PKG_VERSION`).
+pub fn version() -> &'static str {
+    env!("CARGO_PKG_VERSION")
+}
+
+/// Target names this build can produce; feature-gated ones are listed only
+/// when their feature is compiled in.
+pub fn enabled_formats() -> Vec<&'static str> {
+    let mut formats = vec!["json", "ndjson", "markdown_table", "table", "headers", "flat", "json5", "dotenv", "bundle"];
+    if cfg!(feature = "serde_yaml") {
+        formats.push("yaml");
+    }
+    if cfg!(feature = "toml") {
+        formats.push("toml");
+    }
+    if cfg!(feature = "csv") {
+        formats.push("csv");
+    }
+    if cfg!(feature = "form_urlencoded") {
+        formats.push("query");
+    }
+    if cfg!(feature = "flate2") {
+        formats.push("jsonl.gz");
+    }
+    if cfg!(feature = "ubjson") {
+        formats.push("ubjson");
+    }
+    if cfg!(feature = "xml") {
+        formats.push("xml");
+    }
+    formats
 }
 
 /// Main conversion map. Always returns keys:
 /// - "Format", "Original", "Beautified", "normal"
 /// - Plus one key per requested target format.
-/// If `targets` is None => return **all** formats.
+///
+/// If `targets` is None => return **all** formats that fit the value (CSV and
+/// markdown only for arrays).
 pub fn convert_map(
     input: &[u8],
     targets: Option<&[&str]>,
     allow_permissive: bool,
     max_bytes: Option<usize>,
 ) -> BTreeMap<String, Value> {
-    let mut buf = input.to_vec();
-    if let Some(n) = max_bytes {
-        if buf.len() > n {
-            buf.truncate(n);
+    let opts = ConvertOptions {
+        targets: targets.map(|list| list.iter().map(|s| s.to_string()).collect()),
+        allow_permissive,
+        max_bytes,
+        ..ConvertOptions::default()
+    };
+    convert_map_with(input, &opts)
+}
+
+/// Same as [`convert_map`], driven by a [`ConvertOptions`].
+///
+/// Target conversions that fail are emitted as `Null` and their reasons are
+/// collected under an `"Errors"` object keyed by target name. With
+/// `include_stats`, a `"Stats"` object reports how many targets were produced;
+/// with `include_warnings`, a `"Warnings"` array lists lossy conversions.
+///
+/// Gzip input (with the `flate2` feature) is decompressed first, so `.jsonl.gz`
+/// blobs convert like plain NDJSON; `max_bytes` then caps the inflated size.
+/// Without `max_bytes`, input that inflates past 256 MiB is an error.
+///
+/// When `max_bytes` cuts the input, `"Truncated": true` and `"OriginalBytes"`
+/// (the length of `input`) are added so partial data isn't mistaken for whole.
+/// For gzip input that is the compressed length: the inflated size isn't
+/// known without inflating past the cut.
+///
+/// With `include_meta`, `"SchemaVersion"` carries [`SCHEMA_VERSION`].
+///
+/// Under `allow_permissive`, NDJSON lines that aren't strict JSON (trailing
+/// commas, single quotes, comments) are read leniently instead of dropped,
+/// and `"RecoveredLines"` counts them.
+///
+/// Input no parser accepts is reported as `"Format": "unknown"` and handled
+/// per `unknown_fallback`.
+pub fn convert_map_with(input: &[u8], opts: &ConvertOptions) -> BTreeMap<String, Value> {
+    if let Some(limit) = opts.timeout {
+        return with_timeout(input, opts, limit, convert_map_with)
+            .unwrap_or_else(|| with_meta(error_map(&ConvertError::Timeout(limit)), opts));
+    }
+    with_meta(convert_bytes(input, opts), opts)
+}
+
+/// Same as [`convert_map`], but input that can't be converted is an error
+/// instead of a map with `"Format": "unknown"`.
+pub fn try_convert_map(
+    input: &[u8],
+    targets: Option<&[&str]>,
+    allow_permissive: bool,
+    max_bytes: Option<usize>,
+) -> Result<BTreeMap<String, Value>, ConvertError> {
+    let opts = ConvertOptions {
+        targets: targets.map(|list| list.iter().map(|s| s.to_string()).collect()),
+        allow_permissive,
+        max_bytes,
+        ..ConvertOptions::default()
+    };
+    try_convert_map_with(input, &opts)
+}
+
+/// Same as [`convert_map_with`], but failures are returned rather than folded
+/// into the map: decode errors and timeouts as their `ConvertError`, and
+/// input no parser accepts as `ConvertError::Unrecognized`, listing the
+/// formats tried and the JSON parser's complaint.
+pub fn try_convert_map_with(
+    input: &[u8],
+    opts: &ConvertOptions,
+) -> Result<BTreeMap<String, Value>, ConvertError> {
+    if let Some(limit) = opts.timeout {
+        return with_timeout(input, opts, limit, try_convert_map_with).unwrap_or(Err(ConvertError::Timeout(limit)));
+    }
+    let (map, truncated) = with_input_text(input, opts, |text| {
+        let map = convert_text(text, opts);
+        if detected_format(&map) == DataFormat::Unknown {
+            return Err(unrecognized(text, opts));
         }
+        Ok(map)
+    })?;
+    let mut map = map?;
+    if truncated {
+        mark_truncated(&mut map, input.len());
     }
+    Ok(with_meta(map, opts))
+}
 
-    let cleaned = strip_markdown_fences_bytes(&buf);
-    let original = String::from_utf8_lossy(&cleaned).to_string();
+fn unrecognized(text: &str, opts: &ConvertOptions) -> ConvertError {
+    let original = preprocess(text, opts);
+    let mut steps = Vec::new();
+    if !original.trim().is_empty() {
+        let _ = detect(&original, opts, Some(&mut steps));
+    }
+    let mut attempted: Vec<DataFormat> = steps
+        .iter()
+        .filter(|step| step.outcome == DetectionOutcome::Rejected)
+        .map(|step| step.format)
+        .collect();
+    attempted.dedup();
+    let cause = parse_error(skip_script_header(&original), &attempted, opts).map(Box::new);
+    ConvertError::Unrecognized { attempted, cause }
+}
 
+/// The error explaining best why `s` failed: a repeated CSV header under
+/// `DuplicateHeaders::Error`, JSON's when `s` opens with `{`, otherwise
+/// whichever of JSON, YAML and TOML got furthest before failing.
+fn parse_error(s: &str, attempted: &[DataFormat], opts: &ConvertOptions) -> Option<ConvertError> {
+    let tried = |format| attempted.contains(&format);
+    #[cfg(feature = "csv")]
+    if tried(DataFormat::Csv)
+        && opts.csv_duplicate_headers == DuplicateHeaders::Error
+        && let Some(err) = csv_header_error(s, opts)
+    {
+        return Some(err);
+    }
+    #[cfg(not(feature = "csv"))]
+    let _ = opts;
+    let mut errors = Vec::new();
+    if tried(DataFormat::Json)
+        && let Err(err) = serde_json::from_str::<Value>(s)
+    {
+        let err = ConvertError::from_json(DataFormat::Json, &err, 0);
+        if s.trim_start().starts_with('{') {
+            return Some(err);
+        }
+        errors.push(err);
+    }
+    #[cfg(feature = "serde_yaml")]
+    if tried(DataFormat::Yaml)
+        && let Err(err) = serde_yaml::from_str::<Value>(s)
+    {
+        errors.push(ConvertError::from_yaml(&err));
+    }
+    #[cfg(feature = "toml")]
+    if tried(DataFormat::Toml)
+        && let Err(err) = toml::from_str::<toml::Value>(s)
+    {
+        errors.push(ConvertError::from_toml(&err, s));
+    }
+    let position = |e: &ConvertError| match e {
+        ConvertError::Parse { line, column, .. } => (*line, *column),
+        _ => (0, 0),
+    };
+    // Ties go to the earlier parser.
+    errors.into_iter().reduce(|best, e| if position(&e) > position(&best) { e } else { best })
+}
+
+/// Runs `convert` on a worker thread and gives up after `limit`, returning
+/// `None`. The worker can't be killed; it finishes in the background and its
+/// result is dropped.
+fn with_timeout<I, T>(
+    input: &I,
+    opts: &ConvertOptions,
+    limit: std::time::Duration,
+    convert: fn(&I, &ConvertOptions) -> T,
+) -> Option<T>
+where
+    I: ToOwned + ?Sized + 'static,
+    I::Owned: Send,
+    T: Send + 'static,
+{
+    use std::borrow::Borrow;
+    let (tx, rx) = std::sync::mpsc::channel();
+    let worker_input = input.to_owned();
+    let worker_opts = ConvertOptions { timeout: None, ..opts.clone() };
+    let spawned = std::thread::Builder::new().name("llmkit-convert".into()).spawn(move || {
+        let _ = tx.send(convert(worker_input.borrow(), &worker_opts));
+    });
+    if spawned.is_err() {
+        // No threads available (e.g. wasm): run without a budget.
+        return Some(convert(input, &ConvertOptions { timeout: None, ..opts.clone() }));
+    }
+    rx.recv_timeout(limit).ok()
+}
+
+/// Parses `input` and returns just its `target` rendering (`"yaml"`, `"csv"`,
+/// ...), without the rest of the map. Binary targets come back
+/// base64-encoded. Unrecognized input is `ConvertError::Unrecognized`; a
+/// target that can't hold the value or isn't compiled in is
+/// `ConvertError::Unsupported`.
+pub fn convert_one(input: &[u8], target: &str, allow_permissive: bool) -> Result<String, ConvertError> {
+    use base64::Engine;
+    let opts = ConvertOptions { allow_permissive, ..ConvertOptions::default() };
+    let tgt = to_target(target);
+    let parsed = parse_to_value_with(input, &opts)?;
+    let bytes = convert_to_bytes(&parsed, &tgt, &opts)?;
+    if tgt.is_binary() {
+        return Ok(base64::engine::general_purpose::STANDARD.encode(bytes));
+    }
+    String::from_utf8(bytes).map_err(|_| ConvertError::Unsupported {
+        target: tgt.name(),
+        reason: "output is not valid UTF-8".into(),
+    })
+}
+
+/// The value `input` parses to under `opts`, before any target is rendered.
+/// Unrecognized input is `ConvertError::Unrecognized`.
+pub(crate) fn parse_to_value_with(input: &[u8], opts: &ConvertOptions) -> Result<Value, ConvertError> {
+    let (parsed, _) = with_input_text(input, opts, |text| {
+        let original = preprocess(text, opts);
+        match parse_prepared(&original, opts) {
+            Ok((val, _)) if !original.trim().is_empty() => Ok(val),
+            _ => Err(unrecognized(text, opts)),
+        }
+    })?;
+    parsed
+}
+
+/// Converts every input as [`convert_map_with`] would, sharing `opts` and the
+/// compiled fence patterns across the batch. With the `rayon` feature inputs
+/// are converted in parallel; results are always in input order.
+pub fn convert_batch(inputs: &[&[u8]], opts: &ConvertOptions) -> Vec<BTreeMap<String, Value>> {
+    #[cfg(feature = "rayon")]
+    {
+        use rayon::prelude::*;
+        inputs.par_iter().map(|input| convert_map_with(input, opts)).collect()
+    }
+    #[cfg(not(feature = "rayon"))]
+    {
+        inputs.iter().map(|input| convert_map_with(input, opts)).collect()
+    }
+}
+
+/// Version of the output map's shape, reported as `"SchemaVersion"` under
+/// `ConvertOptions::include_meta`. Bumped whenever keys are renamed, removed
+/// or change type; adding an optional key doesn't bump it.
+pub const SCHEMA_VERSION: u64 = 1;
+
+fn with_meta(mut map: BTreeMap<String, Value>, opts: &ConvertOptions) -> BTreeMap<String, Value> {
+    if opts.include_meta {
+        map.insert("SchemaVersion".into(), SCHEMA_VERSION.into());
+    }
+    map
+}
+
+fn convert_bytes(input: &[u8], opts: &ConvertOptions) -> BTreeMap<String, Value> {
+    convert_bytes_value(input, opts).map_or_else(|e| error_map(&e), |(map, _)| map)
+}
+
+/// [`convert_bytes`], also handing back the parsed value, so callers that
+/// want both don't parse twice. Decode errors are returned, not mapped.
+fn convert_bytes_value(
+    input: &[u8],
+    opts: &ConvertOptions,
+) -> Result<(BTreeMap<String, Value>, Option<Value>), ConvertError> {
+    let ((mut map, value), truncated) = with_input_text(input, opts, |text| convert_text_value(text, opts))?;
+    if truncated {
+        mark_truncated(&mut map, input.len());
+    }
+    Ok((map, value))
+}
+
+/// Decompresses (gzip), truncates to `max_bytes` and decodes `input`, then
+/// hands the text to `f`. Also reports whether `max_bytes` cut the input.
+fn with_input_text<T>(
+    input: &[u8],
+    opts: &ConvertOptions,
+    f: impl FnOnce(&str) -> T,
+) -> Result<(T, bool), ConvertError> {
+    #[cfg(feature = "flate2")]
+    if gzip::is_gzip(input)
+        && let Some((inflated, truncated)) = gzip::gunzip(input, opts.max_bytes)
+    {
+        if truncated && opts.max_bytes.is_none() {
+            return Err(ConvertError::Io(format!(
+                "gzip input inflates past {} bytes; set max_bytes to read more or a prefix",
+                gzip::MAX_INFLATED
+            )));
+        }
+        let text = if truncated { cut_at_char_boundary(&inflated, opts) } else { &inflated };
+        return Ok((f(&decode(text, opts)?), truncated));
+    }
+
+    let buf = match opts.max_bytes {
+        Some(n) if input.len() > n => cut_at_char_boundary(&input[..n], opts),
+        _ => input,
+    };
+    Ok((f(&decode(buf, opts)?), buf.len() < input.len()))
+}
+
+/// Drops a UTF-8 sequence that `max_bytes` cut in half, so valid input isn't
+/// rejected under `strict_utf8` (or given a trailing U+FFFD) for where the
+/// cut landed. Other encodings are left alone.
+pub(crate) fn cut_at_char_boundary<'a>(buf: &'a [u8], opts: &ConvertOptions) -> &'a [u8] {
+    if opts.input_encoding.as_deref().is_some_and(|label| !is_utf8_label(label)) {
+        return buf;
+    }
+    match std::str::from_utf8(buf) {
+        Err(e) if e.error_len().is_none() => &buf[..e.valid_up_to()],
+        _ => buf,
+    }
+}
+
+/// Bytes to text: lossy by default, or rejected with `ConvertError::Utf8`
+/// under `strict_utf8`.
+fn decode<'a>(buf: &'a [u8], opts: &ConvertOptions) -> Result<Cow<'a, str>, ConvertError> {
+    if let Some(label) = opts.input_encoding.as_deref()
+        && !is_utf8_label(label)
+    {
+        return decode_legacy(buf, label);
+    }
+    if opts.strict_utf8 {
+        return std::str::from_utf8(buf)
+            .map(Cow::Borrowed)
+            .map_err(|e| ConvertError::Utf8 { valid_up_to: e.valid_up_to() });
+    }
+    Ok(String::from_utf8_lossy(buf))
+}
+
+fn is_utf8_label(label: &str) -> bool {
+    matches!(label.trim().to_lowercase().as_str(), "utf-8" | "utf8")
+}
+
+/// Checks that `label` names an input encoding this build can decode
+/// (`utf-8` always; `latin1`, `windows-1252`, `utf-16le`, ... with the
+/// `encoding` feature).
+pub fn validate_encoding(label: &str) -> Result<(), ConvertError> {
+    if is_utf8_label(label) {
+        return Ok(());
+    }
+    #[cfg(feature = "encoding")]
+    {
+        encoding_rs::Encoding::for_label(label.trim().as_bytes())
+            .map(|_| ())
+            .ok_or_else(|| ConvertError::Encoding(format!("unknown encoding '{label}'")))
+    }
+    #[cfg(not(feature = "encoding"))]
+    {
+        Err(ConvertError::Encoding(format!("'{label}' requires the `encoding` feature")))
+    }
+}
+
+#[cfg(feature = "encoding")]
+fn decode_legacy<'a>(buf: &'a [u8], label: &str) -> Result<Cow<'a, str>, ConvertError> {
+    let enc = encoding_rs::Encoding::for_label(label.trim().as_bytes())
+        .ok_or_else(|| ConvertError::Encoding(format!("unknown encoding '{label}'")))?;
+    Ok(enc.decode(buf).0)
+}
+
+#[cfg(not(feature = "encoding"))]
+fn decode_legacy<'a>(_buf: &'a [u8], label: &str) -> Result<Cow<'a, str>, ConvertError> {
+    Err(ConvertError::Encoding(format!("'{label}' requires the `encoding` feature")))
+}
+
+/// Output for input rejected before detection: empty text fields, no
+/// conversions, and the reason under `"Error"`.
+fn error_map(e: &ConvertError) -> BTreeMap<String, Value> {
+    let mut out = BTreeMap::new();
+    out.insert("Format".into(), Value::String(DataFormat::Unknown.as_str().into()));
+    out.insert("Original".into(), Value::String(String::new()));
+    out.insert("Beautified".into(), Value::String(String::new()));
+    out.insert("normal".into(), Value::String(String::new()));
+    out.insert("Error".into(), Value::String(e.to_string()));
+    out
+}
+
+/// [`convert_map_with`] for input that is already text, skipping the UTF-8
+/// decode. `max_bytes` truncation backs off to the nearest char boundary.
+pub fn convert_str(input: &str, opts: &ConvertOptions) -> BTreeMap<String, Value> {
+    if let Some(limit) = opts.timeout {
+        return with_timeout(input, opts, limit, convert_str)
+            .unwrap_or_else(|| with_meta(error_map(&ConvertError::Timeout(limit)), opts));
+    }
+    let text = match opts.max_bytes {
+        Some(n) if input.len() > n => &input[..input.floor_char_boundary(n)],
+        _ => input,
+    };
+    let mut map = convert_text(text, opts);
+    if text.len() < input.len() {
+        mark_truncated(&mut map, input.len());
+    }
+    with_meta(map, opts)
+}
+
+/// The columns CSV output would use for `value`, in order, without rendering
+/// it: the first-seen union of keys across rows (after `sort_by`), or
+/// `col0..colN` for an array of arrays. `None` for anything else, or when
+/// `csv_max_columns` would reject the table.
+pub fn tabular_headers(value: &Value, opts: &ConvertOptions) -> Option<Vec<String>> {
+    let headers = table::csv_headers(&sorted_rows(value, opts))?;
+    match opts.csv_max_columns {
+        Some(limit) if headers.len() > limit => None,
+        _ => Some(headers),
+    }
+}
+
+/// The converted text for `target` in a [`convert_map`] result, or `None` if
+/// the target wasn't requested or couldn't be produced.
+pub fn get_conversion<'a>(map: &'a BTreeMap<String, Value>, target: &str) -> Option<&'a str> {
+    map.get(target).and_then(Value::as_str)
+}
+
+/// The format [`convert_map`] detected, read from the `"Format"` key.
+pub fn detected_format(map: &BTreeMap<String, Value>) -> DataFormat {
+    map.get("Format")
+        .and_then(Value::as_str)
+        .map_or(DataFormat::Unknown, DataFormat::from_name)
+}
+
+/// One parser attempt recorded by [`explain_detection`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DetectionStep {
+    /// Parser name, e.g. `json`, `python_repr`, `yaml`.
+    pub parser: &'static str,
+    /// The format reported if this parser accepts the input.
+    pub format: DataFormat,
+    pub outcome: DetectionOutcome,
+}
+
+/// How a [`DetectionStep`] ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DetectionOutcome {
+    /// The parser took the input; detection stopped here.
+    Accepted,
+    /// The parser ran and didn't take the input.
+    Rejected,
+    /// Skipped because the format is in `disabled_formats`, or
+    /// `input_format` forces a different one.
+    Disabled,
+    /// Skipped because it only runs with `allow_permissive`.
+    NeedsPermissive,
+}
+
+impl DetectionOutcome {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Accepted => "accepted",
+            Self::Rejected => "rejected",
+            Self::Disabled => "disabled",
+            Self::NeedsPermissive => "needs permissive",
+        }
+    }
+}
+
+/// The parser attempts [`convert_map_with`] makes on `input`, in order,
+/// ending with the accepted one (if any). Parsers whose feature isn't compiled
+/// in are left out. Input that is empty after preprocessing, or that fails to
+/// decode, yields no steps.
+pub fn explain_detection(input: &[u8], opts: &ConvertOptions) -> Vec<DetectionStep> {
+    #[cfg(feature = "flate2")]
+    let inflated = gzip::is_gzip(input).then(|| gzip::gunzip(input, opts.max_bytes)).flatten();
+    #[cfg(feature = "flate2")]
+    let input = inflated.as_ref().map_or(input, |(bytes, _)| bytes.as_slice());
+    let buf = match opts.max_bytes {
+        Some(n) if input.len() > n => &input[..n],
+        _ => input,
+    };
+    let mut steps = Vec::new();
+    if let Ok(text) = decode(buf, opts) {
+        let original = preprocess(&text, opts);
+        if !original.trim().is_empty() {
+            let _ = detect(&original, opts, Some(&mut steps));
+        }
+    }
+    steps
+}
+
+/// Runs only detection on `input` (after the same decoding and fence
+/// stripping as [`convert_map`]) and returns the format, skipping every
+/// target conversion. `Unknown` if nothing accepts it.
+pub fn detect_format(input: &[u8], allow_permissive: bool) -> DataFormat {
+    detect_format_with(input, &ConvertOptions { allow_permissive, ..ConvertOptions::default() })
+}
+
+/// [`detect_format`] with explicit options.
+pub fn detect_format_with(input: &[u8], opts: &ConvertOptions) -> DataFormat {
+    explain_detection(input, opts)
+        .into_iter()
+        .find(|step| step.outcome == DetectionOutcome::Accepted)
+        .map_or(DataFormat::Unknown, |step| step.format)
+}
+
+/// `original_len` is the length of the input as given, compressed or not.
+fn mark_truncated(map: &mut BTreeMap<String, Value>, original_len: usize) {
+    map.insert("Truncated".into(), Value::Bool(true));
+    map.insert("OriginalBytes".into(), original_len.into());
+}
+
+/// Applies the BOM/hook/fence/whitespace preprocessing selected in `opts`.
+fn preprocess(text: &str, opts: &ConvertOptions) -> String {
+    let mut text = text;
+    if opts.strip_bom {
+        text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    }
+    let rewritten;
+    if let Some(hook) = &opts.preprocessor {
+        rewritten = hook.apply(text);
+        text = &rewritten;
+    }
+    let mut original = if opts.strip_fences {
+        strip_markdown_fences(text)
+    } else {
+        text.to_string()
+    };
+    if opts.trim_whitespace {
+        original = original.trim().to_string();
+    }
+    original
+}
+
+/// Detects and parses preprocessed input, applying `wrap_as_string` and
+/// `coerce_scalars`.
+fn parse_prepared(original: &str, opts: &ConvertOptions) -> Result<(Value, DataFormat), ()> {
+    let (mut val, detected) = parse_to_value(original, opts).or_else(|e| {
+        match &opts.wrap_as_string {
+            Some(key) => {
+                let mut wrapper = Map::new();
+                wrapper.insert(key.clone(), Value::String(original.to_string()));
+                Ok((Value::Object(wrapper), DataFormat::Text))
+            }
+            None => Err(e),
+        }
+    })?;
+    if opts.coerce_scalars && matches!(detected, DataFormat::Csv | DataFormat::MarkdownTable) {
+        table::coerce_rows(&mut val);
+    }
+    if rewrites_value(opts) {
+        rewrite_value(&mut val, opts);
+    }
+    Ok((val, detected))
+}
+
+/// Whether `opts` turns on any of the rewrites in [`rewrite_value`]. The
+/// rewrites only run when this says so, and [`convert_stream`] buffers the
+/// whole input when it does, so a new rewrite left out here has no effect
+/// anywhere rather than being skipped only while streaming.
+pub(crate) fn rewrites_value(opts: &ConvertOptions) -> bool {
+    opts.unquote_numbers
+        || opts.geojson_flatten
+        || opts.transpose
+        || opts.datetime_format.is_some()
+        || opts.sort_keys
+}
+
+/// Rewrites that look at the whole parsed value, not one record at a time.
+fn rewrite_value(val: &mut Value, opts: &ConvertOptions) {
+    if opts.unquote_numbers {
+        table::unquote_numbers(val);
+    }
+    if opts.geojson_flatten
+        && let Some(rows) = table::geojson_rows(val)
+    {
+        *val = rows;
+    }
+    if opts.transpose
+        && let Ok(swapped) = transpose::transpose(val)
+    {
+        *val = swapped;
+    }
+    #[cfg(feature = "chrono")]
+    if let Some(pattern) = &opts.datetime_format {
+        datetime::normalize_datetimes(val, pattern, opts.datetime_timezone.as_deref());
+    }
+    if opts.sort_keys {
+        val.sort_all_objects();
+    }
+}
+
+fn convert_text(text: &str, opts: &ConvertOptions) -> BTreeMap<String, Value> {
+    convert_text_value(text, opts).0
+}
+
+/// [`convert_text`], also handing back the parsed value (`None` when no
+/// parser accepted the text).
+fn convert_text_value(text: &str, opts: &ConvertOptions) -> (BTreeMap<String, Value>, Option<Value>) {
+    let original = preprocess(text, opts);
     let mut out = Map::new();
 
     if original.trim().is_empty() {
@@ -78,53 +804,135 @@ pub fn convert_map(
         out.insert("Original".into(), Value::String(String::new()));
         out.insert("Beautified".into(), Value::String(String::new()));
         out.insert("normal".into(), Value::String(String::new()));
-        return out.into_iter().collect();
+        if opts.unknown_fallback == UnknownFallback::Error {
+            let e = ConvertError::Unrecognized { attempted: Vec::new(), cause: None };
+            out.insert("Error".into(), Value::String(e.to_string()));
+        }
+        return (out.into_iter().collect(), None);
     }
 
-    match parse_to_value(cleaned.as_slice(), allow_permissive) {
+    match parse_prepared(&original, opts) {
         Ok((val, detected)) => {
             out.insert("Format".into(), Value::String(detected.as_str().into()));
             out.insert("Original".into(), Value::String(original.clone()));
+            if detected == DataFormat::Ndjson && opts.allow_permissive {
+                let recovered = recovered_ndjson_lines(&original, opts);
+                if recovered > 0 {
+                    out.insert("RecoveredLines".into(), recovered.into());
+                }
+            }
 
             // Pretty & compact JSON versions
-            let pretty = serde_json::to_string_pretty(&val).unwrap_or_else(|_| val.to_string());
-            let normal = serde_json::to_string(&val).unwrap_or_else(|_| val.to_string());
+            let pretty = match opts.include_beautified {
+                true => native_pretty(&val, detected, opts).unwrap_or_else(|| json_string(&val, true, opts)),
+                false => String::new(),
+            };
+            let normal = json_string(&val, false, opts);
             out.insert("Beautified".into(), Value::String(pretty));
             out.insert("normal".into(), Value::String(normal));
 
-            let targets = match targets {
+            let targets = match &opts.targets {
                 Some(list) => list.iter().map(|s| to_target(s)).collect::<Vec<_>>(),
-                None => default_targets(),
+                None => default_targets(&val, opts),
             };
 
-            let converted = convert_value_to_formats_with_targets(&val, &targets);
-            for (k, v) in converted {
-                out.insert(k, v);
+            let mut errors = Map::new();
+            let mut warnings = Vec::new();
+            // Row numbers in warnings follow the order tabular targets write.
+            let rows = match opts.include_warnings {
+                true => sorted_rows(&val, opts),
+                false => Cow::Borrowed(&val),
+            };
+            for (tgt, result) in targets.iter().zip(convert_targets(&val, &targets, opts)) {
+                let converted = result.unwrap_or_else(|e| {
+                    errors.insert(tgt.name(), Value::String(e.to_string()));
+                    Value::Null
+                });
+                if opts.include_warnings && !converted.is_null() {
+                    let checked = if is_tabular(tgt) { rows.as_ref() } else { &val };
+                    warnings.extend(lossy::lossy_warnings(checked, tgt).into_iter().map(Value::String));
+                }
+                out.insert(tgt.name(), converted);
+            }
+            if opts.preserve_comments && detected == DataFormat::Json {
+                comments::annotate_targets(&mut out, &original);
+            }
+            if !errors.is_empty() {
+                out.insert("Errors".into(), Value::Object(errors));
+            }
+            if opts.include_warnings {
+                out.insert("Warnings".into(), Value::Array(warnings));
+            }
+            if opts.include_stats {
+                let produced = targets
+                    .iter()
+                    .filter(|t| out.get(&t.name()).is_some_and(|v| !v.is_null()))
+                    .count();
+                let mut stats = Map::new();
+                stats.insert("ConversionCount".into(), produced.into());
+                stats.insert("TargetCount".into(), targets.len().into());
+                out.insert("Stats".into(), Value::Object(stats));
             }
 
-            out.into_iter().collect()
+            (out.into_iter().collect(), Some(val))
         }
         Err(_) => {
             out.insert("Format".into(), Value::String(DataFormat::Unknown.as_str().into()));
             out.insert("Original".into(), Value::String(original.clone()));
-            out.insert("Beautified".into(), Value::String(original.clone()));
-            out.insert("normal".into(), Value::String(original));
-            out.into_iter().collect()
+            let echo = if opts.unknown_fallback == UnknownFallback::Echo { original } else { String::new() };
+            let pretty = if opts.include_beautified { echo.clone() } else { String::new() };
+            out.insert("Beautified".into(), Value::String(pretty));
+            out.insert("normal".into(), Value::String(echo));
+            if opts.unknown_fallback == UnknownFallback::Error {
+                out.insert("Error".into(), Value::String(unrecognized(text, opts).to_string()));
+            }
+            (out.into_iter().collect(), None)
         }
     }
 }
 
+/// `val` rendered in its source format for `beautified_native`; `None` for
+/// formats without a writer (or that fail to render), which fall back to JSON.
+fn native_pretty(val: &Value, detected: DataFormat, opts: &ConvertOptions) -> Option<String> {
+    if !opts.beautified_native {
+        return None;
+    }
+    let target = match detected {
+        DataFormat::Yaml => TargetFormat::Yaml,
+        DataFormat::Toml => TargetFormat::Toml,
+        DataFormat::Csv => TargetFormat::Csv,
+        DataFormat::MarkdownTable => TargetFormat::MarkdownTable,
+        DataFormat::Ndjson => TargetFormat::Ndjson,
+        DataFormat::Dotenv => TargetFormat::Dotenv,
+        DataFormat::Headers => TargetFormat::Other("headers".into()),
+        DataFormat::Query => TargetFormat::Other("query".into()),
+        _ => return None,
+    };
+    let bytes = render_target(val, &target, opts).ok()??;
+    String::from_utf8(bytes).ok()
+}
+
 /* ================= Helpers ================= */
 
-fn strip_markdown_fences_bytes(input: &[u8]) -> Vec<u8> {
-    use regex::Regex;
-    let s = String::from_utf8_lossy(input);
-    let re_block = Regex::new(r"(?is)```(?:[a-zA-Z0-9_+\-]+)?\s*(.*?)\s*```").unwrap();
-    if let Some(cap) = re_block.captures(&s) {
-        return cap.get(1).unwrap().as_str().as_bytes().to_vec();
+/// Compiles `pattern` once; a pattern that fails to compile disables the
+/// step that uses it rather than panicking.
+fn cached_regex(cell: &'static OnceLock<Option<Regex>>, pattern: &str) -> Option<&'static Regex> {
+    cell.get_or_init(|| Regex::new(pattern).ok()).as_ref()
+}
+
+fn strip_markdown_fences(s: &str) -> String {
+    static BLOCK: OnceLock<Option<Regex>> = OnceLock::new();
+    static INLINE: OnceLock<Option<Regex>> = OnceLock::new();
+    if let Some(body) = cached_regex(&BLOCK, r"(?is)```(?:[a-zA-Z0-9_+\-]+)?\s*(.*?)\s*```")
+        .and_then(|re| re.captures(s))
+        .and_then(|cap| cap.get(1))
+    {
+        return body.as_str().to_string();
+    }
+    match cached_regex(&INLINE, r"`([^`]*)`") {
+        Some(re) => re.replace_all(s, "$1").into_owned(),
+        None => s.to_string(),
     }
-    let re_inline = Regex::new(r"`([^`]*)`").unwrap();
-    re_inline.replace_all(&s, "$1").as_bytes().to_vec()
 }
 
 fn to_target(s: &str) -> TargetFormat {
@@ -134,91 +942,526 @@ fn to_target(s: &str) -> TargetFormat {
         "toml" => TargetFormat::Toml,
         "csv" => TargetFormat::Csv,
         "markdown_table" | "md" => TargetFormat::MarkdownTable,
+        "ndjson" | "jsonl" => TargetFormat::Ndjson,
+        "jsonl.gz" => TargetFormat::JsonlGz,
+        "dotenv" | "env" => TargetFormat::Dotenv,
+        "ubjson" | "ubj" => TargetFormat::Ubjson,
+        "xml" => TargetFormat::Xml,
         other => TargetFormat::Other(other.to_string()),
     }
 }
 
-fn default_targets() -> Vec<TargetFormat> {
-    vec![
-        TargetFormat::Json,
-        TargetFormat::Yaml,
-        TargetFormat::Toml,
-        TargetFormat::Csv,
-        TargetFormat::MarkdownTable,
-    ]
+/// Maps a file extension (with or without the leading dot, any case) to the
+/// target that writes it, e.g. `yml` => Yaml, `md` => MarkdownTable,
+/// `jsonl` => Ndjson. Unknown extensions return `None`.
+pub fn target_from_extension(ext: &str) -> Option<TargetFormat> {
+    let ext = ext.trim().trim_start_matches('.').to_lowercase();
+    Some(match ext.as_str() {
+        "json" => TargetFormat::Json,
+        "yaml" | "yml" => TargetFormat::Yaml,
+        "toml" => TargetFormat::Toml,
+        "csv" => TargetFormat::Csv,
+        "md" | "markdown" => TargetFormat::MarkdownTable,
+        "jsonl" | "ndjson" => TargetFormat::Ndjson,
+        "jsonl.gz" | "ndjson.gz" => TargetFormat::JsonlGz,
+        "env" => TargetFormat::Dotenv,
+        "ubj" | "ubjson" => TargetFormat::Ubjson,
+        "xml" => TargetFormat::Xml,
+        _ => return None,
+    })
 }
 
-fn parse_to_value(input: &[u8], _allow_permissive: bool) -> Result<(Value, DataFormat), ()> {
-    let s = String::from_utf8_lossy(input).to_string();
+/// Maps a MIME type such as `application/json` or `text/csv; charset=utf-8`
+/// (any case, parameters ignored) to the format it names. Structured-syntax
+/// suffixes count too: `application/ld+json` is JSON, `image/svg+xml` XML.
+/// Unknown types return `None`.
+pub fn format_from_mime(mime: &str) -> Option<DataFormat> {
+    let essence = mime.split(';').next().unwrap_or_default().trim().to_lowercase();
+    let (_, subtype) = essence.split_once('/')?;
+    Some(match subtype {
+        "json" => DataFormat::Json,
+        "x-ndjson" | "ndjson" | "jsonl" | "x-jsonlines" | "jsonlines" => DataFormat::Ndjson,
+        "yaml" | "x-yaml" => DataFormat::Yaml,
+        "toml" | "x-toml" => DataFormat::Toml,
+        "csv" => DataFormat::Csv,
+        "markdown" | "x-markdown" => DataFormat::MarkdownTable,
+        "x-www-form-urlencoded" => DataFormat::Query,
+        "xml" => DataFormat::Xml,
+        "edn" => DataFormat::Edn,
+        _ if subtype.ends_with("+json") => DataFormat::Json,
+        _ if subtype.ends_with("+xml") => DataFormat::Xml,
+        _ if subtype.ends_with("+yaml") => DataFormat::Yaml,
+        _ => return None,
+    })
+}
 
-    // JSON
-    if let Ok(v) = serde_json::from_str::<Value>(&s) {
-        return Ok((v, DataFormat::Json));
+/// Targets that can only hold an object or array, subject to `scalar_mode`.
+fn needs_container(tgt: &TargetFormat) -> bool {
+    match tgt {
+        TargetFormat::Toml
+        | TargetFormat::Csv
+        | TargetFormat::MarkdownTable
+        | TargetFormat::Dotenv
+        | TargetFormat::Xml => true,
+        TargetFormat::Other(name) => matches!(name.as_str(), "table" | "query"),
+        _ => false,
     }
+}
 
-    // NDJSON
-    if s.lines().count() > 1 {
-        let mut arr = Vec::new();
-        for line in s.lines() {
-            if let Ok(v) = serde_json::from_str::<Value>(line) {
-                arr.push(v);
+/// Targets used when none are requested. Tabular targets are skipped for
+/// values that aren't arrays (they would only ever be `Null`), unless markdown
+/// is set to render objects as sections.
+fn default_targets(v: &Value, opts: &ConvertOptions) -> Vec<TargetFormat> {
+    let mut targets = vec![TargetFormat::Json, TargetFormat::Yaml, TargetFormat::Toml];
+    if v.is_array() {
+        targets.push(TargetFormat::Csv);
+    }
+    if v.is_array() || (v.is_object() && opts.markdown_object_mode == MarkdownObjectMode::Sections) {
+        targets.push(TargetFormat::MarkdownTable);
+    }
+    targets
+}
+
+/// Skips a leading `#!` line, plus `# ...` comment lines when JSON follows
+/// them (JSON has no comments). Comments ahead of YAML or TOML are left for
+/// those parsers, which understand them.
+fn skip_script_header(s: &str) -> &str {
+    let next_line = |t: &str| t.find('\n').map_or(t.len(), |i| i + 1);
+    let mut rest = s;
+    if rest.starts_with("#!") {
+        rest = &rest[next_line(rest)..];
+    }
+    let mut probe = rest.trim_start();
+    while probe.starts_with('#') {
+        probe = probe[next_line(probe)..].trim_start();
+    }
+    if probe.starts_with(['{', '[']) { probe } else { rest }
+}
+
+fn parse_to_value(s: &str, opts: &ConvertOptions) -> Result<(Value, DataFormat), ()> {
+    detect(s, opts, None)
+}
+
+/// Detection for `s`: the `mime_hint` format first, when it names one and no
+/// `input_format` is forced, then the full cascade.
+fn detect(
+    s: &str,
+    opts: &ConvertOptions,
+    mut trace: Option<&mut Vec<DetectionStep>>,
+) -> Result<(Value, DataFormat), ()> {
+    if opts.input_format == DataFormat::Auto
+        && let Some(hint) = opts.mime_hint.as_deref().and_then(format_from_mime)
+    {
+        let hinted = ConvertOptions { input_format: hint, mime_hint: None, ..opts.clone() };
+        let mut steps = Vec::new();
+        let found = detect_cascade(s, &hinted, Some(&mut steps));
+        if let Some(trace) = trace.as_deref_mut() {
+            // Only the hinted parsers ran; the rest are noise here.
+            trace.extend(steps.into_iter().filter(|step| step.outcome != DetectionOutcome::Disabled));
+        }
+        if found.is_ok() {
+            return found;
+        }
+    }
+    detect_cascade(s, opts, trace)
+}
+
+/// The detection cascade. Each parser is tried in order and the first to
+/// accept `s` wins; with `trace`, every attempt is recorded.
+fn detect_cascade(
+    s: &str,
+    opts: &ConvertOptions,
+    mut trace: Option<&mut Vec<DetectionStep>>,
+) -> Result<(Value, DataFormat), ()> {
+    let s = skip_script_header(s);
+    let mut attempt = |parser: &'static str, format: DataFormat, permissive: bool, parse: &dyn Fn() -> Option<Value>| {
+        let forced_elsewhere = opts.input_format != DataFormat::Auto && opts.input_format != format;
+        let (outcome, value) = if forced_elsewhere || opts.disabled_formats.contains(&format) {
+            (DetectionOutcome::Disabled, None)
+        } else if permissive && !opts.allow_permissive {
+            (DetectionOutcome::NeedsPermissive, None)
+        } else {
+            match parse() {
+                Some(v) => (DetectionOutcome::Accepted, Some((v, format))),
+                None => (DetectionOutcome::Rejected, None),
             }
+        };
+        if let Some(steps) = trace.as_deref_mut() {
+            steps.push(DetectionStep { parser, format, outcome });
         }
-        if !arr.is_empty() {
-            return Ok((Value::Array(arr), DataFormat::Ndjson));
+        value
+    };
+
+    // JSON, past serde_json's recursion limit when a document is that deep
+    let json = || match serde_json::from_str(s) {
+        Ok(v) => Some(v),
+        Err(e) if e.to_string().starts_with("recursion limit exceeded") => deep::from_str_deep(s),
+        Err(_) => None,
+    };
+    if let Some(found) = attempt("json", DataFormat::Json, false, &json) {
+        return Ok(found);
+    }
+
+    // JSONC: JSON with `//` and `/* */` comments (permissive only)
+    let jsonc = || permissive::strip_json_comments(s).and_then(|json| serde_json::from_str(&json).ok());
+    if let Some(found) = attempt("jsonc", DataFormat::Json, true, &jsonc) {
+        return Ok(found);
+    }
+
+    // JSON5: unquoted keys, single quotes, trailing commas (permissive only)
+    #[cfg(feature = "json5")]
+    if let Some(found) = attempt("json5", DataFormat::Json, true, &|| ::json5::from_str(s).ok()) {
+        return Ok(found);
+    }
+
+    // Python repr (permissive only)
+    let python = || permissive::python_repr_to_json(s).and_then(|json| serde_json::from_str(&json).ok());
+    if let Some(found) = attempt("python_repr", DataFormat::Json, true, &python) {
+        return Ok(found);
+    }
+
+    // EDN / Clojure maps (permissive only)
+    #[cfg(feature = "edn")]
+    if let Some(found) = attempt("edn", DataFormat::Edn, true, &|| permissive::edn_to_value(s)) {
+        return Ok(found);
+    }
+
+    // NDJSON
+    let ndjson = || {
+        // Blank lines (trailing newlines, spacing between records) aren't records.
+        let lines = || s.lines().filter(|line| !line.trim().is_empty());
+        if lines().count() < 2 {
+            return None;
         }
+        let arr: Vec<Value> = lines().filter_map(|line| ndjson_line(line, opts.allow_permissive).map(|(v, _)| v)).collect();
+        (!arr.is_empty()).then_some(Value::Array(arr))
+    };
+    if let Some(found) = attempt("ndjson", DataFormat::Ndjson, false, &ndjson) {
+        return Ok(found);
+    }
+
+    // Concatenated JSON documents (`{"a":1}{"b":2}`)
+    if let Some(found) = attempt("json_stream", DataFormat::JsonStream, false, &|| json_stream_to_array(s)) {
+        return Ok(found);
+    }
+
+    // Query string (before YAML, which would take it as a bare scalar)
+    #[cfg(feature = "form_urlencoded")]
+    if let Some(found) = attempt("query", DataFormat::Query, false, &|| query::parse_query(s)) {
+        return Ok(found);
+    }
+
+    // Header block (before YAML, which accepts any `key: value` lines)
+    if let Some(found) = attempt("headers", DataFormat::Headers, false, &|| headers::parse_headers(s)) {
+        return Ok(found);
     }
 
     // YAML
     #[cfg(feature = "serde_yaml")]
-    if let Ok(v) = serde_yaml::from_str::<Value>(&s) {
-        return Ok((v, DataFormat::Yaml));
+    {
+        let yaml = || {
+            serde_yaml::from_str::<Value>(s)
+                .ok()
+                .filter(|v| !(v.is_string() && (opts.reject_plain_yaml || plain_yaml_yields(s))))
+        };
+        if let Some(found) = attempt("yaml", DataFormat::Yaml, false, &yaml) {
+            return Ok(found);
+        }
     }
 
     // TOML
     #[cfg(feature = "toml")]
-    if let Ok(tv) = toml::from_str::<toml::Value>(&s) {
-        if let Ok(jv) = serde_json::to_value(tv) {
-            return Ok((jv, DataFormat::Toml));
+    {
+        let toml = || {
+            let mut jv = serde_json::to_value(toml::from_str::<toml::Value>(s).ok()?).ok()?;
+            unwrap_toml_datetimes(&mut jv);
+            Some(jv)
+        };
+        if let Some(found) = attempt("toml", DataFormat::Toml, false, &toml) {
+            return Ok(found);
         }
     }
 
+    // INI (after TOML, which reads the stricter subset with typed values)
+    if let Some(found) = attempt("ini", DataFormat::Ini, false, &|| ini::parse_ini(s)) {
+        return Ok(found);
+    }
+
+    // .env (after TOML, which reads `KEY=5432` and `KEY="value"` lines with
+    // types; `export` or a bare `KEY=value` string is left for this step)
+    if let Some(found) = attempt("dotenv", DataFormat::Dotenv, false, &|| dotenv::parse_dotenv(s)) {
+        return Ok(found);
+    }
+
+    // XML (before CSV, so text with commas inside elements stays XML)
+    #[cfg(feature = "xml")]
+    if let Some(found) = attempt("xml", DataFormat::Xml, false, &|| xml::xml_to_value(s)) {
+        return Ok(found);
+    }
+
     // CSV
     #[cfg(feature = "csv")]
-    if s.contains(',') && s.contains('\n') {
-        if let Ok(v) = csv_to_json(&s) {
-            return Ok((v, DataFormat::Csv));
+    {
+        let csv = || {
+            let s = if opts.csv_stop_at_blank { leading_block(s) } else { s };
+            let delimiter = s.contains('\n').then(|| sniff_delimiter(s)).flatten()?;
+            csv_to_json(s, delimiter, opts).ok()
+        };
+        if let Some(found) = attempt("csv", DataFormat::Csv, false, &csv) {
+            return Ok(found);
         }
     }
 
     // Markdown table
-    if let Some(v) = markdown_table_to_json(&s) {
-        return Ok((v, DataFormat::MarkdownTable));
+    let markdown = || markdown_table_to_json(s, opts.markdown_stitch);
+    if let Some(found) = attempt("markdown_table", DataFormat::MarkdownTable, false, &markdown) {
+        return Ok(found);
     }
 
     Err(())
 }
 
+/// One NDJSON line as a record. With `allow_permissive`, a line that isn't
+/// strict JSON gets the JSONC, JSON5 and Python-repr readers before it is
+/// skipped; the flag reports such a recovery.
+pub(crate) fn ndjson_line(line: &str, allow_permissive: bool) -> Option<(Value, bool)> {
+    if let Ok(v) = serde_json::from_str(line) {
+        return Some((v, false));
+    }
+    if !allow_permissive {
+        return None;
+    }
+    let jsonc = || permissive::strip_json_comments(line).and_then(|json| serde_json::from_str(&json).ok());
+    #[cfg(feature = "json5")]
+    let jsonc = || jsonc().or_else(|| ::json5::from_str(line).ok());
+    let python = || permissive::python_repr_to_json(line).and_then(|json| serde_json::from_str(&json).ok());
+    jsonc().or_else(python).map(|v| (v, true))
+}
+
+/// How many records of NDJSON input `s` only parsed thanks to
+/// [`ndjson_line`]'s permissive fallbacks.
+pub(crate) fn recovered_ndjson_lines(s: &str, opts: &ConvertOptions) -> usize {
+    s.lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter(|line| ndjson_line(line, opts.allow_permissive).is_some_and(|(_, recovered)| recovered))
+        .count()
+}
+
+/// A document YAML would read as one plain string but which a later, more
+/// specific parser (INI, `.env`, XML) accepts; it is left for that parser.
+#[cfg(feature = "serde_yaml")]
+fn plain_yaml_yields(s: &str) -> bool {
+    #[cfg(feature = "xml")]
+    if xml::xml_to_value(s).is_some() {
+        return true;
+    }
+    ini::parse_ini(s).is_some() || dotenv::parse_dotenv(s).is_some()
+}
+
+/// toml serializes datetimes as `{"$__toml_private_datetime": "..."}`;
+/// replace those wrappers with the plain datetime string.
+#[cfg(feature = "toml")]
+fn unwrap_toml_datetimes(v: &mut Value) {
+    match v {
+        Value::Object(map) => {
+            if map.len() == 1
+                && let Some(Value::String(dt)) = map.get("$__toml_private_datetime")
+            {
+                *v = Value::String(dt.clone());
+                return;
+            }
+            map.values_mut().for_each(unwrap_toml_datetimes);
+        }
+        Value::Array(items) => items.iter_mut().for_each(unwrap_toml_datetimes),
+        _ => {}
+    }
+}
+
+/// Collects back-to-back JSON documents into an array. Requires at least two
+/// documents, all objects or arrays, consuming the whole input, so runs of
+/// bare numbers or words aren't mistaken for a stream.
+fn json_stream_to_array(s: &str) -> Option<Value> {
+    let values = serde_json::Deserializer::from_str(s)
+        .into_iter::<Value>()
+        .collect::<Result<Vec<_>, _>>()
+        .ok()?;
+    if values.len() < 2 || !values.iter().all(|v| v.is_object() || v.is_array()) {
+        return None;
+    }
+    Some(Value::Array(values))
+}
+
 #[cfg(feature = "csv")]
-fn csv_to_json(s: &str) -> Result<Value, ()> {
-    let mut rdr = csv::Reader::from_reader(s.as_bytes());
+fn csv_to_json(s: &str, delimiter: u8, opts: &ConvertOptions) -> Result<Value, ()> {
+    // A real CSV reader, so quoted cells keep their delimiters and newlines.
+    let mut rdr = csv::ReaderBuilder::new().delimiter(delimiter).from_reader(s.as_bytes());
+    // Column index paired with its key; dropped columns are left out.
+    let columns = csv_columns(rdr.headers().map_err(|_| ())?, opts.csv_duplicate_headers).map_err(drop)?;
     let mut arr = Vec::new();
-    for rec in rdr.deserialize::<serde_json::Value>() {
-        arr.push(rec.map_err(|_| ())?);
+    for rec in rdr.records() {
+        let rec = rec.map_err(|_| ())?;
+        let obj: Map<String, Value> = columns
+            .iter()
+            .filter_map(|(i, key)| {
+                let cell = match rec.get(*i)? {
+                    "" => match opts.csv_empty_as {
+                        CsvEmpty::EmptyString => Value::String(String::new()),
+                        CsvEmpty::Null => Value::Null,
+                        CsvEmpty::Omit => return None,
+                    },
+                    cell if opts.allow_permissive => table::infer_scalar(cell),
+                    cell => Value::String(cell.to_string()),
+                };
+                Some((key.clone(), cell))
+            })
+            .collect();
+        arr.push(Value::Object(obj));
     }
     Ok(Value::Array(arr))
 }
 
-fn markdown_table_to_json(s: &str) -> Option<Value> {
-    let lines: Vec<&str> = s.lines().collect();
-    let mut start: Option<usize> = None;
-    for i in 0..lines.len().saturating_sub(1) {
-        if lines[i].contains('|') && lines[i + 1].contains('-') {
-            start = Some(i);
+/// Why the header row of `s` was refused, read as the CSV step reads it.
+#[cfg(feature = "csv")]
+fn csv_header_error(s: &str, opts: &ConvertOptions) -> Option<ConvertError> {
+    let s = if opts.csv_stop_at_blank { leading_block(s) } else { s };
+    let delimiter = s.contains('\n').then(|| sniff_delimiter(s)).flatten()?;
+    let mut rdr = csv::ReaderBuilder::new().delimiter(delimiter).from_reader(s.as_bytes());
+    csv_columns(rdr.headers().ok()?, opts.csv_duplicate_headers).err()
+}
+
+/// Picks the delimiter of delimited text: the first of `,`, tab, `;` and `|`
+/// that splits every sampled record into the same number of columns (more
+/// than one). Delimiters inside quotes don't count. `|` is skipped for
+/// markdown tables, which have their own parser.
+#[cfg(feature = "csv")]
+fn sniff_delimiter(s: &str) -> Option<u8> {
+    const CANDIDATES: [u8; 4] = [b',', b'\t', b';', b'|'];
+    const SAMPLE: usize = 10;
+    let mut records: Vec<[usize; 4]> = Vec::new();
+    let (mut counts, mut quoted, mut blank) = ([0; 4], false, true);
+    for b in s.bytes() {
+        match b {
+            b'"' => quoted = !quoted,
+            b'\n' if !quoted => {
+                if !blank {
+                    records.push(counts);
+                    if records.len() == SAMPLE {
+                        break;
+                    }
+                }
+                (counts, blank) = ([0; 4], true);
+                continue;
+            }
+            _ if !quoted => {
+                if let Some(i) = CANDIDATES.iter().position(|&c| c == b) {
+                    counts[i] += 1;
+                }
+            }
+            _ => {}
+        }
+        blank &= b.is_ascii_whitespace();
+    }
+    if !blank && records.len() < SAMPLE {
+        records.push(counts);
+    }
+    let markdown = s.lines().take(SAMPLE).any(|line| is_table_separator(strip_table_prefix(line)));
+    let first = records.first()?;
+    (0..CANDIDATES.len())
+        .filter(|&i| !(markdown && CANDIDATES[i] == b'|'))
+        .find(|&i| first[i] > 0 && records.iter().all(|r| r[i] == first[i]))
+        .map(|i| CANDIDATES[i])
+}
+
+/// The lines of `s` before its first blank line, skipping leading blanks.
+/// Keeps the newline ending the last line so one-row tables stay tabular.
+#[cfg(feature = "csv")]
+fn leading_block(s: &str) -> &str {
+    let s = s.trim_start_matches(['\r', '\n']);
+    let mut end = 0;
+    for line in s.split_inclusive('\n') {
+        if line.trim().is_empty() {
             break;
         }
+        end += line.len();
+    }
+    &s[..end]
+}
+
+/// Resolves repeated header names per `duplicates`, so `id,id,name` doesn't
+/// silently lose a column. Under `DuplicateHeaders::Error` the first repeat
+/// is a `Parse` error naming it.
+#[cfg(feature = "csv")]
+fn csv_columns(
+    headers: &csv::StringRecord,
+    duplicates: DuplicateHeaders,
+) -> Result<Vec<(usize, String)>, ConvertError> {
+    let mut seen = std::collections::HashSet::new();
+    let mut columns = Vec::new();
+    for (i, name) in headers.iter().enumerate() {
+        if seen.insert(name.to_string()) {
+            columns.push((i, name.to_string()));
+            continue;
+        }
+        let repeated = || ConvertError::Parse {
+            format: DataFormat::Csv,
+            line: 1,
+            column: headers.iter().take(i).map(|h| h.len() + 1).sum::<usize>() + 1,
+            message: format!("duplicate header '{name}'"),
+        };
+        match duplicates {
+            DuplicateHeaders::Error => return Err(repeated()),
+            DuplicateHeaders::First => {}
+            DuplicateHeaders::Suffix => {
+                let key = (2..)
+                    .map(|n| format!("{name}_{n}"))
+                    .find(|key| !headers.iter().any(|h| h == key) && !seen.contains(key))
+                    .ok_or_else(repeated)?;
+                seen.insert(key.clone());
+                columns.push((i, key));
+            }
+        }
+    }
+    Ok(columns)
+}
+
+/// Strips indentation and blockquote markers (`> `, `> > `) from a line that
+/// may belong to a markdown table.
+fn strip_table_prefix(line: &str) -> &str {
+    let mut line = line.trim_start();
+    while let Some(rest) = line.strip_prefix('>') {
+        line = rest.trim_start();
+    }
+    line
+}
+
+/// Whether `line` is a header separator such as `| --- | :-: |`.
+fn is_table_separator(line: &str) -> bool {
+    line.contains('-') && line.chars().all(|c| matches!(c, '|' | '-' | ':' | ' ' | '\t'))
+}
+
+fn markdown_table_to_json(s: &str, stitch: bool) -> Option<Value> {
+    let lines: Vec<&str> = s.lines().map(strip_table_prefix).collect();
+    let (headers, mut rows, mut end) = next_markdown_table(&lines, 0)?;
+    // Tables split by prose or "..." continue while the header row repeats.
+    while stitch
+        && let Some((more_headers, more_rows, more_end)) = next_markdown_table(&lines, end)
+        && more_headers == headers
+    {
+        rows.extend(more_rows);
+        end = more_end;
+    }
+    if rows.is_empty() {
+        None
+    } else {
+        Some(Value::Array(rows))
     }
-    let idx = start?;
+}
+
+/// Finds the first table at or after `from`, returning its headers, rows and
+/// the index of the line after it.
+fn next_markdown_table(lines: &[&str], from: usize) -> Option<(Vec<String>, Vec<Value>, usize)> {
+    let idx = (from..lines.len().saturating_sub(1))
+        .find(|&i| lines[i].contains('|') && is_table_separator(lines[i + 1]))?;
     let headers: Vec<String> = lines[idx]
         .split('|')
         .map(|x| x.trim().to_string())
@@ -228,10 +1471,12 @@ fn markdown_table_to_json(s: &str) -> Option<Value> {
         return None;
     }
     let mut rows = Vec::new();
+    let mut end = idx + 2;
     for &line in &lines[idx + 2..] {
         if !line.contains('|') {
             break;
         }
+        end += 1;
         let cells: Vec<String> = line
             .split('|')
             .map(|x| x.trim().to_string())
@@ -242,98 +1487,448 @@ fn markdown_table_to_json(s: &str) -> Option<Value> {
         }
         let mut obj = serde_json::Map::new();
         for (k, v) in headers.iter().zip(cells.iter()) {
-            obj.insert(k.clone(), Value::String(v.clone()));
+            obj.insert(k.clone(), table::infer_scalar(v));
         }
         rows.push(Value::Object(obj));
     }
-    if rows.is_empty() {
-        None
-    } else {
-        Some(Value::Array(rows))
-    }
+    Some((headers, rows, end))
 }
 
 pub fn convert_value_to_formats_with_targets(
     v: &Value,
     targets: &[TargetFormat],
 ) -> BTreeMap<String, Value> {
-    let mut out = BTreeMap::<String, Value>::new();
-    for tgt in targets {
-        let key = tgt.name();
-        let val = match tgt {
-            TargetFormat::Json => serde_json::to_string_pretty(v).ok().map(Value::String),
-            TargetFormat::Yaml => {
-                #[cfg(feature = "serde_yaml")]
-                { serde_yaml::to_string(v).ok().map(Value::String) }
-                #[cfg(not(feature = "serde_yaml"))]
-                { None }
+    let opts = ConvertOptions::default();
+    targets
+        .iter()
+        .zip(convert_targets(v, targets, &opts))
+        .map(|(tgt, val)| (tgt.name(), val.unwrap_or(Value::Null)))
+        .collect()
+}
+
+/// Runs [`convert_value_to_target`] for each target, in parallel with the
+/// `rayon` feature. Results are in `targets` order either way.
+fn convert_targets(
+    v: &Value,
+    targets: &[TargetFormat],
+    opts: &ConvertOptions,
+) -> Vec<Result<Value, ConvertError>> {
+    #[cfg(feature = "rayon")]
+    {
+        use rayon::prelude::*;
+        targets.par_iter().map(|tgt| convert_value_to_target(v, tgt, opts)).collect()
+    }
+    #[cfg(not(feature = "rayon"))]
+    {
+        targets.iter().map(|tgt| convert_value_to_target(v, tgt, opts)).collect()
+    }
+}
+
+/// Converts `v` into a single target. Returns `Ok(Value::Null)` when the
+/// target can't represent the value or its feature is disabled, and an error
+/// when the conversion was attempted but aborted. Binary targets are returned
+/// base64-encoded.
+pub fn convert_value_to_target(
+    v: &Value,
+    tgt: &TargetFormat,
+    opts: &ConvertOptions,
+) -> Result<Value, ConvertError> {
+    use base64::Engine;
+    Ok(match render_target(v, tgt, opts)? {
+        Some(bytes) if tgt.is_binary() => {
+            Value::String(base64::engine::general_purpose::STANDARD.encode(bytes))
+        }
+        Some(bytes) => String::from_utf8(bytes).map(Value::String).unwrap_or(Value::Null),
+        None => Value::Null,
+    })
+}
+
+/// Converts `v` into the raw bytes of a single target, for binary targets
+/// such as `jsonl.gz` or for writing text targets straight to disk.
+pub fn convert_to_bytes(
+    v: &Value,
+    tgt: &TargetFormat,
+    opts: &ConvertOptions,
+) -> Result<Vec<u8>, ConvertError> {
+    render_target(v, tgt, opts)?.ok_or_else(|| ConvertError::Unsupported {
+        target: tgt.name(),
+        reason: "value not representable or feature disabled".into(),
+    })
+}
+
+fn render_target(
+    v: &Value,
+    tgt: &TargetFormat,
+    opts: &ConvertOptions,
+) -> Result<Option<Vec<u8>>, ConvertError> {
+    if !v.is_array() && !v.is_object() && needs_container(tgt) {
+        match opts.scalar_mode {
+            ScalarMode::Null => return Ok(None),
+            ScalarMode::Wrap => {
+                let mut wrapper = Map::new();
+                wrapper.insert("value".into(), v.clone());
+                let mut wrapped = Value::Object(wrapper);
+                // Tabular targets want rows, so the object becomes a one-row table.
+                if matches!(tgt, TargetFormat::Csv | TargetFormat::MarkdownTable)
+                    || *tgt == TargetFormat::Other("table".into())
+                {
+                    wrapped = Value::Array(vec![wrapped]);
+                }
+                return render_target(&wrapped, tgt, opts);
             }
-            TargetFormat::Toml => {
-                #[cfg(feature = "toml")]
-                { toml::to_string(v).ok().map(Value::String) }
-                #[cfg(not(feature = "toml"))]
-                { None }
+            ScalarMode::Error => {
+                return Err(ConvertError::Unsupported {
+                    target: tgt.name(),
+                    reason: "input is a bare scalar".into(),
+                });
             }
-            TargetFormat::Csv => {
-                #[cfg(feature = "csv")]
-                { json_to_csv_string(v).ok().map(Value::String) }
-                #[cfg(not(feature = "csv"))]
-                { None }
+        }
+    }
+    let sorted = if is_tabular(tgt) { sorted_rows(v, opts) } else { Cow::Borrowed(v) };
+    let v = sorted.as_ref();
+    let mut w = LimitedWriter::new(opts.max_output_bytes);
+    let rendered: Option<()> = match tgt {
+        TargetFormat::Json if opts.prefer_ndjson_for_arrays && v.is_array() => {
+            ndjson::write_ndjson(v, &mut w).ok()
+        }
+        TargetFormat::Json => write_json(v, true, opts, &mut w).ok(),
+        TargetFormat::Yaml => {
+            #[cfg(feature = "serde_yaml")]
+            {
+                if opts.yaml_inline_arrays {
+                    yaml::to_yaml_inline_arrays(v).and_then(|s| w.write_all(s.as_bytes()).ok())
+                } else {
+                    serde_yaml::to_writer(&mut w, v).ok()
+                }
             }
-            TargetFormat::MarkdownTable => None,
-            TargetFormat::Other(_) => None,
-        };
-        out.insert(key, val.unwrap_or(Value::Null));
+            #[cfg(not(feature = "serde_yaml"))]
+            { None }
+        }
+        TargetFormat::Toml => {
+            #[cfg(feature = "toml")]
+            {
+                let mut stripped = lossy::strip_nulls(v);
+                if let (Some(key), Value::Array(_)) = (&opts.toml_root_array_key, &stripped) {
+                    let mut root = Map::new();
+                    root.insert(key.clone(), stripped);
+                    stripped = Value::Object(root);
+                }
+                write_toml(&stripped, &mut w)
+            }
+            #[cfg(not(feature = "toml"))]
+            { None }
+        }
+        TargetFormat::Csv => {
+            #[cfg(feature = "csv")]
+            {
+                if opts.csv_ragged_rows == RaggedRows::Error
+                    && let Some(rows) = table::matrix_rows(v)
+                    && rows.iter().any(|row| row.len() != rows[0].len())
+                {
+                    return Err(ConvertError::Unsupported {
+                        target: "csv".into(),
+                        reason: "rows have different lengths".into(),
+                    });
+                }
+                if let Some(limit) = opts.csv_max_columns {
+                    let columns = table::csv_headers(v).map_or(0, |headers| headers.len());
+                    if columns > limit {
+                        return Err(table::too_many_columns(columns, limit));
+                    }
+                }
+                if opts.csv_write_bom {
+                    w.write_all("\u{feff}".as_bytes()).ok();
+                }
+                write_csv(v, &mut w).ok()
+            }
+            #[cfg(not(feature = "csv"))]
+            { None }
+        }
+        TargetFormat::MarkdownTable => match v {
+            Value::Object(obj) if opts.markdown_object_mode == MarkdownObjectMode::Sections => {
+                markdown::write_sections(obj, &mut w).ok()
+            }
+            _ => markdown::write_markdown_table(v, &opts.markdown_empty_cell, &mut w).and_then(Result::ok),
+        },
+        TargetFormat::Ndjson => ndjson::write_ndjson(v, &mut w).ok(),
+        TargetFormat::JsonlGz => {
+            #[cfg(feature = "flate2")]
+            { gzip::write_jsonl_gz(v, &mut w).ok() }
+            #[cfg(not(feature = "flate2"))]
+            { None }
+        }
+        TargetFormat::Dotenv => dotenv::to_dotenv(v, opts)?.and_then(|s| w.write_all(s.as_bytes()).ok()),
+        TargetFormat::Ubjson => {
+            #[cfg(feature = "ubjson")]
+            { w.write_all(&ubjson::to_ubjson(v)).ok() }
+            #[cfg(not(feature = "ubjson"))]
+            { None }
+        }
+        TargetFormat::Xml => {
+            #[cfg(feature = "xml")]
+            { xml::to_xml(v).and_then(|s| w.write_all(s.as_bytes()).ok()) }
+            #[cfg(not(feature = "xml"))]
+            { None }
+        }
+        TargetFormat::Other(name) => match name.as_str() {
+            #[cfg(feature = "form_urlencoded")]
+            "query" => query::to_query_string(v, opts.query_nested)?
+                .and_then(|s| w.write_all(s.as_bytes()).ok()),
+            "table" => table::to_ascii_table(v).and_then(|s| w.write_all(s.as_bytes()).ok()),
+            "flat" if opts.treat_dots_as_literal => {
+                write_json(&flatten::flatten_escaped(v, "."), true, opts, &mut w).ok()
+            }
+            "flat" => write_json(&flatten::flatten(v, "."), true, opts, &mut w).ok(),
+            "json5" => w.write_all(json5::to_json5(v).as_bytes()).ok(),
+            "bundle" => {
+                let mut bundle = Map::new();
+                bundle.insert("schema".into(), schema::infer_schema(v));
+                bundle.insert("data".into(), v.clone());
+                write_json(&Value::Object(bundle), true, opts, &mut w).ok()
+            }
+            "headers" => headers::to_headers(v)?.and_then(|s| w.write_all(s.as_bytes()).ok()),
+            _ => None,
+        },
+    };
+    if w.exceeded {
+        return Err(ConvertError::OutputTooLarge {
+            target: tgt.name(),
+            limit: opts.max_output_bytes.unwrap_or_default(),
+        });
     }
-    out
+    Ok(rendered.map(|()| w.buf))
+}
+
+fn is_tabular(tgt: &TargetFormat) -> bool {
+    matches!(tgt, TargetFormat::Csv | TargetFormat::MarkdownTable) || *tgt == TargetFormat::Other("table".into())
+}
+
+/// `v` with its rows ordered by `opts.sort_by`, as tabular targets render it.
+fn sorted_rows<'a>(v: &'a Value, opts: &ConvertOptions) -> Cow<'a, Value> {
+    match &opts.sort_by {
+        Some((column, order)) => table::sort_rows(v, column, *order).map_or(Cow::Borrowed(v), Cow::Owned),
+        None => Cow::Borrowed(v),
+    }
+}
+
+/// Writes `v` as JSON. Values nested deeper than `opts.iterative_json_depth`
+/// go through the heap-stack serializer so they can't overflow the stack.
+fn write_json<W: Write>(v: &Value, pretty: bool, opts: &ConvertOptions, w: &mut W) -> io::Result<()> {
+    let pad = match opts.indent_tabs {
+        true => "\t".to_string(),
+        false => " ".repeat(opts.indent),
+    };
+    if deep::value_depth(v) > opts.iterative_json_depth {
+        return deep::write_json_iterative(v, pretty, pad.as_bytes(), w);
+    }
+    if pretty {
+        let formatter = serde_json::ser::PrettyFormatter::with_indent(pad.as_bytes());
+        serde::Serialize::serialize(v, &mut serde_json::Serializer::with_formatter(w, formatter))?;
+    } else {
+        serde_json::to_writer(w, v)?;
+    }
+    Ok(())
+}
+
+fn json_string(v: &Value, pretty: bool, opts: &ConvertOptions) -> String {
+    let mut buf = Vec::new();
+    // Writing a Value into a Vec can't fail, and serde_json only emits UTF-8.
+    let _ = write_json(v, pretty, opts, &mut buf);
+    String::from_utf8(buf).unwrap_or_default()
+}
+
+/// In-memory sink that refuses writes past an optional byte limit, so a
+/// runaway serializer stops early instead of buffering the whole output.
+struct LimitedWriter {
+    buf: Vec<u8>,
+    limit: Option<usize>,
+    exceeded: bool,
+}
+
+impl LimitedWriter {
+    fn new(limit: Option<usize>) -> Self {
+        Self { buf: Vec::new(), limit, exceeded: false }
+    }
+}
+
+impl Write for LimitedWriter {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        if let Some(limit) = self.limit
+            && self.buf.len() + data.len() > limit
+        {
+            self.exceeded = true;
+            return Err(io::Error::other("output size limit exceeded"));
+        }
+        self.buf.extend_from_slice(data);
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Writes `v` as TOML a section at a time (the top-level values, then each
+/// table and each `[[array]]` entry), so `max_output_bytes` stops a huge
+/// document before all of it is rendered.
+#[cfg(feature = "toml")]
+fn write_toml<W: Write>(v: &Value, mut w: W) -> Option<()> {
+    let Value::Object(root) = v else {
+        return toml::to_string(v).ok().and_then(|s| w.write_all(s.as_bytes()).ok());
+    };
+    let is_section = |v: &Value| match v {
+        Value::Object(_) => true,
+        Value::Array(items) => !items.is_empty() && items.iter().all(Value::is_object),
+        _ => false,
+    };
+    let mut first = true;
+    let mut emit = |doc: Map<String, Value>| -> Option<()> {
+        let s = toml::to_string(&Value::Object(doc)).ok()?;
+        if s.is_empty() {
+            return Some(());
+        }
+        if !std::mem::take(&mut first) {
+            w.write_all(b"\n").ok()?;
+        }
+        w.write_all(s.as_bytes()).ok()
+    };
+    emit(root.iter().filter(|(_, v)| !is_section(v)).map(|(k, v)| (k.clone(), v.clone())).collect())?;
+    for (key, section) in root.iter().filter(|(_, v)| is_section(v)) {
+        match section {
+            Value::Array(items) => {
+                for item in items {
+                    emit(Map::from_iter([(key.clone(), Value::Array(vec![item.clone()]))]))?;
+                }
+            }
+            table => emit(Map::from_iter([(key.clone(), table.clone())]))?,
+        }
+    }
+    Some(())
 }
 
 #[cfg(feature = "csv")]
-fn json_to_csv_string(v: &Value) -> Result<String, String> {
-    let arr = v.as_array().ok_or_else(|| "CSV requires array of objects".to_string())?;
-    let mut headers = BTreeMap::<String, ()>::new();
-    for item in arr {
-        let obj = item.as_object().ok_or_else(|| "CSV requires array of objects".to_string())?;
-        for k in obj.keys() {
-            headers.insert(k.clone(), ());
-        }
-    }
-    let headers_vec: Vec<String> = headers.keys().cloned().collect();
-    let mut wtr = csv::Writer::from_writer(vec![]);
-    wtr.write_record(&headers_vec).map_err(|e| e.to_string())?;
-    for item in arr {
-        let obj = item.as_object().unwrap();
-        let row: Vec<String> = headers_vec.iter()
-            .map(|h| obj.get(h).map(|v| v.to_string()).unwrap_or_default())
+fn write_csv<W: Write>(v: &Value, w: W) -> Result<(), String> {
+    if let Some(rows) = table::matrix_rows(v) {
+        return write_csv_matrix(&rows, w);
+    }
+    let table::Table { rows, headers } =
+        table::Table::from_value(v).ok_or_else(|| "CSV requires array of objects".to_string())?;
+    let mut wtr = csv::Writer::from_writer(w);
+    wtr.write_record(&headers).map_err(|e| e.to_string())?;
+    for obj in rows {
+        let row: Vec<String> = headers.iter()
+            .map(|h| obj.get(h).map(table::cell_string).unwrap_or_default())
             .collect();
         wtr.write_record(&row).map_err(|e| e.to_string())?;
     }
-    let bytes = wtr.into_inner().map_err(|e| e.to_string())?;
-    String::from_utf8(bytes).map_err(|e| e.to_string())
+    wtr.flush().map_err(|e| e.to_string())
+}
+
+/// Writes an array of arrays under `col0..colN` headers sized to the widest
+/// row, padding shorter rows with empty cells.
+#[cfg(feature = "csv")]
+fn write_csv_matrix<W: Write>(rows: &[&Vec<Value>], w: W) -> Result<(), String> {
+    let width = rows.iter().map(|row| row.len()).max().unwrap_or(0);
+    let mut wtr = csv::Writer::from_writer(w);
+    wtr.write_record(table::matrix_headers(width)).map_err(|e| e.to_string())?;
+    for row in rows {
+        let cells = (0..width).map(|i| row.get(i).map(table::cell_string).unwrap_or_default());
+        wtr.write_record(cells).map_err(|e| e.to_string())?;
+    }
+    wtr.flush().map_err(|e| e.to_string())
 }
 
 /* ============== Python bindings (PyO3) ============== */
 
 #[cfg(feature = "python")]
-use pyo3::prelude::*;
+mod python {
+    use pyo3::exceptions::PyValueError;
+    use pyo3::prelude::*;
+    use pyo3::types::{PyBool, PyDict, PyList, PyString};
+    use serde_json::Value;
+
+    #[pymodule]
+    fn llmkit_py(m: &Bound<'_, PyModule>) -> PyResult<()> {
+        m.add_function(wrap_pyfunction!(convert_map, m)?)?;
+        m.add_function(wrap_pyfunction!(detect_format, m)?)?;
+        Ok(())
+    }
 
-#[cfg(feature = "python")]
-#[pymodule]
-fn llmkit_py(_py: Python, m: &PyModule) -> PyResult<()> {
     /// convert_map(input: bytes, targets: list[str] | None, allow_permissive: bool=False, max_input_bytes: int | None=None) -> dict
-    #[pyfn(m, "convert_map")]
-    fn convert_map_py(
-        _py: Python,
+    ///
+    /// `"value"` holds the parsed input as Python objects (`None` if unrecognized);
+    /// the per-format keys stay strings.
+    #[pyfunction]
+    #[pyo3(signature = (input, targets=None, allow_permissive=false, max_input_bytes=None))]
+    fn convert_map(
+        py: Python<'_>,
         input: &[u8],
         targets: Option<Vec<String>>,
         allow_permissive: bool,
         max_input_bytes: Option<usize>,
     ) -> PyResult<Py<PyAny>> {
-        let t_slices: Option<Vec<&str>> = targets.as_ref().map(|v| v.iter().map(|s| s.as_str()).collect());
-        let map = crate::convert_map(input, t_slices.as_deref(), allow_permissive, max_input_bytes);
-        Python::with_gil(|py| Ok(serde_json::to_value(&map).unwrap().into_py(py)))
+        let opts = crate::ConvertOptions {
+            targets,
+            allow_permissive,
+            max_bytes: max_input_bytes,
+            ..crate::ConvertOptions::default()
+        };
+        // One parse feeds both the map and `"value"`, the parsed input as real
+        // dicts/lists so callers needn't json.loads("normal").
+        let (map, value) =
+            crate::convert_bytes_value(input, &opts).map_err(|e| PyValueError::new_err(e.to_string()))?;
+        let dict = PyDict::new(py);
+        for (key, v) in &crate::with_meta(map, &opts) {
+            dict.set_item(key, to_py(py, v)?)?;
+        }
+        dict.set_item("value", to_py(py, &value.unwrap_or(Value::Null))?)?;
+        Ok(dict.into_any().unbind())
+    }
+
+    /// detect_format(input: bytes | str, allow_permissive: bool=False) -> str
+    #[pyfunction]
+    #[pyo3(signature = (input, allow_permissive=false))]
+    fn detect_format(input: &Bound<'_, PyAny>, allow_permissive: bool) -> PyResult<&'static str> {
+        // A str is read as its UTF-8 bytes.
+        let text;
+        let bytes: &[u8] = match input.extract::<String>() {
+            Ok(s) => {
+                text = s;
+                text.as_bytes()
+            }
+            Err(_) => input.extract()?,
+        };
+        Ok(crate::detect_format(bytes, allow_permissive).as_str())
+    }
+
+    /// A JSON value as the matching Python object: dict, list, str, int,
+    /// float, bool or None.
+    fn to_py<'py>(py: Python<'py>, v: &Value) -> PyResult<Bound<'py, PyAny>> {
+        Ok(match v {
+            Value::Null => py.None().into_bound(py),
+            Value::Bool(b) => PyBool::new(py, *b).to_owned().into_any(),
+            Value::Number(n) => match (n.as_i64(), n.as_u64()) {
+                (Some(i), _) => i.into_pyobject(py)?.into_any(),
+                (None, Some(u)) => u.into_pyobject(py)?.into_any(),
+                (None, None) => n.as_f64().unwrap_or(f64::NAN).into_pyobject(py)?.into_any(),
+            },
+            Value::String(s) => PyString::new(py, s).into_any(),
+            Value::Array(items) => {
+                let list = PyList::empty(py);
+                for item in items {
+                    list.append(to_py(py, item)?)?;
+                }
+                list.into_any()
+            }
+            Value::Object(map) => {
+                let dict = PyDict::new(py);
+                for (key, item) in map {
+                    dict.set_item(key, to_py(py, item)?)?;
+                }
+                dict.into_any()
+            }
+        })
     }
-    Ok(())
 }
 
 /* ============== WASM bindings (wasm-bindgen) ============== */
@@ -341,17 +1936,35 @@ fn llmkit_py(_py: Python, m: &PyModule) -> PyResult<()> {
 #[cfg(feature = "wasm")]
 pub mod wasm {
     use wasm_bindgen::prelude::*;
-    use serde_wasm_bindgen::to_value as to_js; // <- use serde-wasm-bindgen
+    use serde_wasm_bindgen::{from_value as from_js, to_value as to_js};
     use super::convert_map;
 
+    /// `targets` is an array of names (`["json", "yaml"]`), a comma-separated
+    /// string, or `null`/`undefined` for the defaults. Anything else is
+    /// reported as `{ error }` rather than silently using the defaults.
     #[wasm_bindgen]
-    pub fn convert_map_js(input: &str, targets: Option<String>, allow_permissive: bool) -> JsValue {
+    pub fn convert_map_js(input: &str, targets: JsValue, allow_permissive: bool) -> JsValue {
+        let targets_owned: Option<Vec<String>> = match targets.as_string() {
+            Some(s) => Some(s.split(',').map(|x| x.trim().to_string()).collect()),
+            None if targets.is_null() || targets.is_undefined() => None,
+            None => match from_js(targets) {
+                Ok(list) => Some(list),
+                Err(e) => return error_object(&format!("targets: {e}")),
+            },
+        };
         let targets_vec: Option<Vec<&str>> =
-            targets.as_ref().map(|s| s.split(',').map(|x| x.trim()).collect());
+            targets_owned.as_ref().map(|v| v.iter().map(String::as_str).collect());
         let map = convert_map(input.as_bytes(), targets_vec.as_deref(), allow_permissive, None);
 
-        // Convert serde_json::Value/BTreeMap -> JsValue safely
-        to_js(&map).expect("serialize to JsValue failed")
+        // Convert serde_json::Value/BTreeMap -> JsValue safely. A panic would
+        // poison the module for the rest of the page, so report `{ error }`.
+        to_js(&map).unwrap_or_else(|e| error_object(&e.to_string()))
+    }
+
+    fn error_object(message: &str) -> JsValue {
+        let mut err = std::collections::BTreeMap::new();
+        err.insert("error", message);
+        to_js(&err).unwrap_or_else(|_| JsValue::from_str(message))
     }
 }
 
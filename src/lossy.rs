@@ -0,0 +1,78 @@
+//! Detection of information lost by a target conversion.
+
+use serde_json::Value;
+
+use crate::TargetFormat;
+use crate::table::Table;
+
+/// Describes what converting `v` to `tgt` silently degrades, e.g.
+/// `"csv: nested object at row 2 col 'meta' stringified"`.
+pub(crate) fn lossy_warnings(v: &Value, tgt: &TargetFormat) -> Vec<String> {
+    let name = tgt.name();
+    match tgt {
+        TargetFormat::Toml => {
+            let mut paths = Vec::new();
+            null_paths(v, String::new(), &mut paths);
+            paths
+                .into_iter()
+                .map(|p| format!("{name}: null at '{p}' dropped"))
+                .collect()
+        }
+        TargetFormat::Csv | TargetFormat::MarkdownTable => nested_cells(v, &name),
+        TargetFormat::Other(other) if other == "table" => nested_cells(v, &name),
+        _ => Vec::new(),
+    }
+}
+
+fn nested_cells(v: &Value, name: &str) -> Vec<String> {
+    let Some(table) = Table::from_value(v) else {
+        return Vec::new();
+    };
+    let mut out = Vec::new();
+    for (i, row) in table.rows.iter().enumerate() {
+        for h in &table.headers {
+            let kind = match row.get(h) {
+                Some(Value::Object(_)) => "object",
+                Some(Value::Array(_)) => "array",
+                _ => continue,
+            };
+            out.push(format!("{name}: nested {kind} at row {} col '{h}' stringified", i + 1));
+        }
+    }
+    out
+}
+
+fn null_paths(v: &Value, path: String, out: &mut Vec<String>) {
+    match v {
+        Value::Null => out.push(path),
+        Value::Object(obj) => {
+            for (k, child) in obj {
+                let p = if path.is_empty() { k.clone() } else { format!("{path}.{k}") };
+                null_paths(child, p, out);
+            }
+        }
+        Value::Array(items) => {
+            for (i, child) in items.iter().enumerate() {
+                null_paths(child, format!("{path}[{i}]"), out);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Removes nulls (object entries and array items), which TOML can't express.
+#[cfg(feature = "toml")]
+pub(crate) fn strip_nulls(v: &Value) -> Value {
+    match v {
+        Value::Object(obj) => Value::Object(
+            obj.iter()
+                .filter(|(_, child)| !child.is_null())
+                .map(|(k, child)| (k.clone(), strip_nulls(child)))
+                .collect(),
+        ),
+        Value::Array(items) => {
+            Value::Array(items.iter().filter(|c| !c.is_null()).map(strip_nulls).collect())
+        }
+        other => other.clone(),
+    }
+}
@@ -1,58 +1,274 @@
-use std::io::{self, Read};
+use std::io::{self, BufWriter, Read, Write};
 use std::{env, fs, process};
 use serde_json::Value;
-use llmkit::convert_map;
+use llmkit::{convert_stream, try_convert_map_with, ConvertOptions, DataFormat, TargetFormat};
 
 fn main() {
-    let mut file_path: Option<String> = None;
+    let mut file_paths: Vec<String> = Vec::new();
     let mut targets_arg: Option<String> = None;
     let mut single_format: Option<String> = None;
     let mut allow_permissive = false;
     let mut max_bytes: Option<usize> = None;
+    let mut input_encoding: Option<String> = None;
+    let mut raw = false;
+    let mut transpose = false;
+    let mut explain = false;
+    let mut indent: Option<String> = None;
+    let mut out_path: Option<String> = None;
+    let mut input_format = DataFormat::Auto;
+    let mut compact = false;
+    let mut detect_only = false;
+    let mut jsonl = false;
+    let mut strict = false;
+    let mut envelope_format = TargetFormat::Json;
 
     let mut args = env::args().skip(1);
     while let Some(a) = args.next() {
         match a.as_str() {
-            "--file" => file_path = args.next(),
+            "--file" => file_paths.extend(args.next()),
             "--targets" => targets_arg = args.next(),
             "--format" => single_format = args.next(),
             "--permissive" => allow_permissive = true,
             "--max-bytes" => max_bytes = args.next().and_then(|n| n.parse::<usize>().ok()),
+            "--input-encoding" => input_encoding = args.next(),
+            "--raw" => raw = true,
+            "--transpose" => transpose = true,
+            "--explain" => explain = true,
+            "--indent" => indent = Some(args.next().unwrap_or_else(|| usage())),
+            "--out" => out_path = Some(args.next().unwrap_or_else(|| usage())),
+            "--compact" => compact = true,
+            "--detect-only" => detect_only = true,
+            "--jsonl" => jsonl = true,
+            "--strict" => strict = true,
+            "--envelope-format" => {
+                envelope_format = match args.next().as_deref() {
+                    Some("json") => TargetFormat::Json,
+                    Some("yaml") => TargetFormat::Yaml,
+                    Some("toml") => TargetFormat::Toml,
+                    _ => usage(),
+                }
+            }
+            "--input-format" => {
+                input_format = match args.next().map(|name| DataFormat::from_name(&name)) {
+                    Some(DataFormat::Unknown) | None => usage(),
+                    Some(format) => format,
+                }
+            }
+            "--version" | "-V" => {
+                println!("llmkit {} ({})", llmkit::version(), llmkit::enabled_formats().join(", "));
+                return;
+            }
             "--help" | "-h" => usage(),
+            path if !path.starts_with('-') => file_paths.push(path.to_string()),
             _ => usage(),
         }
     }
 
-    let mut input = match file_path {
-        Some(p) => fs::read(&p).expect("failed to read file"),
-        None => {
-            let mut b = Vec::new();
-            io::stdin().read_to_end(&mut b).expect("stdin read failed");
-            b
-        }
+    if let Some(label) = input_encoding.as_deref()
+        && let Err(e) = llmkit::validate_encoding(label)
+    {
+        eprintln!("llmkit: {e}");
+        process::exit(2);
+    }
+
+    // `--indent N` spaces per level, or `--indent tab`.
+    let (indent, indent_tabs) = match indent.as_deref() {
+        None => (2, false),
+        Some("tab") => (2, true),
+        Some(n) => (n.parse::<usize>().unwrap_or_else(|_| usage()), false),
     };
 
-    if let Some(n) = max_bytes {
-        if input.len() > n { input.truncate(n); }
+    if raw {
+        // Stream the single format straight to stdout instead of building the envelope.
+        // Explaining and detecting need the whole input, which --raw never holds.
+        let envelope = envelope_format != TargetFormat::Json;
+        let (Some(fmt), false) = (single_format, explain || detect_only || jsonl || envelope || file_paths.len() > 1)
+        else {
+            usage()
+        };
+        let target = TargetFormat::from_name(&fmt);
+        let opts = ConvertOptions {
+            allow_permissive,
+            max_bytes,
+            input_encoding,
+            transpose,
+            indent,
+            indent_tabs,
+            input_format,
+            ..ConvertOptions::default()
+        };
+        // Open the input first so an unreadable path doesn't truncate `--out`.
+        let file = file_paths.pop().map(|p| {
+            fs::File::open(&p).unwrap_or_else(|e| {
+                eprintln!("llmkit: {p}: {e}");
+                process::exit(1);
+            })
+        });
+        let out = output(out_path.as_deref());
+        let result = match file {
+            Some(f) => convert_stream(f, out, &target, &opts),
+            None => convert_stream(io::stdin().lock(), out, &target, &opts),
+        };
+        match result {
+            Ok(0) => {}
+            Ok(recovered) => eprintln!("llmkit: recovered {recovered} malformed NDJSON lines"),
+            Err(e) => {
+                eprintln!("llmkit: {e}");
+                process::exit(1);
+            }
+        }
+        return;
     }
 
     // Choose targets
-    let targets: Option<Vec<&str>> = if let Some(fmt) = single_format {
-        Some(vec![fmt.as_str()])
+    let targets: Option<Vec<String>> = if let Some(fmt) = single_format {
+        Some(vec![fmt])
     } else {
-        targets_arg
-            .as_ref()
-            .map(|s| s.split(',').map(|x| x.trim()).filter(|x| !x.is_empty()).collect())
+        targets_arg.as_ref().map(|s| {
+            s.split(',').map(|x| x.trim()).filter(|x| !x.is_empty()).map(String::from).collect()
+        })
+    };
+
+    let opts = ConvertOptions {
+        targets,
+        allow_permissive,
+        max_bytes,
+        input_encoding,
+        transpose,
+        indent,
+        indent_tabs,
+        input_format,
+        ..ConvertOptions::default()
     };
 
-    let map = convert_map(&input, targets.as_deref(), allow_permissive, max_bytes);
-    let json_obj: Value = Value::Object(map.into_iter().collect());
-    println!("{}", serde_json::to_string_pretty(&json_obj).unwrap());
+    // JSON lines are JSON by definition.
+    if jsonl && envelope_format != TargetFormat::Json {
+        usage();
+    }
+
+    // Several inputs (or --jsonl) tag each result with its path.
+    let batch = file_paths.len() > 1 || jsonl;
+    let sources: Vec<Option<String>> = match file_paths.is_empty() {
+        true => vec![None],
+        false => file_paths.into_iter().map(Some).collect(),
+    };
+    let mut results = Vec::new();
+    let mut detected = Vec::new();
+    let mut failed = false;
+    let mut unreadable = false;
+    for source in sources {
+        let read = match &source {
+            Some(p) => fs::read(p),
+            None => {
+                let mut b = Vec::new();
+                io::stdin().read_to_end(&mut b).map(|_| b)
+            }
+        };
+        // An unreadable input is skipped so the rest of the batch still converts.
+        let input = match read {
+            Ok(input) => input,
+            Err(e) => {
+                match &source {
+                    Some(p) => eprintln!("llmkit: {p}: {e}"),
+                    None => eprintln!("llmkit: stdin: {e}"),
+                }
+                unreadable = true;
+                continue;
+            }
+        };
+        if explain {
+            for step in llmkit::explain_detection(&input, &opts) {
+                eprintln!("{}: {} ({})", step.parser, step.outcome.as_str(), step.format.as_str());
+            }
+        }
+        if detect_only {
+            let format = llmkit::detect_format_with(&input, &opts);
+            failed |= format == DataFormat::Unknown;
+            let format = format.as_str();
+            detected.push(match (&source, batch) {
+                (Some(p), true) => format!("{p}: {format}"),
+                _ => format.to_string(),
+            });
+            continue;
+        }
+        // Still print the envelope for unrecognized input, but say why on stderr.
+        let mut map = try_convert_map_with(&input, &opts).unwrap_or_else(|e| {
+            match &source {
+                Some(p) if batch => eprintln!("llmkit: {p}: {e}"),
+                _ => eprintln!("llmkit: {e}"),
+            }
+            failed = true;
+            // Built from the error rather than by converting the input again.
+            [("Format", Value::from(DataFormat::Unknown.as_str())), ("Error", Value::from(e.to_string()))]
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value))
+                .collect()
+        });
+        if batch && let Some(p) = source {
+            map.insert("Source".into(), Value::String(p));
+        }
+        results.push(Value::Object(map.into_iter().collect()));
+    }
+
+    if detect_only {
+        emit(out_path.as_deref(), detected.iter().map(String::as_str));
+    } else {
+        // Only the wrapper; the per-format strings inside keep their own layout.
+        let envelope_opts = ConvertOptions::default().toml_root_array_key(Some("results".into()));
+        let render = |v: &Value| match (&envelope_format, compact) {
+            (TargetFormat::Json, true) => serde_json::to_string(v).unwrap(),
+            (TargetFormat::Json, false) => serde_json::to_string_pretty(v).unwrap(),
+            // Same writers as the yaml/toml targets; binary values are already base64.
+            // A batch is an array, which TOML can only hold under a key.
+            (other, _) => match llmkit::convert_value_to_target(v, other, &envelope_opts) {
+                Ok(Value::String(s)) => s.trim_end_matches('\n').to_string(),
+                _ => {
+                    eprintln!("llmkit: cannot render the result as {}", other.name());
+                    process::exit(1);
+                }
+            },
+        };
+        let lines: Vec<String> = match (jsonl, batch) {
+            (true, _) => results.iter().map(|v| serde_json::to_string(v).unwrap()).collect(),
+            (false, true) => vec![render(&Value::Array(results))],
+            (false, false) => results.iter().map(render).collect(),
+        };
+        emit(out_path.as_deref(), lines.iter().map(String::as_str));
+    }
+    // The output is still written, so scripts can inspect what came back.
+    // An input that couldn't be read fails the run even without `--strict`.
+    if unreadable || (strict && failed) {
+        process::exit(1);
+    }
+}
+
+/// Writes each line to the output, exiting non-zero if that fails.
+fn emit<'a>(path: Option<&str>, mut lines: impl Iterator<Item = &'a str>) {
+    let mut out = output(path);
+    if let Err(e) = lines.try_for_each(|line| writeln!(out, "{line}")).and_then(|()| out.flush()) {
+        eprintln!("llmkit: {e}");
+        process::exit(1);
+    }
+}
+
+/// Where results go: `--out <path>` (created or truncated) or stdout.
+fn output(path: Option<&str>) -> BufWriter<Box<dyn Write>> {
+    let sink: Box<dyn Write> = match path {
+        Some(p) => match fs::File::create(p) {
+            Ok(f) => Box::new(f),
+            Err(e) => {
+                eprintln!("llmkit: cannot write {p}: {e}");
+                process::exit(1);
+            }
+        },
+        None => Box::new(io::stdout().lock()),
+    };
+    BufWriter::new(sink)
 }
 
 fn usage() -> ! {
     eprintln!(
-        "usage: llmkit [--file <path>] [--targets json,yaml,...] [--format yaml] [--permissive] [--max-bytes N]"
+        "usage: llmkit [--file <path>]... [<path>...] [--targets json,yaml,...] [--format yaml] [--permissive] [--max-bytes N] [--input-encoding <name>] [--input-format auto|json|yaml|...] [--raw] [--transpose] [--explain] [--indent N|tab] [--out <path>] [--compact] [--jsonl] [--detect-only] [--strict] [--envelope-format json|yaml|toml] [--version]"
     );
     process::exit(2);
 }
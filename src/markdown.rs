@@ -0,0 +1,104 @@
+//! Markdown output.
+
+use serde_json::{Map, Value};
+use std::io::{self, Write};
+
+use crate::table::{Table, cell_string};
+
+/// Writes an array of objects as a GitHub-flavored markdown table, a row at a
+/// time. Missing keys and nulls render as `empty_cell`, matching how CSV
+/// leaves them blank. `None` if `v` isn't a table.
+pub(crate) fn write_markdown_table<W: Write>(v: &Value, empty_cell: &str, mut w: W) -> Option<io::Result<()>> {
+    let Table { rows, headers } = Table::from_value(v)?;
+    if headers.is_empty() {
+        return None;
+    }
+    Some(write_table_rows(&rows, &headers, empty_cell, &mut w))
+}
+
+fn write_table_rows<W: Write>(
+    rows: &[&Map<String, Value>],
+    headers: &[String],
+    empty_cell: &str,
+    w: &mut W,
+) -> io::Result<()> {
+    let mut line = |cells: Vec<String>| writeln!(w, "| {} |", cells.join(" | "));
+    line(headers.iter().map(|h| escape_cell(h)).collect())?;
+    line(vec!["---".to_string(); headers.len()])?;
+    for row in rows {
+        let cells = headers
+            .iter()
+            .map(|h| match row.get(h) {
+                None | Some(Value::Null) => empty_cell.to_string(),
+                Some(v) => escape_cell(&cell_string(v)),
+            })
+            .collect();
+        line(cells)?;
+    }
+    Ok(())
+}
+
+fn escape_cell(s: &str) -> String {
+    s.replace('|', "\\|").replace('\n', " ")
+}
+
+/// Writes an object as markdown sections: one `#` heading per top-level key,
+/// scalars as paragraphs, and nested objects/arrays as (indented) bullet lists.
+pub(crate) fn write_sections<W: Write>(obj: &Map<String, Value>, mut w: W) -> io::Result<()> {
+    for (i, (k, v)) in obj.iter().enumerate() {
+        if i > 0 {
+            writeln!(w)?;
+        }
+        writeln!(w, "# {k}\n")?;
+        match v {
+            Value::Object(inner) => write_object_items(&mut w, inner, 0)?,
+            Value::Array(items) => write_array_items(&mut w, items, 0)?,
+            scalar => writeln!(w, "{}", scalar_text(scalar))?,
+        }
+    }
+    Ok(())
+}
+
+fn write_object_items<W: Write>(w: &mut W, obj: &Map<String, Value>, depth: usize) -> io::Result<()> {
+    let indent = "  ".repeat(depth);
+    for (k, v) in obj {
+        match v {
+            Value::Object(inner) => {
+                writeln!(w, "{indent}- {k}:")?;
+                write_object_items(w, inner, depth + 1)?;
+            }
+            Value::Array(items) => {
+                writeln!(w, "{indent}- {k}:")?;
+                write_array_items(w, items, depth + 1)?;
+            }
+            scalar => writeln!(w, "{indent}- {k}: {}", scalar_text(scalar))?,
+        }
+    }
+    Ok(())
+}
+
+fn write_array_items<W: Write>(w: &mut W, items: &[Value], depth: usize) -> io::Result<()> {
+    let indent = "  ".repeat(depth);
+    for item in items {
+        match item {
+            Value::Object(inner) => {
+                writeln!(w, "{indent}-")?;
+                write_object_items(w, inner, depth + 1)?;
+            }
+            Value::Array(nested) => {
+                writeln!(w, "{indent}-")?;
+                write_array_items(w, nested, depth + 1)?;
+            }
+            scalar => writeln!(w, "{indent}- {}", scalar_text(scalar))?,
+        }
+    }
+    Ok(())
+}
+
+fn scalar_text(v: &Value) -> String {
+    match v {
+        Value::Null => "null".into(),
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
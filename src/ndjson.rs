@@ -0,0 +1,79 @@
+//! Newline-delimited JSON reading and writing.
+
+use serde_json::Value;
+use std::io::{self, BufRead, BufReader, Read, Write};
+
+use crate::{ConvertError, DataFormat};
+
+/// Lazily yields one parsed record per non-blank line of `reader`.
+pub(crate) struct Records<R> {
+    lines: io::Lines<BufReader<R>>,
+    line: usize,
+    done: bool,
+    allow_permissive: bool,
+    /// Lines read only thanks to the permissive fallbacks.
+    pub(crate) recovered: usize,
+}
+
+impl<R: Read> Records<R> {
+    pub(crate) fn new(reader: R) -> Self {
+        Self::permissive(reader, false)
+    }
+
+    /// Like [`Records::new`], but lines that aren't strict JSON get
+    /// [`ndjson_line`](crate::ndjson_line)'s fallbacks when `allow_permissive`.
+    pub(crate) fn permissive(reader: R, allow_permissive: bool) -> Self {
+        Self { lines: BufReader::new(reader).lines(), line: 0, done: false, allow_permissive, recovered: 0 }
+    }
+}
+
+impl<R: Read> Iterator for Records<R> {
+    type Item = Result<Value, ConvertError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            let line = match self.lines.next()? {
+                Ok(line) => line,
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(ConvertError::Io(e.to_string())));
+                }
+            };
+            self.line += 1;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let record = serde_json::from_str(&line).or_else(|e| match crate::ndjson_line(&line, self.allow_permissive) {
+                Some((v, _)) => {
+                    self.recovered += 1;
+                    Ok(v)
+                }
+                None => Err(ConvertError::from_json(DataFormat::Ndjson, &e, self.line - 1)),
+            });
+            return Some(record);
+        }
+        None
+    }
+}
+
+/// Reads `reader` as NDJSON one record at a time, so inputs of any size can
+/// be processed without collecting them. Blank lines are skipped; a line that
+/// isn't valid JSON yields `ConvertError::Parse` with its line number and
+/// iteration continues with the next line. A read error ends the iterator.
+pub fn ndjson_records<R: Read>(reader: R) -> impl Iterator<Item = Result<Value, ConvertError>> {
+    Records::new(reader)
+}
+
+/// Writes an array as one compact JSON document per line; any other value is
+/// written as a single line.
+pub(crate) fn write_ndjson<W: Write>(v: &Value, mut w: W) -> io::Result<()> {
+    let records = match v {
+        Value::Array(items) => items.iter().collect::<Vec<_>>(),
+        other => vec![other],
+    };
+    for record in records {
+        serde_json::to_writer(&mut w, record)?;
+        w.write_all(b"\n")?;
+    }
+    Ok(())
+}
@@ -0,0 +1,627 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::DataFormat;
+
+/// Conversion settings for [`crate::convert_map_with`].
+///
+/// Every field has a builder-style setter of the same name, so options can be
+/// built inline:
+///
+/// ```
+/// let opts = llmkit::ConvertOptions::default()
+///     .targets(&["json", "yaml"])
+///     .max_output_bytes(Some(1 << 20));
+/// let map = llmkit::convert_map_with(br#"{"a":1}"#, &opts);
+/// assert!(map.contains_key("yaml"));
+/// ```
+#[derive(Debug, Clone)]
+pub struct ConvertOptions {
+    /// Target formats to emit. `None` => all default targets.
+    pub targets: Option<Vec<String>>,
+    /// Enables looser parsing.
+    pub allow_permissive: bool,
+    /// Truncate input to this many bytes before parsing. For gzip input this
+    /// bounds the inflated size, which is otherwise capped at 256 MiB.
+    pub max_bytes: Option<usize>,
+    /// Abort any single target conversion whose output exceeds this many bytes.
+    /// `json`, `ndjson`, `jsonl.gz`, `csv`, `markdown_table`, block-style
+    /// `yaml` and `toml` (a section at a time) stop writing at the limit. The
+    /// other targets (inline `yaml`, `xml`, `table`, `json5`, `dotenv`,
+    /// `headers`, `query`, `ubjson`) render in full before the check, and
+    /// `flat` and `bundle` copy the value first, so for them this caps the
+    /// result but not peak memory.
+    pub max_output_bytes: Option<usize>,
+    /// How the `query` target handles nested objects.
+    pub query_nested: NestedMode,
+    /// Adds a `"Stats"` object (`ConversionCount`, `TargetCount`) to the output.
+    pub include_stats: bool,
+    /// Adds a `"Warnings"` array describing information lost by each target.
+    pub include_warnings: bool,
+    /// How the markdown target renders non-tabular objects.
+    pub markdown_object_mode: MarkdownObjectMode,
+    /// What targets that need an object or array (TOML, CSV, markdown,
+    /// tables, `.env`, query strings) do with a bare scalar input.
+    pub scalar_mode: ScalarMode,
+    /// How the `dotenv` target handles nested objects and arrays. Defaults to
+    /// `NestedMode::Error`; `Flatten` joins keys with `dotenv_separator`.
+    pub dotenv_nested: NestedMode,
+    /// Joins nested keys when `dotenv_nested` flattens (default `__`, so
+    /// `{"db":{"port":1}}` becomes `db__port=1`).
+    pub dotenv_separator: String,
+    /// Uppercase every key in the `dotenv` target (`db__port` → `DB__PORT`).
+    pub dotenv_uppercase_keys: bool,
+    /// Markdown table cell text for missing keys and nulls (default empty).
+    pub markdown_empty_cell: String,
+    /// When detection fails, wrap the raw text as `{"<key>": "<text>"}` (format
+    /// `text`) instead of returning `unknown`.
+    pub wrap_as_string: Option<String>,
+    /// Drop a leading UTF-8 byte-order mark.
+    pub strip_bom: bool,
+    /// Unwrap the first ```` ``` ```` fenced block (or inline backticks). On by default.
+    pub strip_fences: bool,
+    /// Trim surrounding whitespace before detection.
+    pub trim_whitespace: bool,
+    /// Turn CSV cells like `42`, `1.5`, `true` into JSON scalars, as
+    /// `allow_permissive` also does. Markdown table cells are always typed.
+    pub coerce_scalars: bool,
+    /// Turn quoted numbers anywhere in the input (`{"count": "42"}`) into
+    /// JSON numbers. Leading zeros (`"007"`) and integers past 64 bits stay
+    /// strings.
+    pub unquote_numbers: bool,
+    /// Don't accept YAML whose whole document is a plain string (i.e. prose).
+    pub reject_plain_yaml: bool,
+    /// Reject input that isn't valid UTF-8 (reported as `ConvertError::Utf8`
+    /// under `"Error"`) instead of replacing bad bytes.
+    pub strict_utf8: bool,
+    /// Decode input from this encoding label (`latin1`, `windows-1252`,
+    /// `utf-16le`, ...) instead of UTF-8. Needs the `encoding` feature.
+    pub input_encoding: Option<String>,
+    /// Start CSV output with a UTF-8 byte-order mark so Excel detects the
+    /// encoding.
+    pub csv_write_bom: bool,
+    /// Render a top-level array's `json` target as NDJSON, one element per
+    /// line, instead of a pretty-printed array.
+    pub prefer_ndjson_for_arrays: bool,
+    /// Add `"SchemaVersion"` ([`SCHEMA_VERSION`](crate::SCHEMA_VERSION)) to
+    /// the output map.
+    pub include_meta: bool,
+    /// Handling of repeated CSV header names.
+    pub csv_duplicate_headers: DuplicateHeaders,
+    /// What an empty CSV cell becomes when reading CSV input.
+    pub csv_empty_as: CsvEmpty,
+    /// What the CSV target does when an array of arrays has rows of
+    /// different lengths.
+    pub csv_ragged_rows: RaggedRows,
+    /// Sort an array of objects by this column before CSV, markdown and
+    /// `table` output. Numbers sort before strings, then booleans; rows
+    /// missing the column (or holding null or a nested value) go last.
+    pub sort_by: Option<(String, SortOrder)>,
+    /// Fail the CSV target with `ConvertError::Unsupported` when the output
+    /// would have more columns than this, e.g. rows with thousands of
+    /// distinct sparse keys.
+    pub csv_max_columns: Option<usize>,
+    /// Read CSV only up to the first blank line, so notes after the table
+    /// don't fail the whole parse.
+    pub csv_stop_at_blank: bool,
+    /// Reformat datetime strings (RFC 3339 or `YYYY-MM-DDTHH:MM:SS`) with this
+    /// strftime pattern in every output. Needs the `chrono` feature.
+    pub datetime_format: Option<String>,
+    /// Shift offset datetimes to this zone (`UTC`, `+05:30`) before applying
+    /// `datetime_format`.
+    pub datetime_timezone: Option<String>,
+    /// Parse input only as this format, skipping the rest of detection.
+    /// `DataFormat::Auto` (the default) runs the full cascade.
+    pub input_format: DataFormat,
+    /// The input's MIME type (`Content-Type`, parameters allowed). Its format
+    /// (see [`format_from_mime`](crate::format_from_mime)) is tried first;
+    /// if that parser rejects the input, or the type is unknown, full
+    /// detection runs as usual.
+    pub mime_hint: Option<String>,
+    /// Formats skipped during detection, e.g. `Yaml` for sources where its
+    /// "anything is a scalar" fallback misfires.
+    pub disabled_formats: HashSet<DataFormat>,
+    /// Merge consecutive markdown tables whose header rows match exactly
+    /// into one array, for tables an LLM split with prose or `...`.
+    pub markdown_stitch: bool,
+    /// Turn a GeoJSON `FeatureCollection` into one row per feature: its
+    /// `properties` plus `geometry` as JSON text, ready for CSV.
+    pub geojson_flatten: bool,
+    /// Swap rows and columns of the parsed value (see [`transpose`](crate::transpose))
+    /// before converting. Values of any other shape pass through unchanged.
+    pub transpose: bool,
+    /// Abandon conversions that take longer than this, reporting
+    /// `ConvertError::Timeout`. Runs the conversion on a spawned thread, so
+    /// it needs thread support; without it the budget is ignored.
+    pub timeout: Option<Duration>,
+    /// Spaces per nesting level in pretty JSON (`"Beautified"`, the `json`
+    /// and `flat` targets).
+    pub indent: usize,
+    /// Indent pretty JSON with one tab per level instead of `indent` spaces.
+    pub indent_tabs: bool,
+    /// Render `"Beautified"` in the detected input format (YAML stays YAML,
+    /// TOML stays TOML, ...) instead of JSON. Formats without a writer still
+    /// get JSON.
+    pub beautified_native: bool,
+    /// Fill `"Beautified"` with pretty-printed JSON. Turn off for large
+    /// inputs to save a copy; the key is then an empty string, and
+    /// [`write_beautified`](crate::write_beautified) can stream the same
+    /// layout to a writer instead.
+    pub include_beautified: bool,
+    /// Escape dots inside keys in the `flat` target (`user\.email`) instead of
+    /// reading them as nesting; see [`flatten_escaped`](crate::flatten_escaped).
+    pub treat_dots_as_literal: bool,
+    /// Copy the comments of JSONC input (read under `allow_permissive`) into
+    /// the `yaml` and `toml` targets as `#` lines above the keys they
+    /// precede, for migrating commented configs.
+    pub preserve_comments: bool,
+    /// Custom cleanup run on the decoded, BOM-stripped text before fence
+    /// stripping and detection.
+    pub preprocessor: Option<Preprocessor>,
+    /// What the map holds when the input's format isn't recognized.
+    pub unknown_fallback: UnknownFallback,
+    /// Wrap a top-level array under this key for the `toml` target
+    /// (`values = [1, 2, 3]`), since a TOML document must be a table.
+    pub toml_root_array_key: Option<String>,
+    /// Write arrays of scalars as YAML flow sequences (`ports: [80, 443]`);
+    /// objects stay block style.
+    pub yaml_inline_arrays: bool,
+    /// JSON output for values nested deeper than this uses a recursion-free
+    /// serializer (default 128).
+    pub iterative_json_depth: usize,
+    /// Sort object keys alphabetically at every level of every output,
+    /// instead of keeping their source order.
+    pub sort_keys: bool,
+}
+
+impl Default for ConvertOptions {
+    fn default() -> Self {
+        Self {
+            targets: None,
+            allow_permissive: false,
+            max_bytes: None,
+            max_output_bytes: None,
+            query_nested: NestedMode::default(),
+            include_stats: false,
+            include_warnings: false,
+            markdown_object_mode: MarkdownObjectMode::default(),
+            scalar_mode: ScalarMode::default(),
+            dotenv_nested: NestedMode::Error,
+            dotenv_separator: "__".into(),
+            dotenv_uppercase_keys: false,
+            markdown_empty_cell: String::new(),
+            wrap_as_string: None,
+            strip_bom: false,
+            strip_fences: true,
+            trim_whitespace: false,
+            coerce_scalars: false,
+            unquote_numbers: false,
+            reject_plain_yaml: false,
+            strict_utf8: false,
+            input_encoding: None,
+            csv_write_bom: false,
+            prefer_ndjson_for_arrays: false,
+            include_meta: false,
+            csv_duplicate_headers: DuplicateHeaders::default(),
+            csv_empty_as: CsvEmpty::default(),
+            sort_by: None,
+            csv_max_columns: None,
+            csv_stop_at_blank: false,
+            csv_ragged_rows: RaggedRows::default(),
+            datetime_format: None,
+            datetime_timezone: None,
+            input_format: DataFormat::Auto,
+            mime_hint: None,
+            disabled_formats: HashSet::new(),
+            markdown_stitch: false,
+            geojson_flatten: false,
+            transpose: false,
+            timeout: None,
+            indent: 2,
+            indent_tabs: false,
+            beautified_native: false,
+            include_beautified: true,
+            treat_dots_as_literal: false,
+            preserve_comments: false,
+            preprocessor: None,
+            unknown_fallback: UnknownFallback::default(),
+            toml_root_array_key: None,
+            yaml_inline_arrays: false,
+            iterative_json_depth: 128,
+            sort_keys: false,
+        }
+    }
+}
+
+/// Markdown rendering for values that aren't an array of rows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MarkdownObjectMode {
+    /// Only tabular data is rendered; objects yield `Null`.
+    #[default]
+    Table,
+    /// Objects render as `#` headings per key with nested bullet lists.
+    Sections,
+}
+
+/// How structured targets render a bare scalar (`42`, `"hi"`). JSON, YAML
+/// and the other value targets always emit the scalar itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ScalarMode {
+    /// The target yields `Null`.
+    #[default]
+    Null,
+    /// Render `{"value": <scalar>}` instead (a one-row table for CSV,
+    /// markdown and `table`).
+    Wrap,
+    /// Fail the target with `ConvertError::Unsupported`.
+    Error,
+}
+
+/// What flat key/value targets do with nested objects.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NestedMode {
+    /// Join nested keys (`a.b=1` in query strings).
+    #[default]
+    Flatten,
+    /// Fail the conversion with `ConvertError::Unsupported`.
+    Error,
+}
+
+/// How CSV input with repeated header names (`id,id,name`) is read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DuplicateHeaders {
+    /// Keep every column, renaming repeats `id_2`, `id_3`, ...
+    #[default]
+    Suffix,
+    /// Keep only the first column of each name.
+    First,
+    /// Don't accept the input as CSV; the repeated name is reported as the
+    /// cause of `ConvertError::Unrecognized`.
+    Error,
+}
+
+/// CSV output for an array of arrays whose rows differ in length
+/// (`[[1,2],[3,4,5]]`). Headers are `col0..colN` either way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RaggedRows {
+    /// Pad short rows with empty cells to the widest row.
+    #[default]
+    Pad,
+    /// Fail the conversion with `ConvertError::Unsupported`.
+    Error,
+}
+
+/// Direction for [`ConvertOptions::sort_by`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SortOrder {
+    #[default]
+    Ascending,
+    Descending,
+}
+
+/// How an empty CSV cell is read. CSV can't tell an absent value from an
+/// empty one, so the caller picks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CsvEmpty {
+    /// `""`, the cell as written.
+    #[default]
+    EmptyString,
+    /// `null`.
+    Null,
+    /// Leave the key out of that row's object.
+    Omit,
+}
+
+/// A text rewrite applied before detection; see
+/// [`ConvertOptions::preprocessor`]. Cheap to clone.
+#[derive(Clone)]
+pub struct Preprocessor(Arc<dyn Fn(&str) -> String + Send + Sync>);
+
+impl Preprocessor {
+    pub fn new(f: impl Fn(&str) -> String + Send + Sync + 'static) -> Self {
+        Self(Arc::new(f))
+    }
+
+    pub(crate) fn apply(&self, text: &str) -> String {
+        (self.0)(text)
+    }
+}
+
+impl fmt::Debug for Preprocessor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Preprocessor(..)")
+    }
+}
+
+/// Output for input no parser accepts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UnknownFallback {
+    /// Copy the text into `"Beautified"` and `"normal"`.
+    #[default]
+    Echo,
+    /// Leave `"Beautified"` and `"normal"` empty.
+    Empty,
+    /// Leave them empty and explain under `"Error"`.
+    Error,
+}
+
+impl ConvertOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn targets<S: AsRef<str>>(mut self, targets: &[S]) -> Self {
+        self.targets = Some(targets.iter().map(|s| s.as_ref().to_string()).collect());
+        self
+    }
+
+    pub fn allow_permissive(mut self, on: bool) -> Self {
+        self.allow_permissive = on;
+        self
+    }
+
+    pub fn max_bytes(mut self, n: Option<usize>) -> Self {
+        self.max_bytes = n;
+        self
+    }
+
+    pub fn max_output_bytes(mut self, n: Option<usize>) -> Self {
+        self.max_output_bytes = n;
+        self
+    }
+
+    pub fn query_nested(mut self, mode: NestedMode) -> Self {
+        self.query_nested = mode;
+        self
+    }
+
+    pub fn include_stats(mut self, on: bool) -> Self {
+        self.include_stats = on;
+        self
+    }
+
+    pub fn include_warnings(mut self, on: bool) -> Self {
+        self.include_warnings = on;
+        self
+    }
+
+    pub fn markdown_object_mode(mut self, mode: MarkdownObjectMode) -> Self {
+        self.markdown_object_mode = mode;
+        self
+    }
+
+    pub fn scalar_mode(mut self, mode: ScalarMode) -> Self {
+        self.scalar_mode = mode;
+        self
+    }
+
+    pub fn dotenv_nested(mut self, mode: NestedMode) -> Self {
+        self.dotenv_nested = mode;
+        self
+    }
+
+    pub fn dotenv_separator(mut self, separator: impl Into<String>) -> Self {
+        self.dotenv_separator = separator.into();
+        self
+    }
+
+    pub fn dotenv_uppercase_keys(mut self, on: bool) -> Self {
+        self.dotenv_uppercase_keys = on;
+        self
+    }
+
+    pub fn markdown_empty_cell(mut self, placeholder: impl Into<String>) -> Self {
+        self.markdown_empty_cell = placeholder.into();
+        self
+    }
+
+    pub fn wrap_as_string(mut self, key: Option<String>) -> Self {
+        self.wrap_as_string = key;
+        self
+    }
+
+    pub fn strip_bom(mut self, on: bool) -> Self {
+        self.strip_bom = on;
+        self
+    }
+
+    pub fn strip_fences(mut self, on: bool) -> Self {
+        self.strip_fences = on;
+        self
+    }
+
+    pub fn trim_whitespace(mut self, on: bool) -> Self {
+        self.trim_whitespace = on;
+        self
+    }
+
+    pub fn coerce_scalars(mut self, on: bool) -> Self {
+        self.coerce_scalars = on;
+        self
+    }
+
+    pub fn unquote_numbers(mut self, on: bool) -> Self {
+        self.unquote_numbers = on;
+        self
+    }
+
+    pub fn reject_plain_yaml(mut self, on: bool) -> Self {
+        self.reject_plain_yaml = on;
+        self
+    }
+
+    pub fn strict_utf8(mut self, on: bool) -> Self {
+        self.strict_utf8 = on;
+        self
+    }
+
+    pub fn input_encoding(mut self, label: Option<String>) -> Self {
+        self.input_encoding = label;
+        self
+    }
+
+    pub fn csv_write_bom(mut self, on: bool) -> Self {
+        self.csv_write_bom = on;
+        self
+    }
+
+    pub fn prefer_ndjson_for_arrays(mut self, on: bool) -> Self {
+        self.prefer_ndjson_for_arrays = on;
+        self
+    }
+
+    pub fn include_meta(mut self, on: bool) -> Self {
+        self.include_meta = on;
+        self
+    }
+
+    pub fn csv_duplicate_headers(mut self, mode: DuplicateHeaders) -> Self {
+        self.csv_duplicate_headers = mode;
+        self
+    }
+
+    pub fn csv_ragged_rows(mut self, mode: RaggedRows) -> Self {
+        self.csv_ragged_rows = mode;
+        self
+    }
+
+    pub fn sort_by(mut self, column: Option<(String, SortOrder)>) -> Self {
+        self.sort_by = column;
+        self
+    }
+
+    pub fn csv_max_columns(mut self, limit: Option<usize>) -> Self {
+        self.csv_max_columns = limit;
+        self
+    }
+
+    pub fn csv_stop_at_blank(mut self, on: bool) -> Self {
+        self.csv_stop_at_blank = on;
+        self
+    }
+
+    pub fn csv_empty_as(mut self, mode: CsvEmpty) -> Self {
+        self.csv_empty_as = mode;
+        self
+    }
+
+    pub fn datetime_format(mut self, pattern: Option<String>) -> Self {
+        self.datetime_format = pattern;
+        self
+    }
+
+    pub fn datetime_timezone(mut self, zone: Option<String>) -> Self {
+        self.datetime_timezone = zone;
+        self
+    }
+
+    pub fn mime_hint(mut self, mime: Option<String>) -> Self {
+        self.mime_hint = mime;
+        self
+    }
+
+    pub fn input_format(mut self, format: DataFormat) -> Self {
+        self.input_format = format;
+        self
+    }
+
+    pub fn disabled_formats(mut self, formats: HashSet<DataFormat>) -> Self {
+        self.disabled_formats = formats;
+        self
+    }
+
+    pub fn markdown_stitch(mut self, on: bool) -> Self {
+        self.markdown_stitch = on;
+        self
+    }
+
+    pub fn geojson_flatten(mut self, on: bool) -> Self {
+        self.geojson_flatten = on;
+        self
+    }
+
+    pub fn transpose(mut self, on: bool) -> Self {
+        self.transpose = on;
+        self
+    }
+
+    pub fn timeout(mut self, limit: Option<Duration>) -> Self {
+        self.timeout = limit;
+        self
+    }
+
+    pub fn indent(mut self, spaces: usize) -> Self {
+        self.indent = spaces;
+        self
+    }
+
+    pub fn indent_tabs(mut self, on: bool) -> Self {
+        self.indent_tabs = on;
+        self
+    }
+
+    pub fn beautified_native(mut self, on: bool) -> Self {
+        self.beautified_native = on;
+        self
+    }
+
+    pub fn include_beautified(mut self, on: bool) -> Self {
+        self.include_beautified = on;
+        self
+    }
+
+    pub fn treat_dots_as_literal(mut self, on: bool) -> Self {
+        self.treat_dots_as_literal = on;
+        self
+    }
+
+    pub fn preserve_comments(mut self, on: bool) -> Self {
+        self.preserve_comments = on;
+        self
+    }
+
+    pub fn preprocessor(mut self, f: impl Fn(&str) -> String + Send + Sync + 'static) -> Self {
+        self.preprocessor = Some(Preprocessor::new(f));
+        self
+    }
+
+    pub fn unknown_fallback(mut self, mode: UnknownFallback) -> Self {
+        self.unknown_fallback = mode;
+        self
+    }
+
+    pub fn toml_root_array_key(mut self, key: Option<String>) -> Self {
+        self.toml_root_array_key = key;
+        self
+    }
+
+    pub fn yaml_inline_arrays(mut self, on: bool) -> Self {
+        self.yaml_inline_arrays = on;
+        self
+    }
+
+    pub fn iterative_json_depth(mut self, depth: usize) -> Self {
+        self.iterative_json_depth = depth;
+        self
+    }
+
+    pub fn sort_keys(mut self, on: bool) -> Self {
+        self.sort_keys = on;
+        self
+    }
+
+    /// "Just do the sensible thing" switch. Turns all of these on at once:
+    /// `strip_bom`, `strip_fences`, `trim_whitespace`, `coerce_scalars` and
+    /// `reject_plain_yaml`. `normalize(false)` puts each back to its default,
+    /// so `strip_fences` stays on.
+    pub fn normalize(self, on: bool) -> Self {
+        let defaults = Self::default();
+        self.strip_bom(on || defaults.strip_bom)
+            .strip_fences(on || defaults.strip_fences)
+            .trim_whitespace(on || defaults.trim_whitespace)
+            .coerce_scalars(on || defaults.coerce_scalars)
+            .reject_plain_yaml(on || defaults.reject_plain_yaml)
+    }
+}
@@ -0,0 +1,243 @@
+//! Permissive-mode rewrites of almost-JSON into JSON.
+
+/// Translates Python `repr` output (`{'a': 1, 'b': True, 'c': None}`) into
+/// JSON text: single-quoted strings become double-quoted, `True`/`False`/
+/// `None` become `true`/`false`/`null`, tuples become arrays and trailing
+/// commas are dropped. Quotes inside strings (`"it's"`, `'say "hi"'`) are kept
+/// intact. Returns `None` if the input doesn't look like a dict/list literal.
+pub(crate) fn python_repr_to_json(s: &str) -> Option<String> {
+    let s = s.trim();
+    if !s.starts_with(['{', '[', '(']) {
+        return None;
+    }
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' | '"' => {
+                out.push('"');
+                while let Some(ch) = chars.next() {
+                    match ch {
+                        '\\' => match chars.next()? {
+                            '\'' => out.push('\''),
+                            esc => {
+                                out.push('\\');
+                                out.push(esc);
+                            }
+                        },
+                        ch if ch == c => break,
+                        '"' => out.push_str("\\\""),
+                        ch => out.push(ch),
+                    }
+                }
+                out.push('"');
+            }
+            '(' => out.push('['),
+            ')' => out.push(']'),
+            c if c.is_alphabetic() || c == '_' => {
+                let mut word = c.to_string();
+                while let Some(&next) = chars.peek() {
+                    if !(next.is_alphanumeric() || next == '_') {
+                        break;
+                    }
+                    word.push(next);
+                    chars.next();
+                }
+                out.push_str(match word.as_str() {
+                    "True" => "true",
+                    "False" => "false",
+                    "None" => "null",
+                    other => other,
+                });
+            }
+            ',' => {
+                let rest = chars.clone().find(|ch| !ch.is_whitespace());
+                if !matches!(rest, Some('}' | ']' | ')')) {
+                    out.push(',');
+                }
+            }
+            c => out.push(c),
+        }
+    }
+    Some(out)
+}
+
+/// Removes `//` line comments and `/* */` block comments from JSONC text,
+/// leaving string literals (`"http://x"`) untouched. A block comment becomes
+/// a space so the tokens around it stay apart. Returns `None` when there are
+/// no comments or a block comment is left open.
+pub(crate) fn strip_json_comments(s: &str) -> Option<String> {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars().peekable();
+    let mut found = false;
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                out.push(c);
+                while let Some(ch) = chars.next() {
+                    out.push(ch);
+                    match ch {
+                        '\\' => out.extend(chars.next()),
+                        '"' => break,
+                        _ => {}
+                    }
+                }
+            }
+            '/' if chars.peek() == Some(&'/') => {
+                found = true;
+                while chars.next_if(|&ch| ch != '\n').is_some() {}
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                found = true;
+                chars.next();
+                let mut prev = '\0';
+                loop {
+                    let ch = chars.next()?;
+                    if prev == '*' && ch == '/' {
+                        break;
+                    }
+                    prev = ch;
+                }
+                out.push(' ');
+            }
+            c => out.push(c),
+        }
+    }
+    found.then_some(out)
+}
+
+/// Parses an EDN / Clojure literal (`{:a 1 :b "x" :tags #{:x}}`) into JSON:
+/// keywords become strings (`:a` → `"a"`), vectors, lists and sets become
+/// arrays, `nil` becomes `null`, and commas and `;` comments are whitespace.
+/// Non-string map keys are stringified. Only maps and vectors are accepted at
+/// the top level.
+#[cfg(feature = "edn")]
+pub(crate) fn edn_to_value(s: &str) -> Option<serde_json::Value> {
+    let s = s.trim();
+    if !s.starts_with(['{', '[']) {
+        return None;
+    }
+    let mut parser = Edn { chars: s.chars().peekable(), depth: 0 };
+    let value = parser.value()?;
+    parser.skip_ws();
+    parser.chars.peek().is_none().then_some(value)
+}
+
+#[cfg(feature = "edn")]
+struct Edn<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+    depth: usize,
+}
+
+#[cfg(feature = "edn")]
+impl Edn<'_> {
+    /// Same nesting limit serde_json applies.
+    const MAX_DEPTH: usize = 128;
+
+    fn skip_ws(&mut self) {
+        while let Some(&c) = self.chars.peek() {
+            if c == ';' {
+                while self.chars.next_if(|&c| c != '\n').is_some() {}
+            } else if c.is_whitespace() || c == ',' {
+                self.chars.next();
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn value(&mut self) -> Option<serde_json::Value> {
+        use serde_json::Value;
+        self.skip_ws();
+        match self.chars.next()? {
+            '{' => self.nested(|p| {
+                let mut map = serde_json::Map::new();
+                while !p.at_close('}') {
+                    let key = match p.value()? {
+                        Value::String(s) => s,
+                        other => other.to_string(),
+                    };
+                    if p.at_close('}') {
+                        return None;
+                    }
+                    map.insert(key, p.value()?);
+                }
+                Some(Value::Object(map))
+            }),
+            '[' => self.nested(|p| p.items(']')),
+            '(' => self.nested(|p| p.items(')')),
+            '#' if self.chars.next_if_eq(&'{').is_some() => self.nested(|p| p.items('}')),
+            '"' => {
+                let mut out = String::new();
+                loop {
+                    match self.chars.next()? {
+                        '"' => break,
+                        '\\' => out.push(match self.chars.next()? {
+                            'n' => '\n',
+                            't' => '\t',
+                            'r' => '\r',
+                            other => other,
+                        }),
+                        c => out.push(c),
+                    }
+                }
+                Some(Value::String(out))
+            }
+            c => {
+                let mut atom = c.to_string();
+                while let Some(c) =
+                    self.chars.next_if(|&c| !(c.is_whitespace() || ",;{}[]()\"".contains(c)))
+                {
+                    atom.push(c);
+                }
+                Some(Self::atom(&atom))
+            }
+        }
+    }
+
+    fn nested(&mut self, parse: impl FnOnce(&mut Self) -> Option<serde_json::Value>) -> Option<serde_json::Value> {
+        self.depth += 1;
+        if self.depth > Self::MAX_DEPTH {
+            return None;
+        }
+        let value = parse(self);
+        self.depth -= 1;
+        value
+    }
+
+    /// Skips whitespace and consumes `close` if it's next.
+    fn at_close(&mut self, close: char) -> bool {
+        self.skip_ws();
+        self.chars.next_if_eq(&close).is_some()
+    }
+
+    fn items(&mut self, close: char) -> Option<serde_json::Value> {
+        let mut items = Vec::new();
+        while !self.at_close(close) {
+            items.push(self.value()?);
+        }
+        Some(serde_json::Value::Array(items))
+    }
+
+    fn atom(atom: &str) -> serde_json::Value {
+        use serde_json::Value;
+        match atom {
+            "nil" => return Value::Null,
+            "true" => return Value::Bool(true),
+            "false" => return Value::Bool(false),
+            _ => {}
+        }
+        if let Some(keyword) = atom.strip_prefix(':') {
+            return Value::String(keyword.to_string());
+        }
+        // `42N` / `1.5M` are arbitrary-precision literals.
+        let number = atom.strip_suffix(['N', 'M']).unwrap_or(atom);
+        if let Ok(n) = number.parse::<i64>() {
+            return n.into();
+        }
+        if let Some(n) = number.parse::<f64>().ok().and_then(serde_json::Number::from_f64) {
+            return Value::Number(n);
+        }
+        Value::String(atom.to_string())
+    }
+}
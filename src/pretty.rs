@@ -0,0 +1,151 @@
+//! Pretty-printing JSON from a reader without building a value.
+
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+
+use crate::{ConvertError, DataFormat};
+
+/// Writes the JSON document read from `reader` to `writer` in the layout of
+/// the `"Beautified"` field, holding only the current nesting in memory.
+///
+/// Tokens are copied as is, so numbers and string escapes keep their original
+/// spelling. Only the structure is checked: unbalanced brackets, a value after
+/// the document or input ending mid-document stop with `ConvertError::Parse`,
+/// possibly after part of the output was written.
+pub fn write_beautified<R: Read, W: Write>(reader: R, writer: W) -> Result<(), ConvertError> {
+    let io_err = |e: std::io::Error| ConvertError::Io(e.to_string());
+    let mut reader = BufReader::new(reader);
+    let mut out = BufWriter::new(writer);
+    let mut p = Printer::default();
+
+    loop {
+        let chunk = reader.fill_buf().map_err(io_err)?;
+        if chunk.is_empty() {
+            break;
+        }
+        for &b in chunk {
+            p.byte(b, &mut out)?;
+        }
+        let n = chunk.len();
+        reader.consume(n);
+    }
+    p.finish()?;
+    out.flush().map_err(io_err)
+}
+
+#[derive(Default)]
+struct Printer {
+    /// Closing bracket expected for each open container.
+    stack: Vec<u8>,
+    /// A container was just opened; its first member decides `{}` vs a newline.
+    just_opened: bool,
+    in_string: bool,
+    escaped: bool,
+    /// Any token has been written.
+    started: bool,
+    /// Inside a top-level number or literal, which ends at whitespace or EOF.
+    bare_scalar: bool,
+    /// A complete top-level value has been written.
+    done: bool,
+    line: usize,
+    column: usize,
+}
+
+impl Printer {
+    fn byte<W: Write>(&mut self, b: u8, out: &mut W) -> Result<(), ConvertError> {
+        if b == b'\n' {
+            self.line += 1;
+            self.column = 0;
+        } else {
+            self.column += 1;
+        }
+        let io_err = |e: std::io::Error| ConvertError::Io(e.to_string());
+
+        if self.in_string {
+            if self.escaped {
+                self.escaped = false;
+            } else if b == b'\\' {
+                self.escaped = true;
+            } else if b == b'"' {
+                self.in_string = false;
+                self.done = self.stack.is_empty();
+            }
+            return out.write_all(&[b]).map_err(io_err);
+        }
+        if b.is_ascii_whitespace() {
+            // Whitespace ends a top-level scalar such as `42`.
+            self.done |= self.bare_scalar;
+            return Ok(());
+        }
+        if self.done {
+            return Err(self.error("trailing characters"));
+        }
+        match b {
+            b'{' | b'[' => {
+                self.open_member(out)?;
+                out.write_all(&[b]).map_err(io_err)?;
+                self.stack.push(if b == b'{' { b'}' } else { b']' });
+                self.started = true;
+                self.just_opened = true;
+            }
+            b'}' | b']' => {
+                if self.stack.pop() != Some(b) {
+                    return Err(self.error("unbalanced brackets"));
+                }
+                if !self.just_opened {
+                    self.newline(out)?;
+                }
+                self.just_opened = false;
+                out.write_all(&[b]).map_err(io_err)?;
+                self.done = self.stack.is_empty();
+            }
+            b',' => {
+                if self.stack.is_empty() {
+                    return Err(self.error("comma outside a container"));
+                }
+                out.write_all(b",").map_err(io_err)?;
+                self.newline(out)?;
+            }
+            b':' => out.write_all(b": ").map_err(io_err)?,
+            _ => {
+                self.open_member(out)?;
+                self.in_string = b == b'"';
+                self.bare_scalar = self.stack.is_empty() && !self.in_string;
+                self.started = true;
+                out.write_all(&[b]).map_err(io_err)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn open_member<W: Write>(&mut self, out: &mut W) -> Result<(), ConvertError> {
+        if self.just_opened {
+            self.just_opened = false;
+            self.newline(out)?;
+        }
+        Ok(())
+    }
+
+    fn newline<W: Write>(&self, out: &mut W) -> Result<(), ConvertError> {
+        out.write_all(b"\n").map_err(|e| ConvertError::Io(e.to_string()))?;
+        for _ in 0..self.stack.len() {
+            out.write_all(b"  ").map_err(|e| ConvertError::Io(e.to_string()))?;
+        }
+        Ok(())
+    }
+
+    fn finish(&self) -> Result<(), ConvertError> {
+        if !self.started || self.in_string || !self.stack.is_empty() {
+            return Err(self.error("unexpected end of input"));
+        }
+        Ok(())
+    }
+
+    fn error(&self, message: &str) -> ConvertError {
+        ConvertError::Parse {
+            format: DataFormat::Json,
+            line: self.line + 1,
+            column: self.column,
+            message: message.into(),
+        }
+    }
+}
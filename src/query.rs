@@ -0,0 +1,104 @@
+//! `application/x-www-form-urlencoded` input and output.
+
+use serde_json::{Map, Value};
+
+use crate::{ConvertError, NestedMode};
+
+/// Serializes an object as `a=1&b=two`. Scalar arrays repeat their key;
+/// nested objects either flatten to `a.b=1` or error, depending on `nested`.
+/// Non-object roots are not representable.
+pub(crate) fn to_query_string(v: &Value, nested: NestedMode) -> Result<Option<String>, ConvertError> {
+    let Some(obj) = v.as_object() else {
+        return Ok(None);
+    };
+    let mut pairs = Vec::new();
+    push_pairs(&mut pairs, None, obj, nested)?;
+    let mut ser = form_urlencoded::Serializer::new(String::new());
+    for (k, v) in &pairs {
+        ser.append_pair(k, v);
+    }
+    Ok(Some(ser.finish()))
+}
+
+fn push_pairs(
+    pairs: &mut Vec<(String, String)>,
+    prefix: Option<&str>,
+    obj: &Map<String, Value>,
+    nested: NestedMode,
+) -> Result<(), ConvertError> {
+    for (k, v) in obj {
+        let key = match prefix {
+            Some(p) => format!("{p}.{k}"),
+            None => k.clone(),
+        };
+        match v {
+            Value::Object(inner) => {
+                if nested == NestedMode::Error {
+                    return Err(nested_error(&key));
+                }
+                push_pairs(pairs, Some(&key), inner, nested)?;
+            }
+            Value::Array(items) => {
+                for item in items {
+                    if item.is_object() || item.is_array() {
+                        return Err(nested_error(&key));
+                    }
+                    pairs.push((key.clone(), query_scalar(item)));
+                }
+            }
+            other => pairs.push((key, query_scalar(other))),
+        }
+    }
+    Ok(())
+}
+
+fn nested_error(key: &str) -> ConvertError {
+    ConvertError::Unsupported {
+        target: "query".into(),
+        reason: format!("nested value at '{key}'"),
+    }
+}
+
+fn query_scalar(v: &Value) -> String {
+    match v {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Parses a single-line `key=value&...` string into an object of strings.
+/// Repeated keys collect into arrays. Deliberately strict: no whitespace,
+/// every pair needs an `=` and a plain key, so prose and YAML aren't claimed.
+/// A lone pair that reads as `.env` (`FOO=bar`) is left to that parser.
+pub(crate) fn parse_query(s: &str) -> Option<Value> {
+    let s = s.trim();
+    if s.is_empty() || s.chars().any(char::is_whitespace) {
+        return None;
+    }
+    if !s.contains('&') && crate::dotenv::parse_dotenv(s).is_some() {
+        return None;
+    }
+    for pair in s.split('&') {
+        let (key, _) = pair.split_once('=')?;
+        let plain = |c: char| c.is_ascii_alphanumeric() || "_-.%+[]".contains(c);
+        if key.is_empty() || !key.chars().all(plain) {
+            return None;
+        }
+    }
+    let mut obj = Map::new();
+    for (k, v) in form_urlencoded::parse(s.as_bytes()) {
+        let v = Value::String(v.into_owned());
+        match obj.get_mut(k.as_ref()) {
+            Some(Value::Array(items)) => items.push(v),
+            Some(existing) => {
+                let first = existing.take();
+                *existing = Value::Array(vec![first, v]);
+            }
+            None => {
+                obj.insert(k.into_owned(), v);
+            }
+        }
+    }
+    Some(Value::Object(obj))
+}
@@ -0,0 +1,90 @@
+//! JSON Schema inference from a sample value.
+
+use serde_json::{Map, Value, json};
+
+/// Infers a JSON Schema describing `value`: `type` for every node,
+/// `properties`/`required` for objects and a merged `items` schema for
+/// arrays. Keys present in only some array elements are left out of
+/// `required`; elements of different types get a `type` list.
+pub fn infer_schema(value: &Value) -> Value {
+    match value {
+        Value::Null => json!({"type": "null"}),
+        Value::Bool(_) => json!({"type": "boolean"}),
+        Value::Number(n) if n.is_f64() => json!({"type": "number"}),
+        Value::Number(_) => json!({"type": "integer"}),
+        Value::String(_) => json!({"type": "string"}),
+        Value::Array(items) => {
+            let mut schema = json!({"type": "array"});
+            if let Some(merged) = items.iter().map(infer_schema).reduce(merge) {
+                schema["items"] = merged;
+            }
+            schema
+        }
+        Value::Object(obj) => {
+            let properties: Map<String, Value> = obj.iter().map(|(k, v)| (k.clone(), infer_schema(v))).collect();
+            let required: Vec<Value> = obj.keys().cloned().map(Value::String).collect();
+            json!({"type": "object", "properties": properties, "required": required})
+        }
+    }
+}
+
+/// Combines the schemas of two array elements into one that accepts both.
+fn merge(a: Value, b: Value) -> Value {
+    if a == b {
+        return a;
+    }
+    match (type_name(&a), type_name(&b)) {
+        (Some("object"), Some("object")) => {
+            let (mut props, other) = (properties(&a), properties(&b));
+            for (key, schema) in other {
+                let merged = match props.remove(&key) {
+                    Some(existing) => merge(existing, schema),
+                    None => schema,
+                };
+                props.insert(key, merged);
+            }
+            let required_in_b = required(&b);
+            let required: Vec<Value> = required(&a).into_iter().filter(|k| required_in_b.contains(k)).collect();
+            json!({"type": "object", "properties": props, "required": required})
+        }
+        (Some("array"), Some("array")) => {
+            let merged = match (a.get("items").cloned(), b.get("items").cloned()) {
+                (Some(x), Some(y)) => Some(merge(x, y)),
+                (x, y) => x.or(y),
+            };
+            let mut schema = json!({"type": "array"});
+            if let Some(items) = merged {
+                schema["items"] = items;
+            }
+            schema
+        }
+        (Some("integer"), Some("number")) | (Some("number"), Some("integer")) => json!({"type": "number"}),
+        _ => {
+            // Different shapes: keep just the union of type names.
+            let mut types: Vec<String> = type_names(&a).into_iter().chain(type_names(&b)).collect();
+            types.sort();
+            types.dedup();
+            json!({"type": types})
+        }
+    }
+}
+
+fn type_name(schema: &Value) -> Option<&str> {
+    schema.get("type")?.as_str()
+}
+
+fn type_names(schema: &Value) -> Vec<String> {
+    match schema.get("type") {
+        Some(Value::String(t)) => vec![t.clone()],
+        Some(Value::Array(ts)) => ts.iter().filter_map(Value::as_str).map(String::from).collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn properties(schema: &Value) -> Map<String, Value> {
+    schema.get("properties").and_then(Value::as_object).cloned().unwrap_or_default()
+}
+
+fn required(schema: &Value) -> Vec<Value> {
+    schema.get("required").and_then(Value::as_array).cloned().unwrap_or_default()
+}
@@ -0,0 +1,120 @@
+//! Format detection on a stream prefix, for inputs that can't seek.
+
+use serde_json::Value;
+use std::io::{self, BufRead, Read};
+
+use crate::{ConvertOptions, DataFormat};
+
+/// Bytes [`sniff_format`] looks at before deciding.
+const SNIFF_BYTES: usize = 8 * 1024;
+
+/// Wraps a reader so upcoming bytes can be inspected without consuming them.
+///
+/// Peeked bytes are kept in memory and replayed by `Read`/`BufRead`, so a pipe
+/// or socket can be sniffed and then converted from the start.
+pub struct PeekReader<R> {
+    inner: R,
+    buf: Vec<u8>,
+    pos: usize,
+    eof: bool,
+}
+
+impl<R: Read> PeekReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self { inner, buf: Vec::new(), pos: 0, eof: false }
+    }
+
+    /// Returns up to `n` upcoming bytes; fewer only at end of input.
+    pub fn peek(&mut self, n: usize) -> io::Result<&[u8]> {
+        let mut chunk = [0u8; 4096];
+        while self.buf.len() - self.pos < n && !self.eof {
+            let want = (n - (self.buf.len() - self.pos)).min(chunk.len());
+            match self.inner.read(&mut chunk[..want]) {
+                Ok(0) => self.eof = true,
+                Ok(read) => self.buf.extend_from_slice(&chunk[..read]),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        let end = (self.pos + n).min(self.buf.len());
+        Ok(&self.buf[self.pos..end])
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R: Read> Read for PeekReader<R> {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        if self.pos == self.buf.len() {
+            return self.inner.read(out);
+        }
+        let n = out.len().min(self.buf.len() - self.pos);
+        out[..n].copy_from_slice(&self.buf[self.pos..self.pos + n]);
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl<R: Read> BufRead for PeekReader<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.pos == self.buf.len() {
+            self.buf.clear();
+            self.pos = 0;
+            self.eof = false;
+            self.peek(SNIFF_BYTES)?;
+        }
+        Ok(&self.buf[self.pos..])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos = (self.pos + amt).min(self.buf.len());
+    }
+}
+
+/// Detects the format of `reader`'s input from its first few kilobytes,
+/// leaving every byte in place for the conversion that follows.
+///
+/// A prefix that doesn't hold the whole input is classified best-effort: a
+/// trailing partial line is ignored, and input opening with `{`/`[` is
+/// reported as JSON (NDJSON if its first line is a complete document).
+pub fn sniff_format<R: Read>(reader: &mut PeekReader<R>, opts: &ConvertOptions) -> io::Result<DataFormat> {
+    let prefix = reader.peek(SNIFF_BYTES)?;
+    let complete = prefix.len() < SNIFF_BYTES;
+    Ok(classify_prefix(prefix, complete, opts))
+}
+
+/// Detects the format from `prefix`, bytes a caller already peeked from a
+/// stream it buffers itself. The prefix is assumed to be cut anywhere, so the
+/// result is best-effort exactly as for [`sniff_format`]: a trailing partial
+/// line is ignored and `{`/`[` input is reported as JSON or NDJSON.
+pub fn sniff_format_bytes(prefix: &[u8]) -> DataFormat {
+    classify_prefix(prefix, false, &ConvertOptions::default())
+}
+
+pub(crate) fn classify_prefix(prefix: &[u8], complete: bool, opts: &ConvertOptions) -> DataFormat {
+    let text = String::from_utf8_lossy(prefix);
+    let text = text.trim_start_matches('\u{feff}');
+    let body = match text.rfind('\n') {
+        Some(i) if !complete => &text[..i],
+        _ => text,
+    };
+
+    let trimmed = body.trim_start();
+    if !complete && (trimmed.starts_with('{') || trimmed.starts_with('[')) {
+        let mut lines = trimmed.lines().filter(|l| !l.trim().is_empty());
+        let first_is_document = lines.next().is_some_and(|l| serde_json::from_str::<Value>(l).is_ok());
+        return if first_is_document && lines.next().is_some() {
+            DataFormat::Ndjson
+        } else {
+            DataFormat::Json
+        };
+    }
+
+    let original = crate::preprocess(body, opts);
+    if original.trim().is_empty() {
+        return DataFormat::Unknown;
+    }
+    crate::parse_prepared(&original, opts).map_or(DataFormat::Unknown, |(_, format)| format)
+}
@@ -0,0 +1,154 @@
+//! Single-target conversion from a reader straight to a writer.
+
+use serde_json::Value;
+use std::io::{BufRead, BufReader, Cursor, Read, Write};
+
+use crate::ndjson::Records;
+use crate::{ConvertError, ConvertOptions, DataFormat, TargetFormat};
+
+/// Records written between explicit flushes of the output.
+const FLUSH_EVERY: usize = 1024;
+
+/// Converts `reader` into `target`, writing the result to `writer`.
+///
+/// When the first non-blank line is a complete JSON object the input is read
+/// as NDJSON and `csv`/`ndjson` output is produced one record at a time, so
+/// memory stays flat however large the input is. CSV columns come from the
+/// first record, and a malformed line stops the stream with
+/// `ConvertError::Parse`. Any other input or target, or options such as a
+/// `preprocessor` or forced `input_format` that need the whole text, are read
+/// whole and converted as by [`convert_to_bytes`](crate::convert_to_bytes).
+///
+/// `max_bytes` caps how much of `reader` is consumed. Under
+/// `allow_permissive`, NDJSON lines that aren't strict JSON are read leniently
+/// instead of stopping the stream; the count of such lines is returned, as
+/// `"RecoveredLines"` is in [`convert_map_with`](crate::convert_map_with).
+pub fn convert_stream<R: Read, W: Write>(
+    reader: R,
+    mut writer: W,
+    target: &TargetFormat,
+    opts: &ConvertOptions,
+) -> Result<usize, ConvertError> {
+    let io_err = |e: std::io::Error| ConvertError::Io(e.to_string());
+    let limit = opts.max_bytes.map_or(u64::MAX, |n| n as u64);
+    let mut reader = BufReader::new(reader.take(limit));
+
+    // Buffer up to the first non-blank line to decide how to read the rest.
+    let mut head = Vec::new();
+    let first_line = loop {
+        let start = head.len();
+        if reader.read_until(b'\n', &mut head).map_err(io_err)? == 0 {
+            break None;
+        }
+        let line = head[start..].trim_ascii();
+        if !line.is_empty() {
+            break Some(start);
+        }
+    };
+    // A leading BOM already fails the object check below, so `strip_bom`
+    // needs no special case.
+    let records_first = opts.input_encoding.is_none()
+        && !needs_whole_input(opts)
+        && first_line.is_some_and(|start| {
+            matches!(serde_json::from_slice(head[start..].trim_ascii()), Ok(Value::Object(_)))
+        });
+    let input = Cursor::new(head).chain(reader);
+
+    match target {
+        #[cfg(feature = "csv")]
+        TargetFormat::Csv if records_first => {
+            if opts.csv_write_bom {
+                writer.write_all("\u{feff}".as_bytes()).map_err(io_err)?;
+            }
+            let mut records = Records::permissive(input, opts.allow_permissive);
+            write_csv_records(&mut records, writer, opts.csv_max_columns)?;
+            Ok(records.recovered)
+        }
+        TargetFormat::Ndjson if records_first => {
+            let mut records = Records::permissive(input, opts.allow_permissive);
+            for (i, record) in records.by_ref().enumerate() {
+                serde_json::to_writer(&mut writer, &record?).map_err(|e| ConvertError::Io(e.to_string()))?;
+                writer.write_all(b"\n").map_err(io_err)?;
+                if (i + 1) % FLUSH_EVERY == 0 {
+                    writer.flush().map_err(io_err)?;
+                }
+            }
+            writer.flush().map_err(io_err)?;
+            Ok(records.recovered)
+        }
+        _ => {
+            let (bytes, recovered) = convert_whole(input, target, opts)?;
+            writer.write_all(&bytes).map_err(io_err)?;
+            writer.flush().map_err(io_err)?;
+            Ok(recovered)
+        }
+    }
+}
+
+/// Options that rewrite the text or the whole parsed value (see
+/// [`crate::rewrites_value`]), sort rows, or steer detection away from
+/// NDJSON need the buffered path.
+fn needs_whole_input(opts: &ConvertOptions) -> bool {
+    let reads_records = |format: DataFormat| matches!(format, DataFormat::Ndjson | DataFormat::Json);
+    let steered_elsewhere = (opts.input_format != DataFormat::Auto && !reads_records(opts.input_format))
+        || opts.mime_hint.as_deref().and_then(crate::format_from_mime).is_some_and(|hint| !reads_records(hint))
+        || opts.disabled_formats.contains(&DataFormat::Ndjson);
+    crate::rewrites_value(opts)
+        || opts.sort_by.is_some()
+        || opts.preprocessor.is_some()
+        || steered_elsewhere
+}
+
+/// The converted bytes and how many NDJSON lines needed the permissive readers.
+fn convert_whole<R: Read>(
+    mut input: R,
+    target: &TargetFormat,
+    opts: &ConvertOptions,
+) -> Result<(Vec<u8>, usize), ConvertError> {
+    let mut buf = Vec::new();
+    input.read_to_end(&mut buf).map_err(|e| ConvertError::Io(e.to_string()))?;
+    let text = match opts.max_bytes {
+        Some(n) if buf.len() >= n => crate::decode(crate::cut_at_char_boundary(&buf, opts), opts)?,
+        _ => crate::decode(&buf, opts)?,
+    };
+    let original = crate::preprocess(&text, opts);
+    let (val, detected) = crate::parse_prepared(&original, opts).map_err(|()| crate::unrecognized(&text, opts))?;
+    let recovered = match detected {
+        DataFormat::Ndjson if opts.allow_permissive => crate::recovered_ndjson_lines(&original, opts),
+        _ => 0,
+    };
+    Ok((crate::convert_to_bytes(&val, target, opts)?, recovered))
+}
+
+#[cfg(feature = "csv")]
+fn write_csv_records<I, W>(mut records: I, writer: W, max_columns: Option<usize>) -> Result<(), ConvertError>
+where
+    I: Iterator<Item = Result<Value, ConvertError>>,
+    W: Write,
+{
+    let csv_err = |e: csv::Error| ConvertError::Io(e.to_string());
+    let not_object = |row: usize| ConvertError::Unsupported {
+        target: "csv".into(),
+        reason: format!("record {row} is not an object"),
+    };
+    let mut wtr = csv::Writer::from_writer(writer);
+    let Some(first) = records.next().transpose()? else {
+        return Ok(());
+    };
+    let headers: Vec<String> = first.as_object().ok_or_else(|| not_object(1))?.keys().cloned().collect();
+    if let Some(limit) = max_columns.filter(|&limit| headers.len() > limit) {
+        return Err(crate::table::too_many_columns(headers.len(), limit));
+    }
+    wtr.write_record(&headers).map_err(csv_err)?;
+
+    for (i, record) in std::iter::once(Ok(first)).chain(records).enumerate() {
+        let record = record?;
+        let obj = record.as_object().ok_or_else(|| not_object(i + 1))?;
+        let row = headers.iter().map(|h| obj.get(h).map(crate::table::cell_string).unwrap_or_default());
+        wtr.write_record(row).map_err(csv_err)?;
+        if (i + 1) % FLUSH_EVERY == 0 {
+            wtr.flush().map_err(|e| ConvertError::Io(e.to_string()))?;
+        }
+    }
+    wtr.flush().map_err(|e| ConvertError::Io(e.to_string()))
+}
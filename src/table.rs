@@ -0,0 +1,269 @@
+//! Shared helpers for tabular targets (CSV, ASCII tables).
+
+use serde_json::{Map, Value};
+use std::cmp::Ordering;
+use std::collections::HashSet;
+
+use crate::SortOrder;
+
+/// An array of objects viewed as rows plus the union of their keys.
+pub(crate) struct Table<'a> {
+    pub rows: Vec<&'a Map<String, Value>>,
+    pub headers: Vec<String>,
+}
+
+impl<'a> Table<'a> {
+    /// Returns `None` for anything that isn't an array of objects.
+    pub fn from_value(v: &'a Value) -> Option<Self> {
+        let rows = v
+            .as_array()?
+            .iter()
+            .map(Value::as_object)
+            .collect::<Option<Vec<_>>>()?;
+        // First-seen order across rows, so `name, id, email` isn't alphabetized.
+        // Within a row this is the source order only with `preserve_order`.
+        let mut seen = HashSet::new();
+        let headers = rows
+            .iter()
+            .flat_map(|obj| obj.keys())
+            .filter(|k| seen.insert(*k))
+            .cloned()
+            .collect();
+        Some(Self { rows, headers })
+    }
+}
+
+/// A copy of an array of objects with its rows stably sorted by `column`;
+/// `None` for anything that isn't an array of objects.
+pub(crate) fn sort_rows(v: &Value, column: &str, order: SortOrder) -> Option<Value> {
+    Table::from_value(v)?;
+    let mut rows = v.as_array()?.clone();
+    rows.sort_by(|a, b| {
+        let (a, b) = (sort_key(a.get(column)), sort_key(b.get(column)));
+        match (a, b, order) {
+            // Rows without a sortable value stay at the end either way.
+            (None, None, _) => Ordering::Equal,
+            (None, Some(_), _) => Ordering::Greater,
+            (Some(_), None, _) => Ordering::Less,
+            (Some(a), Some(b), SortOrder::Ascending) => a.cmp(&b),
+            (Some(a), Some(b), SortOrder::Descending) => b.cmp(&a),
+        }
+    });
+    Some(Value::Array(rows))
+}
+
+/// A cell's place in the sort order: numbers, then strings, then booleans.
+enum SortKey<'a> {
+    Number(f64),
+    String(&'a str),
+    Bool(bool),
+}
+
+impl SortKey<'_> {
+    fn rank(&self) -> u8 {
+        match self {
+            SortKey::Number(_) => 0,
+            SortKey::String(_) => 1,
+            SortKey::Bool(_) => 2,
+        }
+    }
+
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (SortKey::Number(a), SortKey::Number(b)) => a.total_cmp(b),
+            (SortKey::String(a), SortKey::String(b)) => a.cmp(b),
+            (SortKey::Bool(a), SortKey::Bool(b)) => a.cmp(b),
+            _ => self.rank().cmp(&other.rank()),
+        }
+    }
+}
+
+fn sort_key(cell: Option<&Value>) -> Option<SortKey<'_>> {
+    match cell? {
+        Value::Number(n) => n.as_f64().map(SortKey::Number),
+        Value::String(s) => Some(SortKey::String(s)),
+        Value::Bool(b) => Some(SortKey::Bool(*b)),
+        _ => None,
+    }
+}
+
+/// CSV's header row for `v`: the [`Table`] headers of an array of objects,
+/// or `col0..colN` sized to the widest row of an array of arrays.
+pub(crate) fn csv_headers(v: &Value) -> Option<Vec<String>> {
+    match matrix_rows(v) {
+        Some(rows) => Some(matrix_headers(rows.iter().map(|row| row.len()).max().unwrap_or(0))),
+        None => Table::from_value(v).map(|t| t.headers),
+    }
+}
+
+pub(crate) fn matrix_headers(width: usize) -> Vec<String> {
+    (0..width).map(|i| format!("col{i}")).collect()
+}
+
+/// The rows of a non-empty array whose elements are all arrays.
+pub(crate) fn matrix_rows(v: &Value) -> Option<Vec<&Vec<Value>>> {
+    let rows = v.as_array().filter(|rows| !rows.is_empty())?;
+    rows.iter().map(Value::as_array).collect()
+}
+
+/// The error for CSV output wider than `ConvertOptions::csv_max_columns`.
+#[cfg(feature = "csv")]
+pub(crate) fn too_many_columns(columns: usize, limit: usize) -> crate::ConvertError {
+    crate::ConvertError::Unsupported {
+        target: "csv".into(),
+        reason: format!("{columns} columns exceed csv_max_columns ({limit})"),
+    }
+}
+
+/// Renders a scalar as a single tabular cell. Strings are emitted raw (no JSON
+/// quotes) so writers can apply their own quoting; nested values fall back to
+/// compact JSON.
+pub(crate) fn cell_string(v: &Value) -> String {
+    match v {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Reads a tabular cell as the JSON scalar it spells: integers, floats,
+/// `true`/`false`, and `null`/empty. Anything else stays a string, including
+/// numbers with leading zeros (`007`) so ZIP codes and IDs survive.
+pub(crate) fn infer_scalar(s: &str) -> Value {
+    match s {
+        "" | "null" => return Value::Null,
+        "true" => return Value::Bool(true),
+        "false" => return Value::Bool(false),
+        _ => {}
+    }
+    let digits = s.strip_prefix('-').unwrap_or(s);
+    let numeric = digits.starts_with(|c: char| c.is_ascii_digit())
+        && digits.chars().all(|c| c.is_ascii_digit() || ".eE+-".contains(c));
+    let leading_zero = digits.len() > 1 && digits.starts_with('0') && !digits.starts_with("0.");
+    if numeric && !leading_zero {
+        if let Ok(i) = s.parse::<i64>() {
+            return Value::from(i);
+        }
+        if let Ok(u) = s.parse::<u64>() {
+            return Value::from(u);
+        }
+        if let Some(n) = s.parse::<f64>().ok().and_then(serde_json::Number::from_f64) {
+            return Value::Number(n);
+        }
+    }
+    Value::String(s.to_string())
+}
+
+/// Applies [`infer_scalar`] to every string cell of an array of rows.
+pub(crate) fn coerce_rows(v: &mut Value) {
+    if let Value::Array(rows) = v {
+        for row in rows.iter_mut().filter_map(Value::as_object_mut) {
+            for cell in row.values_mut() {
+                if let Value::String(s) = cell {
+                    *cell = infer_scalar(s);
+                }
+            }
+        }
+    }
+}
+
+/// Replaces every string anywhere in `v` that spells a number (`"42"`,
+/// `"-1.5"`) with that number, under the same rules as [`infer_scalar`].
+/// Integers too large for 64 bits stay strings rather than losing digits.
+pub(crate) fn unquote_numbers(v: &mut Value) {
+    let mut stack = vec![v];
+    while let Some(v) = stack.pop() {
+        match v {
+            Value::Array(items) => stack.extend(items.iter_mut()),
+            Value::Object(obj) => stack.extend(obj.values_mut()),
+            Value::String(s) => {
+                let integer = !s.contains(['.', 'e', 'E']);
+                match infer_scalar(s) {
+                    Value::Number(n) if !(integer && n.is_f64()) => *v = Value::Number(n),
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Renders an array of objects as an ASCII box table:
+///
+/// ```text
+/// +----+-------+
+/// | id | name  |
+/// +----+-------+
+/// | 1  | alice |
+/// +----+-------+
+/// ```
+pub(crate) fn to_ascii_table(v: &Value) -> Option<String> {
+    let Table { rows, headers } = Table::from_value(v)?;
+    if headers.is_empty() {
+        return None;
+    }
+    let cells: Vec<Vec<String>> = rows
+        .iter()
+        .map(|obj| {
+            headers
+                .iter()
+                .map(|h| obj.get(h).map(cell_string).unwrap_or_default().replace('\n', " "))
+                .collect()
+        })
+        .collect();
+    let widths: Vec<usize> = headers
+        .iter()
+        .enumerate()
+        .map(|(i, h)| {
+            cells
+                .iter()
+                .map(|row| row[i].chars().count())
+                .chain([h.chars().count()])
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+
+    let border: String = widths.iter().fold("+".to_string(), |mut acc, w| {
+        acc.push_str(&"-".repeat(w + 2));
+        acc.push('+');
+        acc
+    });
+    let line = |values: &[String]| {
+        let mut s = "|".to_string();
+        for (value, w) in values.iter().zip(&widths) {
+            let pad = w - value.chars().count();
+            s.push_str(&format!(" {value}{} |", " ".repeat(pad)));
+        }
+        s
+    };
+
+    let mut out = vec![border.clone(), line(&headers), border.clone()];
+    out.extend(cells.iter().map(|row| line(row)));
+    out.push(border);
+    Some(out.join("\n") + "\n")
+}
+
+/// Rows for a GeoJSON `FeatureCollection`: each feature's `properties` plus
+/// its `geometry` as compact JSON text. `None` for anything else.
+pub(crate) fn geojson_rows(v: &Value) -> Option<Value> {
+    let obj = v.as_object()?;
+    if obj.get("type")?.as_str()? != "FeatureCollection" {
+        return None;
+    }
+    let rows = obj
+        .get("features")?
+        .as_array()?
+        .iter()
+        .map(|feature| {
+            let mut row = feature.get("properties").and_then(Value::as_object).cloned().unwrap_or_default();
+            let geometry = match feature.get("geometry") {
+                None | Some(Value::Null) => Value::Null,
+                Some(g) => Value::String(g.to_string()),
+            };
+            row.insert("geometry".into(), geometry);
+            Value::Object(row)
+        })
+        .collect();
+    Some(Value::Array(rows))
+}
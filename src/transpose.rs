@@ -0,0 +1,50 @@
+//! Reshaping between row-oriented and column-oriented tables.
+
+use serde_json::{Map, Value};
+
+use crate::ConvertError;
+
+/// Swaps rows and columns: `[{"a":1},{"a":2}]` ⇄ `{"a":[1,2]}`.
+///
+/// Rows missing a key get `null` in that column. Going the other way every
+/// value must be an array of the same length. Anything else is
+/// `ConvertError::Unsupported`.
+pub fn transpose(value: &Value) -> Result<Value, ConvertError> {
+    match value {
+        Value::Array(rows) => {
+            let rows = rows
+                .iter()
+                .map(Value::as_object)
+                .collect::<Option<Vec<_>>>()
+                .ok_or_else(|| unsupported("array elements must all be objects"))?;
+            let mut columns = Map::new();
+            for key in rows.iter().flat_map(|row| row.keys()) {
+                if !columns.contains_key(key) {
+                    let column = rows.iter().map(|row| row.get(key).cloned().unwrap_or(Value::Null)).collect();
+                    columns.insert(key.clone(), Value::Array(column));
+                }
+            }
+            Ok(Value::Object(columns))
+        }
+        Value::Object(columns) => {
+            let columns = columns
+                .iter()
+                .map(|(k, v)| v.as_array().map(|items| (k, items)))
+                .collect::<Option<Vec<_>>>()
+                .ok_or_else(|| unsupported("object values must all be arrays"))?;
+            let len = columns.first().map_or(0, |(_, items)| items.len());
+            if let Some((key, items)) = columns.iter().find(|(_, items)| items.len() != len) {
+                return Err(unsupported(&format!("column '{key}' has {} values, expected {len}", items.len())));
+            }
+            let rows = (0..len)
+                .map(|i| Value::Object(columns.iter().map(|(k, items)| ((*k).clone(), items[i].clone())).collect()))
+                .collect();
+            Ok(Value::Array(rows))
+        }
+        _ => Err(unsupported("expected an array of objects or an object of arrays")),
+    }
+}
+
+fn unsupported(reason: &str) -> ConvertError {
+    ConvertError::Unsupported { target: "transpose".into(), reason: reason.into() }
+}
@@ -0,0 +1,246 @@
+//! Universal Binary JSON (UBJSON, draft 12).
+
+use serde_json::{Map, Number, Value};
+
+use crate::{ConvertError, DataFormat};
+
+/// Containers nested deeper than this are rejected when reading, so hostile
+/// input can't overflow the stack.
+const MAX_DEPTH: usize = 512;
+
+/// Encodes `v` as UBJSON. Integers use the smallest type that holds them;
+/// `u64` values past `i64::MAX` become high-precision (`H`) numbers.
+pub(crate) fn to_ubjson(v: &Value) -> Vec<u8> {
+    let mut out = Vec::new();
+    write_value(v, &mut out);
+    out
+}
+
+fn write_value(v: &Value, out: &mut Vec<u8>) {
+    match v {
+        Value::Null => out.push(b'Z'),
+        Value::Bool(true) => out.push(b'T'),
+        Value::Bool(false) => out.push(b'F'),
+        Value::Number(n) => write_number(n, out),
+        Value::String(s) => {
+            out.push(b'S');
+            write_str(s, out);
+        }
+        Value::Array(items) => {
+            out.push(b'[');
+            items.iter().for_each(|item| write_value(item, out));
+            out.push(b']');
+        }
+        Value::Object(obj) => {
+            out.push(b'{');
+            for (k, item) in obj {
+                write_str(k, out);
+                write_value(item, out);
+            }
+            out.push(b'}');
+        }
+    }
+}
+
+fn write_number(n: &Number, out: &mut Vec<u8>) {
+    if let Some(i) = n.as_i64() {
+        write_int(i, out);
+    } else if n.is_u64() {
+        out.push(b'H');
+        write_str(&n.to_string(), out);
+    } else {
+        out.push(b'D');
+        out.extend_from_slice(&n.as_f64().unwrap_or_default().to_be_bytes());
+    }
+}
+
+fn write_int(i: i64, out: &mut Vec<u8>) {
+    if let Ok(b) = u8::try_from(i) {
+        out.extend_from_slice(&[b'U', b]);
+    } else if let Ok(b) = i8::try_from(i) {
+        out.push(b'i');
+        out.extend_from_slice(&b.to_be_bytes());
+    } else if let Ok(s) = i16::try_from(i) {
+        out.push(b'I');
+        out.extend_from_slice(&s.to_be_bytes());
+    } else if let Ok(l) = i32::try_from(i) {
+        out.push(b'l');
+        out.extend_from_slice(&l.to_be_bytes());
+    } else {
+        out.push(b'L');
+        out.extend_from_slice(&i.to_be_bytes());
+    }
+}
+
+/// A length-prefixed string without its `S` marker, as object keys are written.
+fn write_str(s: &str, out: &mut Vec<u8>) {
+    write_int(s.len() as i64, out);
+    out.extend_from_slice(s.as_bytes());
+}
+
+/// Decodes one UBJSON value, including strongly typed (`$`) and counted (`#`)
+/// containers. Trailing bytes after the value are an error.
+pub fn read_ubjson(bytes: &[u8]) -> Result<Value, ConvertError> {
+    let mut reader = Reader { bytes, pos: 0 };
+    let marker = reader.marker()?;
+    let value = reader.value(marker, 0)?;
+    if reader.pos < bytes.len() {
+        return Err(reader.error("trailing bytes after value"));
+    }
+    Ok(value)
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    /// A `Parse` error at the current byte offset, reported as line 0.
+    fn error(&self, reason: &str) -> ConvertError {
+        ConvertError::Parse { format: DataFormat::Ubjson, line: 0, column: self.pos, message: reason.to_string() }
+    }
+
+    fn take(&mut self, n: usize) -> Result<&[u8], ConvertError> {
+        let end = self.pos.checked_add(n).filter(|&end| end <= self.bytes.len());
+        let end = end.ok_or_else(|| self.error("unexpected end of input"))?;
+        let chunk = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(chunk)
+    }
+
+    fn byte(&mut self) -> Result<u8, ConvertError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], ConvertError> {
+        Ok(self.take(N)?.try_into().expect("take returns N bytes"))
+    }
+
+    /// The next marker, skipping no-op (`N`) padding.
+    fn marker(&mut self) -> Result<u8, ConvertError> {
+        let marker = self.peek_marker()?;
+        self.pos += 1;
+        Ok(marker)
+    }
+
+    fn peek_marker(&mut self) -> Result<u8, ConvertError> {
+        while self.bytes.get(self.pos) == Some(&b'N') {
+            self.pos += 1;
+        }
+        self.bytes.get(self.pos).copied().ok_or_else(|| self.error("unexpected end of input"))
+    }
+
+    fn int(&mut self, marker: u8) -> Result<Option<i64>, ConvertError> {
+        Ok(Some(match marker {
+            b'i' => i8::from_be_bytes(self.array()?).into(),
+            b'U' => self.byte()?.into(),
+            b'I' => i16::from_be_bytes(self.array()?).into(),
+            b'l' => i32::from_be_bytes(self.array()?).into(),
+            b'L' => i64::from_be_bytes(self.array()?),
+            _ => return Ok(None),
+        }))
+    }
+
+    fn length(&mut self) -> Result<usize, ConvertError> {
+        let marker = self.marker()?;
+        let len = self.int(marker)?.ok_or_else(|| self.error("expected an integer length"))?;
+        usize::try_from(len).map_err(|_| self.error("negative length"))
+    }
+
+    fn string(&mut self) -> Result<String, ConvertError> {
+        let len = self.length()?;
+        let bytes = self.take(len)?.to_vec();
+        String::from_utf8(bytes).map_err(|_| self.error("string is not UTF-8"))
+    }
+
+    /// The optional `$type` and `#count` after a container's opening marker.
+    fn header(&mut self) -> Result<(Option<u8>, Option<usize>), ConvertError> {
+        let mut fixed = None;
+        if self.peek_marker()? == b'$' {
+            self.pos += 1;
+            fixed = Some(self.byte()?);
+            if self.peek_marker()? != b'#' {
+                return Err(self.error("`$` container without a `#` count"));
+            }
+        }
+        if self.peek_marker()? != b'#' {
+            return Ok((fixed, None));
+        }
+        self.pos += 1;
+        let count = self.length()?;
+        // Every item takes at least a byte, bar fixed-type nulls and booleans;
+        // refusing larger counts keeps a forged header from looping for ages.
+        if count > self.bytes.len() - self.pos {
+            return Err(self.error("count exceeds remaining input"));
+        }
+        Ok((fixed, Some(count)))
+    }
+
+    fn value(&mut self, marker: u8, depth: usize) -> Result<Value, ConvertError> {
+        if let Some(i) = self.int(marker)? {
+            return Ok(i.into());
+        }
+        Ok(match marker {
+            b'Z' => Value::Null,
+            b'T' => Value::Bool(true),
+            b'F' => Value::Bool(false),
+            b'd' => f64::from(f32::from_be_bytes(self.array()?)).into(),
+            b'D' => f64::from_be_bytes(self.array()?).into(),
+            b'H' => {
+                let digits = self.string()?;
+                Value::Number(digits.parse().map_err(|_| self.error("invalid high-precision number"))?)
+            }
+            b'C' => Value::String(char::from(self.byte()?).to_string()),
+            b'S' => Value::String(self.string()?),
+            b'[' | b'{' if depth >= MAX_DEPTH => return Err(self.error("nested too deeply")),
+            b'[' => {
+                let (fixed, count) = self.header()?;
+                let mut items = Vec::new();
+                match count {
+                    Some(n) => {
+                        for _ in 0..n {
+                            let marker = match fixed {
+                                Some(t) => t,
+                                None => self.marker()?,
+                            };
+                            items.push(self.value(marker, depth + 1)?);
+                        }
+                    }
+                    None => loop {
+                        match self.marker()? {
+                            b']' => break,
+                            marker => items.push(self.value(marker, depth + 1)?),
+                        }
+                    },
+                }
+                Value::Array(items)
+            }
+            b'{' => {
+                let (fixed, count) = self.header()?;
+                let mut obj = Map::new();
+                let mut remaining = count;
+                loop {
+                    match remaining.as_mut() {
+                        Some(0) => break,
+                        Some(n) => *n -= 1,
+                        None if self.peek_marker()? == b'}' => {
+                            self.pos += 1;
+                            break;
+                        }
+                        None => {}
+                    }
+                    // Keys are strings without the `S` marker.
+                    let key = self.string()?;
+                    let marker = match fixed {
+                        Some(t) => t,
+                        None => self.marker()?,
+                    };
+                    obj.insert(key, self.value(marker, depth + 1)?);
+                }
+                Value::Object(obj)
+            }
+            other => return Err(self.error(&format!("unknown marker {:?}", char::from(other)))),
+        })
+    }
+}
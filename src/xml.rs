@@ -0,0 +1,216 @@
+//! XML documents to and from JSON values.
+
+use quick_xml::Reader;
+use quick_xml::escape::escape;
+use quick_xml::events::{BytesStart, Event};
+use serde_json::{Map, Value};
+
+/// Deepest element nesting read or written; deeper documents are rejected.
+const MAX_DEPTH: usize = 128;
+
+/// Whether `s` is worth handing to [`xml_to_value`]: it opens with a tag,
+/// declaration or comment.
+pub(crate) fn looks_like_xml(s: &str) -> bool {
+    s.trim_start().starts_with('<')
+}
+
+/// Converts `s` to `{"<root>": ...}`.
+///
+/// Attributes become `@`-prefixed keys and repeated sibling elements become
+/// arrays. An element with only text collapses to that (trimmed) string, an
+/// empty one to `null`; text next to attributes or children is kept under
+/// `"#text"`. Comments, processing instructions and the doctype are skipped.
+/// Nesting deeper than `MAX_DEPTH` is rejected.
+pub(crate) fn xml_to_value(s: &str) -> Option<Value> {
+    if !looks_like_xml(s) {
+        return None;
+    }
+    let mut reader = Reader::from_str(s);
+    let mut stack: Vec<Element> = Vec::new();
+    let mut root: Option<(String, Value)> = None;
+
+    loop {
+        match reader.read_event().ok()? {
+            Event::Start(start) => {
+                if root.is_some() || stack.len() >= MAX_DEPTH {
+                    return None;
+                }
+                stack.push(Element::open(&start)?);
+            }
+            Event::Empty(start) => {
+                if root.is_some() {
+                    return None;
+                }
+                let element = Element::open(&start)?;
+                finish(element, &mut stack, &mut root);
+            }
+            Event::End(_) => {
+                let element = stack.pop()?;
+                finish(element, &mut stack, &mut root);
+            }
+            Event::Text(text) => {
+                let text = text.unescape().ok()?;
+                match stack.last_mut() {
+                    Some(element) => element.text.push_str(&text),
+                    None if text.trim().is_empty() => {}
+                    None => return None,
+                }
+            }
+            Event::CData(data) => {
+                let text = std::str::from_utf8(&data).ok()?;
+                stack.last_mut()?.text.push_str(text);
+            }
+            Event::Eof => break,
+            Event::Comment(_) | Event::Decl(_) | Event::PI(_) | Event::DocType(_) => {}
+        }
+    }
+    if !stack.is_empty() {
+        return None;
+    }
+    let (name, value) = root?;
+    let mut doc = Map::new();
+    doc.insert(name, value);
+    Some(Value::Object(doc))
+}
+
+struct Element {
+    name: String,
+    fields: Map<String, Value>,
+    text: String,
+}
+
+impl Element {
+    fn open(start: &BytesStart) -> Option<Self> {
+        let name = std::str::from_utf8(start.name().as_ref()).ok()?.to_string();
+        let mut fields = Map::new();
+        for attr in start.attributes() {
+            let attr = attr.ok()?;
+            let key = std::str::from_utf8(attr.key.as_ref()).ok()?;
+            let value = attr.unescape_value().ok()?;
+            fields.insert(format!("@{key}"), Value::String(value.into_owned()));
+        }
+        Some(Self { name, fields, text: String::new() })
+    }
+
+    fn into_value(self) -> Value {
+        let text = self.text.trim();
+        if self.fields.is_empty() {
+            return if text.is_empty() { Value::Null } else { Value::String(text.to_string()) };
+        }
+        let mut fields = self.fields;
+        if !text.is_empty() {
+            fields.insert("#text".into(), Value::String(text.to_string()));
+        }
+        Value::Object(fields)
+    }
+}
+
+/// Attaches a closed element to its parent, or records it as the root.
+fn finish(element: Element, stack: &mut [Element], root: &mut Option<(String, Value)>) {
+    let name = element.name.clone();
+    let value = element.into_value();
+    let Some(parent) = stack.last_mut() else {
+        *root = Some((name, value));
+        return;
+    };
+    // Element values are never arrays, so an array here means repeats.
+    match parent.fields.get_mut(&name) {
+        Some(Value::Array(items)) => items.push(value),
+        Some(existing) => *existing = Value::Array(vec![existing.take(), value]),
+        None => {
+            parent.fields.insert(name, value);
+        }
+    }
+}
+
+/// Renders `v` as an XML document, the inverse of [`xml_to_value`].
+///
+/// `v` must be an object with one key, the root element. Nested objects
+/// become child elements, arrays repeat their element once per item, and
+/// scalars become text (`null` an empty element). `@`-prefixed keys are
+/// attributes and `"#text"` is the element's own text. `None` if there is no
+/// single root, a key isn't a valid element name, an array holds arrays, or
+/// the value nests deeper than `MAX_DEPTH`.
+pub(crate) fn to_xml(v: &Value) -> Option<String> {
+    let obj = v.as_object().filter(|obj| obj.len() == 1)?;
+    let (name, root) = obj.iter().next()?;
+    if root.is_array() {
+        return None;
+    }
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    write_element(name, root, 0, &mut out)?;
+    Some(out)
+}
+
+fn write_element(name: &str, v: &Value, depth: usize, out: &mut String) -> Option<()> {
+    if depth >= MAX_DEPTH || !is_xml_name(name) {
+        return None;
+    }
+    let pad = "  ".repeat(depth);
+    out.push_str(&format!("{pad}<{name}"));
+    let Value::Object(fields) = v else {
+        match scalar_text(v)? {
+            text if text.is_empty() => out.push_str("/>\n"),
+            text => out.push_str(&format!(">{}</{name}>\n", escape(&text))),
+        }
+        return Some(());
+    };
+
+    let mut text = String::new();
+    let mut children = Vec::new();
+    for (key, field) in fields {
+        if let Some(attr) = key.strip_prefix('@') {
+            if !is_xml_name(attr) {
+                return None;
+            }
+            out.push_str(&format!(" {attr}=\"{}\"", escape(&scalar_text(field)?)));
+        } else if key == "#text" {
+            text = scalar_text(field)?;
+        } else {
+            children.push((key, field));
+        }
+    }
+    if children.is_empty() {
+        match text.is_empty() {
+            true => out.push_str("/>\n"),
+            false => out.push_str(&format!(">{}</{name}>\n", escape(&text))),
+        }
+        return Some(());
+    }
+    out.push_str(">\n");
+    if !text.is_empty() {
+        out.push_str(&format!("{pad}  {}\n", escape(&text)));
+    }
+    for (key, child) in children {
+        match child {
+            Value::Array(items) => {
+                for item in items {
+                    if item.is_array() {
+                        return None;
+                    }
+                    write_element(key, item, depth + 1, out)?;
+                }
+            }
+            _ => write_element(key, child, depth + 1, out)?,
+        }
+    }
+    out.push_str(&format!("{pad}</{name}>\n"));
+    Some(())
+}
+
+/// Text for a scalar; `None` for objects and arrays.
+fn scalar_text(v: &Value) -> Option<String> {
+    match v {
+        Value::Null => Some(String::new()),
+        Value::String(s) => Some(s.clone()),
+        Value::Bool(_) | Value::Number(_) => Some(v.to_string()),
+        Value::Array(_) | Value::Object(_) => None,
+    }
+}
+
+fn is_xml_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_alphabetic() || c == '_')
+        && chars.all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.' | ':'))
+        && !name.to_lowercase().starts_with("xml")
+}
@@ -0,0 +1,98 @@
+//! YAML emitter variant that writes short scalar arrays in flow style.
+
+use serde_json::{Map, Value};
+
+/// Renders `v` like `serde_yaml`, except arrays holding only scalars become
+/// flow sequences (`ports: [80, 443]`). Objects and other arrays stay block
+/// style.
+pub(crate) fn to_yaml_inline_arrays(v: &Value) -> Option<String> {
+    let mut out = String::new();
+    match v {
+        Value::Object(map) if !map.is_empty() => write_map(map, 0, &mut out)?,
+        Value::Array(items) if !is_flow(items) => write_seq(items, 0, &mut out)?,
+        other => {
+            out.push_str(&inline(other)?);
+            out.push('\n');
+        }
+    }
+    Some(out)
+}
+
+fn is_flow(items: &[Value]) -> bool {
+    items.iter().all(|item| !item.is_object() && !item.is_array())
+}
+
+/// A value that fits on one line: a scalar, flow sequence or empty container.
+fn inline(v: &Value) -> Option<String> {
+    Some(match v {
+        Value::Array(items) if is_flow(items) => {
+            let items = items.iter().map(flow_item).collect::<Option<Vec<_>>>()?;
+            format!("[{}]", items.join(", "))
+        }
+        Value::Object(map) if map.is_empty() => "{}".into(),
+        Value::String(s) if s.contains('\n') => Value::String(s.clone()).to_string(),
+        scalar => serde_yaml::to_string(scalar).ok()?.trim_end().to_string(),
+    })
+}
+
+fn flow_item(v: &Value) -> Option<String> {
+    match v {
+        // Flow indicators would end a plain scalar early.
+        Value::String(s) if s.contains([',', '[', ']', '{', '}']) => Some(Value::String(s.clone()).to_string()),
+        other => inline(other),
+    }
+}
+
+fn is_block(v: &Value) -> bool {
+    match v {
+        Value::Object(map) => !map.is_empty(),
+        Value::Array(items) => !is_flow(items),
+        _ => false,
+    }
+}
+
+fn write_map(map: &Map<String, Value>, indent: usize, out: &mut String) -> Option<()> {
+    for (key, value) in map {
+        out.push_str(&" ".repeat(indent));
+        out.push_str(&inline(&Value::String(key.clone()))?);
+        out.push(':');
+        match value {
+            Value::Object(inner) if !inner.is_empty() => {
+                out.push('\n');
+                write_map(inner, indent + 2, out)?;
+            }
+            Value::Array(items) if !is_flow(items) => {
+                out.push('\n');
+                write_seq(items, indent, out)?;
+            }
+            other => {
+                out.push(' ');
+                out.push_str(&inline(other)?);
+                out.push('\n');
+            }
+        }
+    }
+    Some(())
+}
+
+fn write_seq(items: &[Value], indent: usize, out: &mut String) -> Option<()> {
+    for item in items {
+        out.push_str(&" ".repeat(indent));
+        out.push_str("- ");
+        if is_block(item) {
+            // Render at the nested indent, then pull the first line up
+            // beside the dash.
+            let mut nested = String::new();
+            match item {
+                Value::Object(map) => write_map(map, indent + 2, &mut nested)?,
+                Value::Array(inner) => write_seq(inner, indent + 2, &mut nested)?,
+                _ => {}
+            }
+            out.push_str(nested.trim_start_matches(' '));
+        } else {
+            out.push_str(&inline(item)?);
+            out.push('\n');
+        }
+    }
+    Some(())
+}
@@ -1,11 +1,10 @@
-use assert_cmd::prelude::*;
-use assert_cmd::cargo::cargo_bin;
+use assert_cmd::Command;
+use assert_cmd::cargo_bin;
 use predicates::prelude::*;
 use serde_json::Value;
-use std::process::Command;
 
 fn run_with_stdin(stdin: &str, args: &[&str]) -> Value {
-    let mut cmd = Command::from(cargo_bin("llmkit"));
+    let mut cmd = Command::new(cargo_bin!("llmkit"));
     cmd.args(args);
     let assert = cmd
         .write_stdin(stdin)
@@ -40,3 +39,267 @@ fn cli_single_format_flag() {
     assert!(v.get("json").is_none());
     assert!(v.get("yaml").is_some());
 }
+
+#[test]
+fn cli_max_bytes_reports_truncation() {
+    let v = run_with_stdin(r#"{"x":1} extra"#, &["--max-bytes", "7"]);
+    assert_eq!(v.get("Format").unwrap(), "json");
+    assert_eq!(v.get("Truncated").unwrap(), true);
+}
+
+#[cfg(feature = "encoding")]
+#[test]
+fn cli_input_encoding_decodes_latin1_file() {
+    let mut file = tempfile::NamedTempFile::new().unwrap();
+    std::io::Write::write_all(&mut file, b"{\"name\":\"caf\xe9\"}").unwrap();
+    let path = file.path().to_str().unwrap();
+    let v = run_with_stdin("", &["--file", path, "--input-encoding", "latin1"]);
+    assert_eq!(v.get("Format").unwrap(), "json");
+    assert!(v.get("normal").unwrap().as_str().unwrap().contains("café"));
+}
+
+#[test]
+fn cli_rejects_unknown_input_encoding() {
+    Command::new(cargo_bin!("llmkit"))
+        .args(["--input-encoding", "klingon"])
+        .write_stdin("{}")
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains("klingon"));
+}
+
+#[cfg(feature = "csv")]
+#[test]
+fn cli_raw_streams_ndjson_to_csv() {
+    let assert = Command::new(cargo_bin!("llmkit"))
+        .args(["--raw", "--format", "csv"])
+        .write_stdin("{\"id\":1,\"name\":\"a\"}\n{\"id\":2,\"name\":\"b\"}\n")
+        .assert()
+        .success();
+    let out = String::from_utf8(assert.get_output().stdout.clone()).unwrap();
+    assert_eq!(out, "id,name\n1,a\n2,b\n");
+}
+
+#[cfg(unix)]
+#[test]
+fn cli_reads_named_pipe() {
+    let dir = tempfile::tempdir().unwrap();
+    let fifo = dir.path().join("input.fifo");
+    let made = std::process::Command::new("mkfifo").arg(&fifo).status();
+    if !made.is_ok_and(|s| s.success()) {
+        return; // no mkfifo on this system
+    }
+    let writer_path = fifo.clone();
+    let writer = std::thread::spawn(move || std::fs::write(writer_path, br#"{"piped":true}"#).unwrap());
+    let v = run_with_stdin("", &["--file", fifo.to_str().unwrap(), "--format", "json"]);
+    writer.join().unwrap();
+    assert_eq!(v.get("Format").unwrap(), "json");
+    assert_eq!(v.get("normal").unwrap(), r#"{"piped":true}"#);
+}
+
+#[test]
+fn cli_version_lists_enabled_formats() {
+    Command::new(cargo_bin!("llmkit"))
+        .arg("--version")
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match(r"^llmkit \d+\.\d+\.\d+ \(json(, [a-z0-9_.]+)*\)\n$").unwrap());
+}
+
+#[test]
+fn cli_transpose_flag_reshapes_before_converting() {
+    let v = run_with_stdin(r#"{"a":[1,2]}"#, &["--transpose", "--format", "json"]);
+    assert_eq!(v.get("normal").unwrap(), r#"[{"a":1},{"a":2}]"#);
+}
+
+#[cfg(feature = "xml")]
+#[test]
+fn cli_targets_xml() {
+    let v = run_with_stdin(r#"{"user":{"@id":"1","name":"ada"}}"#, &["--targets", "xml"]);
+    assert_eq!(
+        v["xml"],
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<user id=\"1\">\n  <name>ada</name>\n</user>\n"
+    );
+}
+
+#[test]
+fn cli_explain_reports_parser_attempts_on_stderr() {
+    let assert = Command::new(cargo_bin!("llmkit"))
+        .args(["--explain", "--format", "json"])
+        .write_stdin("{'a': True}")
+        .assert()
+        .success()
+        .stderr(predicate::str::starts_with("json: rejected (json)\n"))
+        .stderr(predicate::str::contains("python_repr: needs permissive (json)\n"));
+    let out: Value = serde_json::from_slice(&assert.get_output().stdout).unwrap();
+    assert!(out.get("Format").is_some());
+
+    Command::new(cargo_bin!("llmkit"))
+        .args(["--explain", "--permissive"])
+        .write_stdin("{'a': True}")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("python_repr: accepted (json)"));
+}
+
+#[test]
+fn cli_reports_parse_errors_on_stderr() {
+    Command::new(cargo_bin!("llmkit"))
+        .write_stdin("{\"a\": 1,,}")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\"Format\": \"unknown\""))
+        .stdout(predicate::str::contains("\"Error\": \"input format not recognized"))
+        .stderr(predicate::str::contains("json parse error at line 1, column 9"));
+}
+
+#[test]
+fn cli_indent_sets_json_indentation() {
+    let v = run_with_stdin(r#"{"x":[1]}"#, &["--indent", "4"]);
+    assert_eq!(v["json"], "{\n    \"x\": [\n        1\n    ]\n}");
+    assert_eq!(v["Beautified"], v["json"]);
+
+    let v = run_with_stdin(r#"{"x":1}"#, &["--indent", "tab", "--format", "json"]);
+    assert_eq!(v["json"], "{\n\t\"x\": 1\n}");
+
+    Command::new(cargo_bin!("llmkit")).arg("--indent").write_stdin(r#"{"x":1}"#).assert().code(2);
+}
+
+#[test]
+fn cli_out_writes_file_instead_of_stdout() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("result.json");
+    std::fs::write(&path, "stale contents that should be truncated").unwrap();
+    Command::new(cargo_bin!("llmkit"))
+        .args(["--format", "json", "--out", path.to_str().unwrap()])
+        .write_stdin(r#"{"x":1}"#)
+        .assert()
+        .success()
+        .stdout("");
+    let v: Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    assert_eq!(v["normal"], r#"{"x":1}"#);
+
+    Command::new(cargo_bin!("llmkit"))
+        .args(["--out", dir.path().join("missing/result.json").to_str().unwrap()])
+        .write_stdin(r#"{"x":1}"#)
+        .assert()
+        .failure()
+        .code(1)
+        .stderr(predicate::str::contains("llmkit: cannot write"));
+
+    Command::new(cargo_bin!("llmkit")).arg("--out").write_stdin(r#"{"x":1}"#).assert().code(2).stdout("");
+}
+
+#[test]
+fn cli_compact_prints_wrapper_on_one_line() {
+    let assert = Command::new(cargo_bin!("llmkit"))
+        .args(["--compact", "--format", "json"])
+        .write_stdin(r#"{"x":1}"#)
+        .assert()
+        .success();
+    let out = String::from_utf8(assert.get_output().stdout.clone()).unwrap();
+    assert_eq!(out.lines().count(), 1);
+    let v: Value = serde_json::from_str(&out).unwrap();
+    assert_eq!(v["json"], "{\n  \"x\": 1\n}");
+}
+
+#[test]
+fn cli_detect_only_prints_format_name() {
+    Command::new(cargo_bin!("llmkit"))
+        .arg("--detect-only")
+        .write_stdin("```json\n{\"x\":1}\n```\n")
+        .assert()
+        .success()
+        .stdout("json\n");
+}
+
+#[test]
+fn cli_multiple_files_yield_tagged_results() {
+    let dir = tempfile::tempdir().unwrap();
+    let a = dir.path().join("a.json");
+    let b = dir.path().join("b.json");
+    std::fs::write(&a, r#"{"a":1}"#).unwrap();
+    std::fs::write(&b, "{\"b\":1}\n{\"b\":2}\n").unwrap();
+    let (a, b) = (a.to_str().unwrap(), b.to_str().unwrap());
+
+    let assert = Command::new(cargo_bin!("llmkit")).args(["--format", "json", "--file", a, b]).assert().success();
+    let v: Value = serde_json::from_slice(&assert.get_output().stdout).unwrap();
+    let results = v.as_array().unwrap();
+    assert_eq!(results.len(), 2);
+    assert_eq!((&results[0]["Source"], &results[0]["Format"]), (&a.into(), &"json".into()));
+    assert_eq!((&results[1]["Source"], &results[1]["Format"]), (&b.into(), &"ndjson".into()));
+
+    let assert = Command::new(cargo_bin!("llmkit")).args(["--jsonl", "--format", "json", a, b]).assert().success();
+    let out = String::from_utf8(assert.get_output().stdout.clone()).unwrap();
+    let sources: Vec<Value> = out.lines().map(|l| serde_json::from_str::<Value>(l).unwrap()["Source"].clone()).collect();
+    assert_eq!(sources, [a, b]);
+}
+
+#[test]
+fn cli_reports_unreadable_files_without_panicking() {
+    let dir = tempfile::tempdir().unwrap();
+    let good = dir.path().join("good.json");
+    std::fs::write(&good, r#"{"a":1}"#).unwrap();
+    let missing = dir.path().join("missing.json");
+    let (good, missing) = (good.to_str().unwrap(), missing.to_str().unwrap());
+
+    let assert = Command::new(cargo_bin!("llmkit"))
+        .args(["--format", "json", "--file", missing, good])
+        .assert()
+        .code(1)
+        .stderr(predicate::str::contains(format!("llmkit: {missing}: ")).and(predicate::str::contains("panicked").not()));
+    let v: Value = serde_json::from_slice(&assert.get_output().stdout).unwrap();
+    assert_eq!(v.as_array().unwrap().len(), 1);
+    assert_eq!(v[0]["Source"], good);
+
+    Command::new(cargo_bin!("llmkit"))
+        .args(["--raw", "--format", "csv", "--file", missing])
+        .assert()
+        .code(1)
+        .stderr(predicate::str::contains(format!("llmkit: {missing}: ")).and(predicate::str::contains("panicked").not()));
+}
+
+#[test]
+fn cli_strict_fails_on_unrecognized_input() {
+    let garbage = "{ this is not data";
+    Command::new(cargo_bin!("llmkit")).write_stdin(garbage).assert().success();
+    Command::new(cargo_bin!("llmkit"))
+        .arg("--strict")
+        .write_stdin(garbage)
+        .assert()
+        .code(1)
+        .stdout(predicate::str::contains(r#""Format": "unknown""#))
+        .stderr(predicate::str::contains("llmkit: input format not recognized"));
+    Command::new(cargo_bin!("llmkit")).arg("--strict").write_stdin(r#"{"ok":1}"#).assert().success();
+}
+
+#[cfg(feature = "serde_yaml")]
+#[test]
+fn cli_envelope_format_prints_yaml() {
+    let assert = Command::new(cargo_bin!("llmkit"))
+        .args(["--envelope-format", "yaml", "--format", "json"])
+        .write_stdin(r#"{"x":1}"#)
+        .assert()
+        .success();
+    let out = String::from_utf8(assert.get_output().stdout.clone()).unwrap();
+    assert!(out.starts_with("Beautified: |-\n  {\n    \"x\": 1\n  }\n"), "{out}");
+    assert!(out.contains("\nFormat: json\n"));
+    assert!(out.contains("\nnormal: '{\"x\":1}'\n"));
+}
+
+#[cfg(feature = "toml")]
+#[test]
+fn cli_envelope_format_toml_wraps_batches() {
+    let dir = tempfile::tempdir().unwrap();
+    let a = dir.path().join("a.json");
+    let b = dir.path().join("b.json");
+    std::fs::write(&a, r#"{"a":1}"#).unwrap();
+    std::fs::write(&b, r#"{"b":2}"#).unwrap();
+    let assert = Command::new(cargo_bin!("llmkit"))
+        .args(["--envelope-format", "toml", "--format", "json"])
+        .args([&a, &b])
+        .assert()
+        .success();
+    let out = String::from_utf8(assert.get_output().stdout.clone()).unwrap();
+    assert_eq!(out.matches("[[results]]").count(), 2, "{out}");
+}
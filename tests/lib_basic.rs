@@ -23,9 +23,1729 @@ fn markdown_table_detects() {
     assert_eq!(map.get("Format").unwrap(), "markdown_table");
 }
 
+#[test]
+fn blockquoted_markdown_table_detects() {
+    let md = b"The results:\n\n> | name | qty |\n> |------|-----|\n>   | pen  | 2   |\n\nThat's all - thanks.\n";
+    let map = llmkit::convert_map(md, Some(&["json"]), false, None);
+    assert_eq!(map.get("Format").unwrap(), "markdown_table");
+    assert_eq!(map.get("normal").unwrap(), r#"[{"name":"pen","qty":2}]"#);
+}
+
 #[test]
 fn only_requested_targets_are_included() {
     let map = llmkit::convert_map(br#"{"a":1}"#, Some(&["json"]), false, None);
     assert!(map.contains_key("json"));
     assert!(!map.contains_key("yaml"));
 }
+
+#[cfg(feature = "csv")]
+#[test]
+fn csv_output_quotes_delimiters_quotes_and_newlines() {
+    let input = br#"[{"note":"a, b","quote":"say \"hi\"","multi":"line1\nline2"}]"#;
+    let map = llmkit::convert_map(input, Some(&["csv"]), false, None);
+    let csv_text = map.get("csv").unwrap().as_str().unwrap();
+
+    let mut rdr = csv::Reader::from_reader(csv_text.as_bytes());
+    let headers = rdr.headers().unwrap().clone();
+    let rows: Vec<csv::StringRecord> = rdr.records().map(|r| r.unwrap()).collect();
+    assert_eq!(rows.len(), 1);
+    let cell = |name: &str| {
+        let idx = headers.iter().position(|h| h == name).unwrap();
+        rows[0].get(idx).unwrap().to_string()
+    };
+    assert_eq!(cell("note"), "a, b");
+    assert_eq!(cell("quote"), "say \"hi\"");
+    assert_eq!(cell("multi"), "line1\nline2");
+}
+
+#[test]
+fn max_output_bytes_aborts_oversized_target() {
+    let input = format!("[{}]", vec!["[1,2,3,4,5,6,7,8]"; 64].join(","));
+    let opts = llmkit::ConvertOptions::default()
+        .targets(&["json"])
+        .max_output_bytes(Some(128));
+    let map = llmkit::convert_map_with(input.as_bytes(), &opts);
+    assert_eq!(map.get("json").unwrap(), &serde_json::Value::Null);
+    let errors = map.get("Errors").unwrap();
+    assert_eq!(errors["json"], "json output exceeds 128 bytes");
+
+    let value: serde_json::Value = serde_json::from_str(&input).unwrap();
+    let err = llmkit::convert_value_to_target(&value, &llmkit::TargetFormat::Json, &opts).unwrap_err();
+    assert_eq!(
+        err,
+        llmkit::ConvertError::OutputTooLarge { target: "json".into(), limit: 128 }
+    );
+}
+
+#[test]
+fn max_output_bytes_bounds_markdown_and_toml() {
+    let rows: Vec<serde_json::Value> = (0..1000).map(|i| serde_json::json!({"id": i, "name": "row"})).collect();
+    let value = serde_json::json!({"title": "t", "rows": rows});
+    let opts = llmkit::ConvertOptions::default().max_output_bytes(Some(256));
+    let too_large = |target: &str| llmkit::ConvertError::OutputTooLarge { target: target.into(), limit: 256 };
+
+    let table = llmkit::TargetFormat::MarkdownTable;
+    assert_eq!(llmkit::convert_to_bytes(&value["rows"], &table, &opts).unwrap_err(), too_large("markdown_table"));
+    let sections = opts.clone().markdown_object_mode(llmkit::MarkdownObjectMode::Sections);
+    assert_eq!(llmkit::convert_to_bytes(&value, &table, &sections).unwrap_err(), too_large("markdown_table"));
+    #[cfg(feature = "toml")]
+    assert_eq!(llmkit::convert_to_bytes(&value, &llmkit::TargetFormat::Toml, &opts).unwrap_err(), too_large("toml"));
+
+    let small = serde_json::json!([{"id": 1}]);
+    assert_eq!(llmkit::convert_to_bytes(&small, &table, &opts).unwrap(), b"| id |\n| --- |\n| 1 |\n");
+}
+
+#[cfg(feature = "form_urlencoded")]
+#[test]
+fn query_string_round_trips_flat_object() {
+    let map = llmkit::convert_map(br#"{"a":1,"b":"two words","c":true}"#, Some(&["query"]), false, None);
+    let qs = map.get("query").unwrap().as_str().unwrap();
+    assert_eq!(qs, "a=1&b=two+words&c=true");
+
+    let back = llmkit::convert_map(qs.as_bytes(), None, false, None);
+    assert_eq!(back.get("Format").unwrap(), "query");
+    assert_eq!(back.get("normal").unwrap(), r#"{"a":"1","b":"two words","c":"true"}"#);
+}
+
+#[cfg(feature = "form_urlencoded")]
+#[test]
+fn query_string_nested_objects_flatten_or_error() {
+    let input = br#"{"a":{"b":1},"tags":["x","y"]}"#;
+    let map = llmkit::convert_map(input, Some(&["query"]), false, None);
+    assert_eq!(map.get("query").unwrap(), "a.b=1&tags=x&tags=y");
+
+    let opts = llmkit::ConvertOptions::default()
+        .targets(&["query"])
+        .query_nested(llmkit::NestedMode::Error);
+    let map = llmkit::convert_map_with(input, &opts);
+    assert!(map.get("query").unwrap().is_null());
+    assert_eq!(map.get("Errors").unwrap()["query"], "query: nested value at 'a'");
+}
+
+#[test]
+fn header_block_detects_and_round_trips() {
+    let input = b"Content-Type: application/json\nX-Id: 5\n";
+    let map = llmkit::convert_map(input, Some(&["headers"]), false, None);
+    assert_eq!(map.get("Format").unwrap(), "headers");
+    assert_eq!(map.get("normal").unwrap(), r#"{"Content-Type":"application/json","X-Id":"5"}"#);
+    assert_eq!(map.get("headers").unwrap(), "Content-Type: application/json\nX-Id: 5\n");
+}
+
+#[test]
+fn plain_yaml_mapping_is_not_a_header_block() {
+    let map = llmkit::convert_map(b"name: John\nage: 5\n", None, false, None);
+    assert_ne!(map.get("Format").unwrap(), "headers");
+}
+
+#[test]
+fn stats_count_non_null_conversions() {
+    let opts = llmkit::ConvertOptions::default()
+        .targets(&["json", "nope"])
+        .include_stats(true);
+    let map = llmkit::convert_map_with(br#"{"a":1}"#, &opts);
+    let stats = map.get("Stats").unwrap();
+    assert_eq!(stats["ConversionCount"], 1);
+    assert_eq!(stats["TargetCount"], 2);
+
+    let map = llmkit::convert_map(br#"{"a":1}"#, Some(&["json"]), false, None);
+    assert!(!map.contains_key("Stats"));
+}
+
+#[test]
+fn markdown_sections_render_nested_object() {
+    let input = br#"{"db":{"host":"localhost","port":5432},"name":"app"}"#;
+    let map = llmkit::convert_map(input, Some(&["md"]), false, None);
+    assert!(map.get("markdown_table").unwrap().is_null());
+
+    let opts = llmkit::ConvertOptions::default()
+        .targets(&["md"])
+        .markdown_object_mode(llmkit::MarkdownObjectMode::Sections);
+    let map = llmkit::convert_map_with(input, &opts);
+    assert_eq!(
+        map.get("markdown_table").unwrap(),
+        "# db\n\n- host: localhost\n- port: 5432\n\n# name\n\napp\n"
+    );
+}
+
+#[test]
+fn wrap_as_string_turns_prose_into_object() {
+    let prose = b"Note: the answer is: unclear";
+    let map = llmkit::convert_map(prose, Some(&["json"]), false, None);
+    assert_eq!(map.get("Format").unwrap(), "unknown");
+
+    let opts = llmkit::ConvertOptions::default()
+        .targets(&["json"])
+        .wrap_as_string(Some("text".into()));
+    let map = llmkit::convert_map_with(prose, &opts);
+    assert_eq!(map.get("Format").unwrap(), "text");
+    assert_eq!(map.get("normal").unwrap(), r#"{"text":"Note: the answer is: unclear"}"#);
+    assert!(map.get("json").unwrap().is_string());
+}
+
+#[test]
+fn ascii_table_target_draws_aligned_box() {
+    let input = br#"[{"id":1,"name":"alice"},{"id":22,"name":"bo"}]"#;
+    let map = llmkit::convert_map(input, Some(&["table"]), false, None);
+    assert_eq!(
+        map.get("table").unwrap(),
+        "+----+-------+\n\
+         | id | name  |\n\
+         +----+-------+\n\
+         | 1  | alice |\n\
+         | 22 | bo    |\n\
+         +----+-------+\n"
+    );
+}
+
+#[cfg(feature = "toml")]
+#[test]
+fn warnings_report_nulls_dropped_by_toml() {
+    let opts = llmkit::ConvertOptions::default()
+        .targets(&["toml"])
+        .include_warnings(true);
+    let map = llmkit::convert_map_with(br#"{"a":1,"b":null,"c":{"d":null}}"#, &opts);
+    assert_eq!(map.get("toml").unwrap(), "a = 1\n\n[c]\n");
+    assert_eq!(
+        map.get("Warnings").unwrap(),
+        &serde_json::json!(["toml: null at 'b' dropped", "toml: null at 'c.d' dropped"])
+    );
+}
+
+#[cfg(feature = "csv")]
+#[test]
+fn warnings_report_nested_values_stringified_by_csv() {
+    let opts = llmkit::ConvertOptions::default()
+        .targets(&["csv"])
+        .include_warnings(true);
+    let map = llmkit::convert_map_with(br#"[{"id":1},{"id":2,"meta":{"x":1}}]"#, &opts);
+    assert!(map.get("csv").unwrap().is_string());
+    assert_eq!(
+        map.get("Warnings").unwrap(),
+        &serde_json::json!(["csv: nested object at row 2 col 'meta' stringified"])
+    );
+
+    let map = llmkit::convert_map(br#"[{"id":1}]"#, Some(&["csv"]), false, None);
+    assert!(!map.contains_key("Warnings"));
+}
+
+#[test]
+fn convert_str_matches_bytes_api() {
+    let opts = llmkit::ConvertOptions::default();
+    for input in [r#"{"a":1,"b":"ü"}"#, "|a|b|\n|--|--|\n|1|x|\n", "Content-Type: text/plain\n"] {
+        assert_eq!(llmkit::convert_str(input, &opts), llmkit::convert_map_with(input.as_bytes(), &opts));
+    }
+
+    let truncated = llmkit::convert_str("\"üü\"", &opts.max_bytes(Some(3)));
+    assert_eq!(truncated.get("Original").unwrap(), "\"ü");
+}
+
+#[cfg(all(feature = "csv", feature = "preserve_order"))]
+#[test]
+fn csv_columns_follow_first_seen_order() {
+    let input = br#"[{"name":"a","id":1},{"email":"e@x","id":2}]"#;
+    let map = llmkit::convert_map(input, Some(&["csv"]), false, None);
+    let csv_text = map.get("csv").unwrap().as_str().unwrap();
+    assert_eq!(csv_text.lines().next().unwrap(), "name,id,email");
+}
+
+#[test]
+fn ndjson_target_writes_one_record_per_line() {
+    let map = llmkit::convert_map(br#"[{"a":1},{"a":2}]"#, Some(&["jsonl"]), false, None);
+    assert_eq!(map.get("ndjson").unwrap(), "{\"a\":1}\n{\"a\":2}\n");
+}
+
+#[cfg(feature = "flate2")]
+#[test]
+fn jsonl_gz_round_trips_through_bytes_and_streams() {
+    let value = serde_json::json!([{"id":1,"msg":"hi"},{"id":2,"msg":"there"}]);
+    let opts = llmkit::ConvertOptions::default();
+    let gz = llmkit::convert_to_bytes(&value, &llmkit::TargetFormat::JsonlGz, &opts).unwrap();
+    assert_eq!(&gz[..2], &[0x1f, 0x8b]);
+
+    let map = llmkit::convert_map_with(&gz, &opts.clone().targets(&["json"]));
+    assert_eq!(map.get("Format").unwrap(), "ndjson");
+    assert_eq!(map.get("normal").unwrap(), &value.to_string());
+
+    let records: Vec<_> = llmkit::read_jsonl_gz(gz.as_slice()).map(|r| r.unwrap()).collect();
+    assert_eq!(serde_json::Value::Array(records), value);
+
+    let streamed = llmkit::write_jsonl_gz_records(Vec::new(), value.as_array().unwrap()).unwrap();
+    let back: Vec<_> = llmkit::read_jsonl_gz(streamed.as_slice()).map(|r| r.unwrap()).collect();
+    assert_eq!(serde_json::Value::Array(back), value);
+
+    let map = llmkit::convert_map(br#"[{"a":1}]"#, Some(&["jsonl.gz"]), false, None);
+    assert!(map.get("jsonl.gz").unwrap().is_string());
+}
+
+#[cfg(feature = "flate2")]
+#[test]
+fn gzip_bomb_is_capped_without_max_bytes() {
+    // 257 MiB of NDJSON that compresses to well under a megabyte.
+    let line = serde_json::Value::String("a".repeat(1 << 20));
+    let gz = llmkit::write_jsonl_gz_records(Vec::new(), std::iter::repeat_n(&line, 257)).unwrap();
+    let opts = llmkit::ConvertOptions::default().targets(&["json"]);
+    let err = llmkit::try_convert_map_with(&gz, &opts).unwrap_err();
+    assert!(matches!(err, llmkit::ConvertError::Io(_)), "{err}");
+    assert!(llmkit::convert_map_with(&gz, &opts).contains_key("Error"));
+
+    let map = llmkit::convert_map_with(&gz, &opts.max_bytes(Some(1 << 20)));
+    assert_eq!(map.get("Truncated").unwrap(), true);
+    // The compressed length; the inflated one was never measured.
+    assert_eq!(map.get("OriginalBytes").unwrap(), gz.len());
+}
+
+#[test]
+fn default_targets_skip_tabular_formats_for_objects() {
+    let map = llmkit::convert_map(br#"{"a":1}"#, None, false, None);
+    assert!(map.contains_key("json"));
+    assert!(!map.contains_key("csv"));
+    assert!(!map.contains_key("markdown_table"));
+
+    let map = llmkit::convert_map(br#"[{"a":1}]"#, None, false, None);
+    assert!(map.contains_key("csv"));
+
+    let map = llmkit::convert_map(br#"{"a":1}"#, Some(&["csv"]), false, None);
+    assert!(map.get("csv").unwrap().is_null());
+}
+
+#[cfg(feature = "csv")]
+#[test]
+fn normalize_cleans_fenced_bom_prefixed_csv() {
+    let input = "\u{feff}  ```csv\nid,name,zip\n1,alice,007\n2,bob,10001\n```  \n";
+    let opts = llmkit::ConvertOptions::default()
+        .targets(&["json"])
+        .normalize(true);
+    let map = llmkit::convert_map_with(input.as_bytes(), &opts);
+    assert_eq!(map.get("Format").unwrap(), "csv");
+    assert_eq!(
+        map.get("normal").unwrap(),
+        r#"[{"id":1,"name":"alice","zip":"007"},{"id":2,"name":"bob","zip":10001}]"#
+    );
+}
+
+#[test]
+fn normalize_off_restores_defaults() {
+    let defaults = llmkit::ConvertOptions::default();
+    let opts = llmkit::ConvertOptions::default().normalize(true).normalize(false);
+    assert_eq!(opts.strip_bom, defaults.strip_bom);
+    assert_eq!(opts.strip_fences, defaults.strip_fences);
+    assert_eq!(opts.trim_whitespace, defaults.trim_whitespace);
+    assert_eq!(opts.coerce_scalars, defaults.coerce_scalars);
+    assert_eq!(opts.reject_plain_yaml, defaults.reject_plain_yaml);
+
+    let map = llmkit::convert_map_with(b"```json\n{\"a\":1}\n```", &opts);
+    assert_eq!(map.get("Format").unwrap(), "json");
+}
+
+#[test]
+fn truncation_is_reported() {
+    let input = br#"{"a":1}   trailing"#;
+    let map = llmkit::convert_map(input, Some(&["json"]), false, Some(7));
+    assert_eq!(map.get("Format").unwrap(), "json");
+    assert_eq!(map.get("Truncated").unwrap(), true);
+    assert_eq!(map.get("OriginalBytes").unwrap(), input.len());
+
+    let map = llmkit::convert_map(input, Some(&["json"]), false, Some(input.len()));
+    assert!(!map.contains_key("Truncated"));
+}
+
+#[test]
+fn markdown_table_missing_cells_use_placeholder() {
+    let input = br#"[{"id":1,"name":"alice"},{"id":2}]"#;
+    let map = llmkit::convert_map(input, Some(&["md"]), false, None);
+    assert_eq!(
+        map.get("markdown_table").unwrap(),
+        "| id | name |\n| --- | --- |\n| 1 | alice |\n| 2 |  |\n"
+    );
+
+    let opts = llmkit::ConvertOptions::default()
+        .targets(&["md"])
+        .markdown_empty_cell("—");
+    let map = llmkit::convert_map_with(input, &opts);
+    assert_eq!(
+        map.get("markdown_table").unwrap(),
+        "| id | name |\n| --- | --- |\n| 1 | alice |\n| 2 | — |\n"
+    );
+}
+
+#[test]
+fn python_repr_parses_under_permissive() {
+    let input = br#"{'a': 1, 'b': True, 'c': None, 'd': "it's", 'e': ('x', 'y',)}"#;
+    let map = llmkit::convert_map(input, Some(&["json"]), false, None);
+    assert_ne!(map.get("Format").unwrap(), "json");
+
+    let map = llmkit::convert_map(input, Some(&["json"]), true, None);
+    assert_eq!(map.get("Format").unwrap(), "json");
+    assert_eq!(map.get("normal").unwrap(), r#"{"a":1,"b":true,"c":null,"d":"it's","e":["x","y"]}"#);
+}
+
+#[test]
+fn deep_values_serialize_without_recursion() {
+    let mut deep = serde_json::json!(1);
+    for _ in 0..400 {
+        deep = serde_json::Value::Array(vec![deep]);
+    }
+    let opts = llmkit::ConvertOptions::default();
+    let out = llmkit::convert_value_to_target(&deep, &llmkit::TargetFormat::Json, &opts).unwrap();
+    let out = out.as_str().unwrap();
+    assert!(out.starts_with("[\n  [\n    ["));
+    assert_eq!(out.matches('[').count(), 400);
+    assert_eq!(out.lines().count(), 801);
+}
+
+#[test]
+fn convert_map_reads_json_past_the_parser_recursion_limit() {
+    let input = format!("{}1{}", "[".repeat(500), "]".repeat(500));
+    let map = llmkit::convert_map(input.as_bytes(), Some(&["json"]), false, None);
+    assert_eq!(map["Format"], "json");
+    assert_eq!(map["normal"], input);
+    assert_eq!(map["json"].as_str().unwrap().matches('[').count(), 500);
+
+    let map = llmkit::convert_map(input.as_bytes(), None, false, None);
+    assert_eq!(map["Format"], "json");
+}
+
+#[test]
+fn iterative_json_matches_serde_json_output() {
+    let value = serde_json::json!({"a":[1,{"b":null,"c":[]}],"d":{},"e":"x\"y"});
+    let opts = llmkit::ConvertOptions::default().iterative_json_depth(0);
+    let out = llmkit::convert_value_to_target(&value, &llmkit::TargetFormat::Json, &opts).unwrap();
+    assert_eq!(out.as_str().unwrap(), serde_json::to_string_pretty(&value).unwrap());
+
+    let map = llmkit::convert_map_with(value.to_string().as_bytes(), &opts);
+    assert_eq!(map.get("normal").unwrap(), &value.to_string());
+}
+
+#[test]
+fn target_from_extension_knows_aliases() {
+    use llmkit::{TargetFormat, target_from_extension};
+    assert_eq!(target_from_extension("yml"), Some(TargetFormat::Yaml));
+    assert_eq!(target_from_extension(".YAML"), Some(TargetFormat::Yaml));
+    assert_eq!(target_from_extension("md"), Some(TargetFormat::MarkdownTable));
+    assert_eq!(target_from_extension("jsonl"), Some(TargetFormat::Ndjson));
+    assert_eq!(target_from_extension("jsonl.gz"), Some(TargetFormat::JsonlGz));
+    assert_eq!(target_from_extension("csv"), Some(TargetFormat::Csv));
+    assert_eq!(target_from_extension("exe"), None);
+}
+
+#[test]
+fn strict_utf8_rejects_invalid_bytes() {
+    let input = b"{\"a\":\"\xff\"}";
+    let map = llmkit::convert_map(input, Some(&["json"]), false, None);
+    assert_eq!(map.get("Format").unwrap(), "json");
+
+    let opts = llmkit::ConvertOptions::default()
+        .targets(&["json"])
+        .strict_utf8(true);
+    let map = llmkit::convert_map_with(input, &opts);
+    assert_eq!(map.get("Format").unwrap(), "unknown");
+    assert!(!map.contains_key("json"));
+    let expected = llmkit::ConvertError::Utf8 { valid_up_to: 6 };
+    assert_eq!(map.get("Error").unwrap(), &expected.to_string());
+}
+
+#[test]
+fn max_bytes_cut_inside_a_character_is_truncation_not_bad_utf8() {
+    let input = r#"{"a":"é"}"#.as_bytes();
+    let opts = llmkit::ConvertOptions::default().targets(&["json"]).strict_utf8(true).max_bytes(Some(7));
+    let map = llmkit::convert_map_with(input, &opts);
+    assert!(!map.contains_key("Error"), "{map:?}");
+    assert_eq!(map["Original"], r#"{"a":""#);
+    assert_eq!(map["Truncated"], true);
+
+    let lossy = llmkit::convert_map_with(input, &opts.strict_utf8(false));
+    assert!(!lossy["Original"].as_str().unwrap().contains('\u{fffd}'));
+}
+
+#[test]
+fn concatenated_json_objects_form_a_stream() {
+    let map = llmkit::convert_map(br#"{"a":1}{"b":2}"#, Some(&["json"]), false, None);
+    assert_eq!(map.get("Format").unwrap(), "json_stream");
+    assert_eq!(map.get("normal").unwrap(), r#"[{"a":1},{"b":2}]"#);
+
+    let map = llmkit::convert_map(b"1 2", Some(&["json"]), false, None);
+    assert_ne!(map.get("Format").unwrap(), "json_stream");
+}
+
+#[cfg(feature = "csv")]
+#[test]
+fn convert_stream_writes_csv_before_input_is_exhausted() {
+    use std::cell::Cell;
+    use std::io::{Read, Write};
+    use std::rc::Rc;
+
+    const ROWS: usize = 200_000;
+
+    // Yields `{"id":N,"name":"row"}` lines on demand, counting bytes handed out.
+    struct Rows { next: usize, pending: Vec<u8>, read: Rc<Cell<usize>> }
+    impl Read for Rows {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            if self.pending.is_empty() {
+                if self.next == ROWS {
+                    return Ok(0);
+                }
+                self.pending = format!("{{\"id\":{},\"name\":\"row\"}}\n", self.next).into_bytes();
+                self.next += 1;
+            }
+            let n = buf.len().min(self.pending.len());
+            buf[..n].copy_from_slice(&self.pending[..n]);
+            self.pending.drain(..n);
+            self.read.set(self.read.get() + n);
+            Ok(n)
+        }
+    }
+
+    // Discards output, remembering how much input had been read at the first write.
+    struct Sink { written: usize, read_at_first_write: Option<usize>, read: Rc<Cell<usize>> }
+    impl Write for &mut Sink {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.read_at_first_write.get_or_insert(self.read.get());
+            self.written += buf.len();
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let read = Rc::new(Cell::new(0));
+    let rows = Rows { next: 0, pending: Vec::new(), read: read.clone() };
+    let mut sink = Sink { written: 0, read_at_first_write: None, read: read.clone() };
+    llmkit::convert_stream(rows, &mut sink, &llmkit::TargetFormat::Csv, &llmkit::ConvertOptions::default())
+        .unwrap();
+
+    let total = read.get();
+    assert!(total > 4_000_000);
+    // Output starts long before the input is consumed, so nothing holds it all.
+    assert!(sink.read_at_first_write.unwrap() < total / 10);
+    // Header plus one `N,row` line per record.
+    assert!(sink.written > ROWS * "0,row\n".len());
+}
+
+#[cfg(feature = "csv")]
+#[test]
+fn csv_write_bom_prefixes_utf8_bom() {
+    let value = serde_json::json!([{"city": "Zürich"}]);
+    let opts = llmkit::ConvertOptions::default().csv_write_bom(true);
+    let bytes = llmkit::convert_to_bytes(&value, &llmkit::TargetFormat::Csv, &opts).unwrap();
+    assert!(bytes.starts_with(&[0xEF, 0xBB, 0xBF]));
+    assert_eq!(&bytes[3..], "city\nZürich\n".as_bytes());
+
+    let plain = llmkit::convert_to_bytes(&value, &llmkit::TargetFormat::Csv, &llmkit::ConvertOptions::default());
+    assert!(plain.unwrap().starts_with(b"city"));
+}
+
+#[test]
+fn result_accessors_read_conversions_and_format() {
+    let map = llmkit::convert_map(br#"{"a":{"b":1}}"#, Some(&["json", "csv"]), false, None);
+    assert_eq!(llmkit::detected_format(&map), llmkit::DataFormat::Json);
+    assert!(llmkit::get_conversion(&map, "json").unwrap().contains("\"b\": 1"));
+    // Requested but not representable: stored as null.
+    assert!(map.get("csv").unwrap().is_null());
+    assert_eq!(llmkit::get_conversion(&map, "csv"), None);
+    // Never requested.
+    assert_eq!(llmkit::get_conversion(&map, "yaml"), None);
+
+    let empty = llmkit::convert_map(b"", None, false, None);
+    assert_eq!(llmkit::detected_format(&empty), llmkit::DataFormat::Unknown);
+}
+
+#[test]
+fn prefer_ndjson_for_arrays_emits_one_line_per_element() {
+    let opts = llmkit::ConvertOptions::default().targets(&["json"]).prefer_ndjson_for_arrays(true);
+    let map = llmkit::convert_map_with(br#"[{"a":1},{"a":2}]"#, &opts);
+    assert_eq!(map.get("json").unwrap(), "{\"a\":1}\n{\"a\":2}\n");
+
+    let map = llmkit::convert_map_with(br#"{"a":1}"#, &opts);
+    assert_eq!(map.get("json").unwrap(), "{\n  \"a\": 1\n}");
+}
+
+proptest::proptest! {
+    #![proptest_config(proptest::prelude::ProptestConfig::with_cases(512))]
+
+    #[test]
+    fn convert_map_never_panics_on_arbitrary_bytes(input in proptest::collection::vec(proptest::num::u8::ANY, 0..512)) {
+        let _ = llmkit::convert_map(&input, None, true, None);
+    }
+
+    // Bias towards the characters the format detectors key on.
+    #[test]
+    fn convert_map_never_panics_on_structured_noise(input in r#"[{}\[\]"',:|=&\-#>`\n\t a-z0-9.]{0,256}"#) {
+        let _ = llmkit::convert_map(input.as_bytes(), None, true, Some(128));
+        let opts = llmkit::ConvertOptions::default().normalize(true).include_warnings(true);
+        let _ = llmkit::convert_map_with(input.as_bytes(), &opts);
+    }
+}
+
+#[test]
+fn include_meta_reports_schema_version() {
+    let opts = llmkit::ConvertOptions::default().include_meta(true);
+    let map = llmkit::convert_map_with(br#"{"a":1}"#, &opts);
+    assert_eq!(map.get("SchemaVersion").unwrap(), 1);
+    assert_eq!(llmkit::SCHEMA_VERSION, 1);
+
+    let map = llmkit::convert_map_with(br#"{"a":1}"#, &llmkit::ConvertOptions::default());
+    assert!(!map.contains_key("SchemaVersion"));
+}
+
+#[cfg(feature = "csv")]
+#[test]
+fn csv_duplicate_headers_follow_strategy() {
+    use llmkit::DuplicateHeaders;
+    let input = b"id,id,name\n1,2,a\n";
+    let normal = |mode| {
+        let opts = llmkit::ConvertOptions::default()
+            .targets(&["json"])
+            .reject_plain_yaml(true)
+            .csv_duplicate_headers(mode);
+        let map = llmkit::convert_map_with(input, &opts);
+        (map.get("Format").unwrap().clone(), map.get("normal").unwrap().clone())
+    };
+
+    assert_eq!(normal(DuplicateHeaders::Suffix), ("csv".into(), r#"[{"id":"1","id_2":"2","name":"a"}]"#.into()));
+    assert_eq!(normal(DuplicateHeaders::First), ("csv".into(), r#"[{"id":"1","name":"a"}]"#.into()));
+    assert_ne!(normal(DuplicateHeaders::Error).0, "csv");
+
+    let opts = llmkit::ConvertOptions::default().reject_plain_yaml(true).csv_duplicate_headers(DuplicateHeaders::Error);
+    let err = llmkit::try_convert_map_with(input, &opts).unwrap_err();
+    let llmkit::ConvertError::Unrecognized { cause: Some(cause), .. } = err else { panic!("{err:?}") };
+    assert!(
+        matches!(*cause, llmkit::ConvertError::Parse { format: llmkit::DataFormat::Csv, line: 1, column: 4, ref message } if message == "duplicate header 'id'"),
+        "{cause:?}"
+    );
+}
+
+#[cfg(all(feature = "csv", feature = "serde_yaml"))]
+#[test]
+fn convert_batch_handles_mixed_inputs_in_order() {
+    let inputs: [&[u8]; 3] = [br#"{"a":1}"#, b"a,b\n1,2\n", b"a: 1\nb: two\n"];
+    let opts = llmkit::ConvertOptions::default().targets(&["json"]).reject_plain_yaml(true);
+    let maps = llmkit::convert_batch(&inputs, &opts);
+    let formats: Vec<_> = maps.iter().map(|m| m.get("Format").unwrap().as_str().unwrap()).collect();
+    assert_eq!(formats, ["json", "csv", "yaml"]);
+    assert_eq!(maps[1].get("normal").unwrap(), r#"[{"a":"1","b":"2"}]"#);
+}
+
+#[test]
+fn multi_target_conversion_matches_one_target_at_a_time() {
+    let value: serde_json::Value = (0..200).map(|i| serde_json::json!({"id": i, "tag": format!("t{i}")})).collect();
+    let targets = ["json", "yaml", "toml", "csv", "markdown_table", "ndjson"].map(llmkit::TargetFormat::from_name);
+    let all = llmkit::convert_value_to_formats_with_targets(&value, &targets);
+    let opts = llmkit::ConvertOptions::default();
+    for tgt in &targets {
+        let one = llmkit::convert_value_to_target(&value, tgt, &opts).unwrap_or(serde_json::Value::Null);
+        assert_eq!(all.get(&tgt.name()), Some(&one), "{}", tgt.name());
+    }
+}
+
+#[cfg(feature = "chrono")]
+#[test]
+fn datetime_format_rewrites_timestamps_only() {
+    let opts = llmkit::ConvertOptions::default()
+        .targets(&["json"])
+        .datetime_format(Some("%d/%m/%Y %H:%M".into()))
+        .datetime_timezone(Some("+02:00".into()));
+    let input = br#"{"at":"2024-03-05T22:30:00Z","local":"2024-03-05T08:00:00","note":"2024-13-99T00:00:00Z"}"#;
+    let map = llmkit::convert_map_with(input, &opts);
+    assert_eq!(
+        map.get("normal").unwrap(),
+        r#"{"at":"06/03/2024 00:30","local":"05/03/2024 08:00","note":"2024-13-99T00:00:00Z"}"#
+    );
+}
+
+#[cfg(feature = "toml")]
+#[test]
+fn toml_datetimes_convert_to_plain_strings() {
+    let opts = llmkit::ConvertOptions::default().targets(&["json"]).reject_plain_yaml(true);
+    let map = llmkit::convert_map_with(b"n = 1\nwhen = 2024-01-02T03:04:05Z\n", &opts);
+    assert_eq!(map.get("Format").unwrap(), "toml");
+    assert_eq!(map.get("normal").unwrap(), r#"{"n":1,"when":"2024-01-02T03:04:05Z"}"#);
+}
+
+#[test]
+fn sniff_format_peeks_a_non_seekable_stream_without_consuming_it() {
+    // Only `Read`, handing out a few bytes per call, like a pipe.
+    struct Pipe(std::io::Cursor<Vec<u8>>);
+    impl std::io::Read for Pipe {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let n = buf.len().min(7);
+            self.0.read(&mut buf[..n])
+        }
+    }
+
+    let mut input: Vec<u8> = Vec::new();
+    for i in 0..2000 {
+        input.extend(format!("{{\"id\":{i}}}\n").into_bytes());
+    }
+    let mut reader = llmkit::PeekReader::new(Pipe(std::io::Cursor::new(input)));
+    let opts = llmkit::ConvertOptions::default();
+    assert_eq!(llmkit::sniff_format(&mut reader, &opts).unwrap(), llmkit::DataFormat::Ndjson);
+
+    let mut out = Vec::new();
+    llmkit::convert_stream(&mut reader, &mut out, &llmkit::TargetFormat::Ndjson, &opts).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert_eq!(out.lines().count(), 2000);
+    assert!(out.starts_with("{\"id\":0}\n"));
+}
+
+#[test]
+fn convert_stream_runs_the_preprocessor_on_ndjson() {
+    let opts = llmkit::ConvertOptions::default().preprocessor(|text| text.replace("\"id\"", "\"key\""));
+    let mut out = Vec::new();
+    llmkit::convert_stream(&b"{\"id\":1}\n{\"id\":2}\n"[..], &mut out, &llmkit::TargetFormat::Ndjson, &opts).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), "{\"key\":1}\n{\"key\":2}\n");
+}
+
+#[test]
+fn tabular_headers_unions_keys_in_first_seen_order() {
+    let value = serde_json::json!([{"id": 1, "name": "a"}, {"id": 2, "email": "b@x"}, {"name": "c", "age": 3}]);
+    let opts = llmkit::ConvertOptions::default();
+    assert_eq!(llmkit::tabular_headers(&value, &opts).unwrap(), ["id", "name", "email", "age"]);
+    assert_eq!(llmkit::tabular_headers(&serde_json::json!({"id": 1}), &opts), None);
+    assert_eq!(llmkit::tabular_headers(&serde_json::json!([1, 2]), &opts), None);
+}
+
+#[cfg(feature = "csv")]
+#[test]
+fn tabular_headers_match_the_csv_header_row() {
+    let value = serde_json::json!([{"b": 1, "id": 2}, {"a": 1, "id": 1}]);
+    let opts = llmkit::ConvertOptions::default().sort_by(Some(("id".into(), llmkit::SortOrder::Ascending)));
+    let headers = llmkit::tabular_headers(&value, &opts).unwrap();
+    assert_eq!(headers, ["a", "id", "b"]);
+    let csv = llmkit::convert_to_bytes(&value, &llmkit::TargetFormat::Csv, &opts).unwrap();
+    assert!(String::from_utf8(csv).unwrap().starts_with("a,id,b\n"));
+
+    assert_eq!(llmkit::tabular_headers(&value, &opts.clone().csv_max_columns(Some(2))), None);
+    let matrix = serde_json::json!([[1, 2], [3]]);
+    assert_eq!(llmkit::tabular_headers(&matrix, &opts).unwrap(), ["col0", "col1"]);
+}
+
+#[cfg(feature = "edn")]
+#[test]
+fn permissive_mode_parses_edn_maps() {
+    let input = br#"{:admin true, :age 36 ; comment
+ :boss nil :langs [:clojure "rust"] :name "Ada" :tags #{:x}}"#;
+    let map = llmkit::convert_map(input, Some(&["json"]), true, None);
+    assert_eq!(map.get("Format").unwrap(), "edn");
+    assert_eq!(
+        map.get("normal").unwrap(),
+        r#"{"admin":true,"age":36,"boss":null,"langs":["clojure","rust"],"name":"Ada","tags":["x"]}"#
+    );
+
+    let strict = llmkit::convert_map(br#"{:a 1}"#, Some(&["json"]), false, None);
+    assert_ne!(strict.get("Format").unwrap(), "edn");
+}
+
+#[test]
+fn flatten_joins_object_and_array_paths() {
+    let value = serde_json::json!({"db": {"hosts": ["a", "b"], "port": 5432, "opts": {}}, "debug": true});
+    assert_eq!(
+        llmkit::flatten(&value, "/"),
+        serde_json::json!({"db/hosts/0": "a", "db/hosts/1": "b", "db/port": 5432, "db/opts": {}, "debug": true})
+    );
+
+    let map = llmkit::convert_map(br#"{"a":{"b":[1,2]}}"#, Some(&["flat"]), false, None);
+    assert_eq!(map.get("flat").unwrap(), "{\n  \"a.b.0\": 1,\n  \"a.b.1\": 2\n}");
+}
+
+#[test]
+fn unflatten_inverts_flatten_and_rejects_conflicts() {
+    let nested = serde_json::json!({"db": {"hosts": ["a", {"name": "b"}], "port": 5432, "opts": {}}, "debug": true});
+    for sep in [".", "__"] {
+        let flat = llmkit::flatten(&nested, sep);
+        assert_eq!(llmkit::unflatten(&flat, sep).unwrap(), nested);
+    }
+    assert_eq!(llmkit::unflatten(&llmkit::flatten(&serde_json::json!(7), "."), ".").unwrap(), 7);
+
+    let clash = serde_json::json!({"a": 1, "a.b": 2});
+    let err = llmkit::unflatten(&clash, ".").unwrap_err();
+    assert!(err.to_string().contains("'a' is both a value and a prefix of 'a.b'"), "{err}");
+}
+
+#[test]
+fn disabled_formats_skip_detection() {
+    use llmkit::DataFormat;
+    let opts = llmkit::ConvertOptions::default()
+        .targets(&["json"])
+        .disabled_formats([DataFormat::Yaml].into_iter().collect());
+    let map = llmkit::convert_map_with(b"just some words", &opts);
+    assert_eq!(map.get("Format").unwrap(), "unknown");
+
+    let opts = opts.disabled_formats([DataFormat::Json].into_iter().collect());
+    let map = llmkit::convert_map_with(br#"{"a":1}"#, &opts);
+    assert_ne!(map.get("Format").unwrap(), "json");
+}
+
+#[test]
+fn markdown_stitch_merges_tables_with_matching_headers() {
+    let md = b"| id | name |\n|----|------|\n| 1  | a    |\n\n...continued on the next page...\n\n| id | name |\n|----|------|\n| 2  | b    |\n";
+    let opts = llmkit::ConvertOptions::default().targets(&["json"]).markdown_stitch(true);
+    let map = llmkit::convert_map_with(md, &opts);
+    assert_eq!(map.get("Format").unwrap(), "markdown_table");
+    assert_eq!(map.get("normal").unwrap(), r#"[{"id":1,"name":"a"},{"id":2,"name":"b"}]"#);
+
+    let unstitched = llmkit::convert_map_with(md, &opts.clone().markdown_stitch(false));
+    assert_eq!(unstitched.get("normal").unwrap(), r#"[{"id":1,"name":"a"}]"#);
+
+    let other = b"| id | name |\n|----|------|\n| 1  | a    |\n\ntext\n\n| id | city |\n|----|------|\n| 2  | x    |\n";
+    let map = llmkit::convert_map_with(other, &opts);
+    assert_eq!(map.get("normal").unwrap(), r#"[{"id":1,"name":"a"}]"#);
+}
+
+#[test]
+fn json5_target_unquotes_identifier_keys() {
+    let map = llmkit::convert_map(br#"{"_ok$":null,"content-type":"a","list":[1,{}],"name":"x"}"#, Some(&["json5"]), false, None);
+    assert_eq!(
+        map.get("json5").unwrap(),
+        "{\n  _ok$: null,\n  \"content-type\": \"a\",\n  list: [\n    1,\n    {},\n  ],\n  name: \"x\",\n}"
+    );
+}
+
+#[test]
+fn leading_shebang_and_comments_before_json_are_skipped() {
+    let map = llmkit::convert_map(b"#!/usr/bin/env llmkit\n# generated\n{\"a\":1}\n", Some(&["json"]), false, None);
+    assert_eq!(map.get("Format").unwrap(), "json");
+    assert_eq!(map.get("normal").unwrap(), r#"{"a":1}"#);
+}
+
+#[cfg(feature = "serde_yaml")]
+#[test]
+fn comments_before_yaml_are_left_to_the_yaml_parser() {
+    let map = llmkit::convert_map(b"#!/bin/app\n# settings\nname: x\ntags: ['#1']\n", Some(&["json"]), false, None);
+    assert_eq!(map.get("Format").unwrap(), "yaml");
+    assert_eq!(map.get("normal").unwrap(), r##"{"name":"x","tags":["#1"]}"##);
+}
+
+#[cfg(feature = "cache")]
+#[test]
+fn converter_cache_answers_repeated_inputs() {
+    let converter = llmkit::Converter::new(llmkit::ConvertOptions::default().targets(&["json"])).with_cache(2);
+    let first = converter.convert(br#"{"a":1}"#);
+    assert_eq!(converter.cache_hits(), 0);
+    assert_eq!(converter.convert(br#"{"a":1}"#), first);
+    assert_eq!(converter.cache_hits(), 1);
+
+    converter.convert(br#"{"b":2}"#);
+    converter.convert(br#"{"c":3}"#); // evicts {"a":1}
+    converter.convert(br#"{"a":1}"#);
+    assert_eq!(converter.cache_hits(), 1);
+}
+
+#[test]
+fn transpose_swaps_rows_and_columns() {
+    let rows = serde_json::json!([{"a": 1, "b": "x"}, {"a": 2, "b": "y"}]);
+    let columns = llmkit::transpose(&rows).unwrap();
+    assert_eq!(columns, serde_json::json!({"a": [1, 2], "b": ["x", "y"]}));
+    assert_eq!(llmkit::transpose(&columns).unwrap(), rows);
+
+    let ragged = serde_json::json!([{"a": 1}, {"b": 2}]);
+    assert_eq!(llmkit::transpose(&ragged).unwrap(), serde_json::json!({"a": [1, null], "b": [null, 2]}));
+
+    assert!(llmkit::transpose(&serde_json::json!({"a": [1], "b": [1, 2]})).is_err());
+    assert!(llmkit::transpose(&serde_json::json!(3)).is_err());
+}
+
+#[test]
+fn lazy_conversions_render_targets_on_first_access() {
+    let lazy = llmkit::convert_lazy(br#"[{"a":1},{"a":2}]"#, &llmkit::ConvertOptions::default());
+    assert_eq!(lazy.get("Format").unwrap(), "json");
+    assert!(lazy.target_names().any(|t| t == "markdown_table"));
+    assert!(!lazy.is_evaluated("markdown_table"));
+    assert!(!lazy.is_evaluated("json"));
+
+    assert!(lazy.get("markdown_table").unwrap().as_str().unwrap().starts_with("| a |"));
+    assert!(lazy.is_evaluated("markdown_table"));
+    assert!(!lazy.is_evaluated("json"));
+
+    let map = lazy.into_map();
+    let eager = llmkit::convert_map(br#"[{"a":1},{"a":2}]"#, None, false, None);
+    assert_eq!(map, eager);
+}
+
+#[cfg(feature = "csv")]
+#[test]
+fn geojson_flatten_exports_feature_properties() {
+    let input = br#"{"type":"FeatureCollection","features":[
+        {"type":"Feature","geometry":{"coordinates":[1,2],"type":"Point"},"properties":{"area":10,"city":"a"}},
+        {"type":"Feature","geometry":null,"properties":{"area":20,"city":"b"}}]}"#;
+    let opts = llmkit::ConvertOptions::default().targets(&["csv"]).geojson_flatten(true);
+    let map = llmkit::convert_map_with(input, &opts);
+    assert_eq!(
+        map.get("csv").unwrap(),
+        "area,city,geometry\n10,a,\"{\"\"coordinates\"\":[1,2],\"\"type\"\":\"\"Point\"\"}\"\n20,b,\n"
+    );
+}
+
+#[test]
+fn timeout_abandons_slow_conversions() {
+    use std::time::Duration;
+    // Tens of megabytes of output across every default target: far slower
+    // than the budget.
+    let rows: Vec<String> = (0..200_000).map(|i| format!(r#"{{"id":{i},"name":"row {i}"}}"#)).collect();
+    let input = format!("[{}]", rows.join(","));
+    let opts = llmkit::ConvertOptions::default().timeout(Some(Duration::from_micros(1)));
+    let timed_out = llmkit::ConvertError::Timeout(Duration::from_micros(1)).to_string();
+    let map = llmkit::convert_map_with(input.as_bytes(), &opts);
+    assert_eq!(map.get("Format").unwrap(), "unknown");
+    assert_eq!(map.get("Error").unwrap(), &timed_out);
+    let map = llmkit::convert_str(&input, &opts);
+    assert_eq!(map.get("Error").unwrap(), &timed_out);
+
+    let opts = opts.timeout(Some(Duration::from_secs(30)));
+    let map = llmkit::convert_map_with(br#"{"a":1}"#, &opts);
+    assert_eq!(map.get("Format").unwrap(), "json");
+    assert_eq!(llmkit::convert_str(r#"{"a":1}"#, &opts)["Format"], "json");
+}
+
+#[cfg(feature = "serde_yaml")]
+#[test]
+fn yaml_inline_arrays_uses_flow_style_for_scalar_lists() {
+    let input = br#"{"hosts":[{"aliases":["x"],"ip":"10.0.0.1"}],"server":{"name":"web, main","ports":[80,443]},"tags":["a","b,c"]}"#;
+    let opts = llmkit::ConvertOptions::default().targets(&["yaml"]).yaml_inline_arrays(true);
+    let yaml = llmkit::convert_map_with(input, &opts).get("yaml").unwrap().as_str().unwrap().to_string();
+    assert_eq!(
+        yaml,
+        "hosts:\n- aliases: [x]\n  ip: 10.0.0.1\nserver:\n  name: web, main\n  ports: [80, 443]\ntags: [a, \"b,c\"]\n"
+    );
+    let reparsed: serde_json::Value = serde_yaml::from_str(&yaml).unwrap();
+    assert_eq!(reparsed, serde_json::from_slice::<serde_json::Value>(input).unwrap());
+}
+
+#[test]
+fn write_beautified_matches_the_beautified_field() {
+    let input = r#"{"a": [1, {"b": "x \"}\" y"}, [], {}], "c": null, "d": {"e": [true]}}"#;
+    let mut out = Vec::new();
+    llmkit::write_beautified(input.as_bytes(), &mut out).unwrap();
+    let map = llmkit::convert_map(input.as_bytes(), Some(&[]), false, None);
+    assert_eq!(String::from_utf8(out).unwrap(), map["Beautified"].as_str().unwrap());
+
+    let err = llmkit::write_beautified(&b"{\"a\": [1}"[..], std::io::sink()).unwrap_err();
+    assert!(matches!(err, llmkit::ConvertError::Parse { line: 1, column: 9, .. }), "{err:?}");
+}
+
+#[cfg(feature = "xml")]
+#[test]
+fn xml_elements_map_to_objects_and_repeats_to_arrays() {
+    let map = llmkit::convert_map(b"<root><item>1</item><item>2</item></root>", Some(&[]), false, None);
+    assert_eq!(map["Format"], "xml");
+    assert_eq!(map["normal"], r#"{"root":{"item":["1","2"]}}"#);
+
+    let input = br#"<?xml version="1.0"?>
+<order id="7">
+  <!-- note -->
+  <name>Tea, green</name>
+  <qty unit="kg">2</qty>
+  <gift/>
+</order>"#;
+    let map = llmkit::convert_map(input, Some(&[]), false, None);
+    assert_eq!(map["Format"], "xml");
+    let v: serde_json::Value = serde_json::from_str(map["normal"].as_str().unwrap()).unwrap();
+    assert_eq!(
+        v,
+        serde_json::json!({"order": {"@id": "7", "name": "Tea, green", "qty": {"@unit": "kg", "#text": "2"}, "gift": null}})
+    );
+
+    let map = llmkit::convert_map(b"<a><b></a>", Some(&[]), false, None);
+    assert_ne!(map["Format"], "xml");
+}
+
+#[test]
+fn format_from_mime_maps_common_content_types() {
+    use llmkit::{format_from_mime, DataFormat};
+    assert_eq!(format_from_mime("application/json"), Some(DataFormat::Json));
+    assert_eq!(format_from_mime("Application/JSON; charset=utf-8"), Some(DataFormat::Json));
+    assert_eq!(format_from_mime("application/vnd.api+json"), Some(DataFormat::Json));
+    assert_eq!(format_from_mime("application/x-ndjson"), Some(DataFormat::Ndjson));
+    assert_eq!(format_from_mime("text/csv"), Some(DataFormat::Csv));
+    assert_eq!(format_from_mime("application/x-yaml"), Some(DataFormat::Yaml));
+    assert_eq!(format_from_mime("text/markdown"), Some(DataFormat::MarkdownTable));
+    assert_eq!(format_from_mime("application/octet-stream"), None);
+    assert_eq!(format_from_mime("json"), None);
+}
+
+#[cfg(feature = "csv")]
+#[test]
+fn mime_hint_is_tried_first_and_falls_back_to_detection() {
+    let csv = llmkit::ConvertOptions::default().mime_hint(Some("text/csv".into()));
+    let map = llmkit::convert_map_with(b"age,name\n36,ada\n", &csv);
+    assert_eq!(map["Format"], "csv");
+    assert_eq!(map["normal"], r#"[{"age":"36","name":"ada"}]"#);
+
+    // A wrong hint costs one failed parse, not the conversion.
+    let map = llmkit::convert_map_with(br#"{"a":1}"#, &csv);
+    assert_eq!(map["Format"], "json");
+    let unknown = llmkit::ConvertOptions::default().mime_hint(Some("application/octet-stream".into()));
+    assert_eq!(llmkit::convert_map_with(br#"{"a":1}"#, &unknown)["Format"], "json");
+}
+
+#[test]
+fn unknown_fallback_modes() {
+    use llmkit::UnknownFallback;
+    let input = b"just some words";
+    let run = |mode| {
+        let opts = llmkit::ConvertOptions::default().reject_plain_yaml(true).unknown_fallback(mode);
+        llmkit::convert_map_with(input, &opts)
+    };
+
+    let echo = run(UnknownFallback::Echo);
+    assert_eq!(echo["Format"], "unknown");
+    assert_eq!(echo["normal"], "just some words");
+    assert_eq!(echo["Beautified"], "just some words");
+    assert!(!echo.contains_key("Error"));
+
+    let empty = run(UnknownFallback::Empty);
+    assert_eq!(empty["Original"], "just some words");
+    assert_eq!(empty["normal"], "");
+    assert_eq!(empty["Beautified"], "");
+    assert!(!empty.contains_key("Error"));
+
+    let error = run(UnknownFallback::Error);
+    assert_eq!(error["normal"], "");
+    let message = error["Error"].as_str().unwrap();
+    assert!(message.starts_with("input format not recognized (tried json"), "{message}");
+
+    let blank = llmkit::convert_map_with(b"  \n", &llmkit::ConvertOptions::default().unknown_fallback(UnknownFallback::Error));
+    assert_eq!(blank["Format"], "unknown");
+    assert_eq!(blank["Error"], "input is empty");
+
+    let ok = llmkit::convert_map_with(b"{\"a\":1}", &llmkit::ConvertOptions::default().unknown_fallback(UnknownFallback::Error));
+    assert!(!ok.contains_key("Error"));
+}
+
+#[cfg(feature = "xml")]
+#[test]
+fn xml_target_round_trips_through_the_importer() {
+    let input = r#"<order id="7"><item sku="a1">pen &amp; ink</item><item sku="b2"/><note>rush</note></order>"#;
+    let map = llmkit::convert_map(input.as_bytes(), Some(&["xml"]), false, None);
+    let xml = map["xml"].as_str().unwrap();
+    assert_eq!(
+        xml,
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<order id=\"7\">\n  <item sku=\"a1\">pen &amp; ink</item>\n  <item sku=\"b2\"/>\n  <note>rush</note>\n</order>\n"
+    );
+    let again = llmkit::convert_map(xml.as_bytes(), Some(&["json"]), false, None);
+    assert_eq!(again["normal"], map["normal"]);
+
+    // No single root element to name.
+    let map = llmkit::convert_map(br#"[{"a":1}]"#, Some(&["xml"]), false, None);
+    assert!(map["xml"].is_null());
+}
+
+#[cfg(feature = "xml")]
+#[test]
+fn deeply_nested_xml_is_rejected_without_overflowing() {
+    let depth = 100_000;
+    let input = format!("{}{}", "<a>".repeat(depth), "</a>".repeat(depth));
+    let map = llmkit::convert_map(input.as_bytes(), Some(&["json"]), false, None);
+    assert_ne!(map["Format"], "xml");
+
+    let shallow = format!("{}x{}", "<a>".repeat(100), "</a>".repeat(100));
+    assert_eq!(llmkit::convert_map(shallow.as_bytes(), Some(&[]), false, None)["Format"], "xml");
+
+    let mut deep = serde_json::json!("x");
+    for _ in 0..200 {
+        deep = serde_json::json!({ "a": deep });
+    }
+    assert!(llmkit::convert_to_bytes(&deep, &llmkit::TargetFormat::Xml, &Default::default()).is_err());
+}
+
+#[cfg(feature = "preserve_order")]
+#[test]
+fn value_to_markdown_table_unions_headers_in_first_seen_order() {
+    let v = serde_json::json!([{"name": "a", "n": 1}, {"name": "b", "ok": true}]);
+    let out = llmkit::convert_value_to_formats_with_targets(&v, &[llmkit::TargetFormat::MarkdownTable]);
+    assert_eq!(out["markdown_table"], "| name | n | ok |\n| --- | --- | --- |\n| a | 1 |  |\n| b |  | true |\n");
+
+    let out = llmkit::convert_value_to_formats_with_targets(&serde_json::json!([1, 2]), &[llmkit::TargetFormat::MarkdownTable]);
+    assert!(out["markdown_table"].is_null());
+}
+
+#[cfg(feature = "csv")]
+#[test]
+fn pasted_tsv_keeps_quoted_tabs_and_newlines_in_one_cell() {
+    let input = "sku\tnote\nA1\t\"line one\nline\ttwo\"\nB2\tplain\n";
+    let opts = llmkit::ConvertOptions::default().reject_plain_yaml(true);
+    let map = llmkit::convert_map_with(input.as_bytes(), &opts);
+    assert_eq!(map["Format"], "csv");
+    let rows: serde_json::Value = serde_json::from_str(map["normal"].as_str().unwrap()).unwrap();
+    assert_eq!(
+        rows,
+        serde_json::json!([{"sku": "A1", "note": "line one\nline\ttwo"}, {"sku": "B2", "note": "plain"}])
+    );
+}
+
+#[cfg(feature = "csv")]
+#[test]
+fn csv_detection_sniffs_the_delimiter() {
+    let opts = llmkit::ConvertOptions::default().reject_plain_yaml(true);
+    let map = llmkit::convert_map_with(b"a\tb\n1\t2", &opts);
+    assert_eq!(map["Format"], "csv");
+    assert_eq!(map["normal"], r#"[{"a":"1","b":"2"}]"#);
+
+    // Commas inside cells don't outvote the tabs that split every line.
+    let map = llmkit::convert_map_with(b"name\tnote\nada\thi, there\nbob\tok", &opts);
+    assert_eq!(map["normal"], r#"[{"name":"ada","note":"hi, there"},{"name":"bob","note":"ok"}]"#);
+
+    let map = llmkit::convert_map_with(b"id;price\n1;\"2;50\"\n", &opts);
+    assert_eq!(map["normal"], r#"[{"id":"1","price":"2;50"}]"#);
+
+    // Pipes with a `---` separator are still a markdown table.
+    let map = llmkit::convert_map_with(b"| a | b |\n|---|---|\n| 1 | 2 |", &opts);
+    assert_eq!(map["Format"], "markdown_table");
+}
+
+#[test]
+fn escaped_flatten_keeps_literal_dot_keys() {
+    let v = serde_json::json!({"user.email": "a@b.c", "user": {"name": "x", "a\\b": [1]}});
+    let flat = llmkit::flatten_escaped(&v, ".");
+    assert_eq!(flat, serde_json::json!({"user\\.email": "a@b.c", "user.name": "x", "user.a\\\\b.0": 1}));
+    assert_eq!(llmkit::unflatten_escaped(&flat, ".").unwrap(), v);
+
+    let opts = llmkit::ConvertOptions::default().targets(&["flat"]).treat_dots_as_literal(true);
+    let map = llmkit::convert_map_with(br#"{"user.email":"a@b.c"}"#, &opts);
+    assert_eq!(map["flat"], "{\n  \"user\\\\.email\": \"a@b.c\"\n}");
+}
+
+#[test]
+fn markdown_table_cells_are_typed_by_default() {
+    let input = b"| count | note | ok | ratio | zip |\n|--|--|--|--|--|\n| 42 | null | true | 0.5 | 007 |";
+    let map = llmkit::convert_map_with(input, &llmkit::ConvertOptions::default());
+    assert_eq!(map["Format"], "markdown_table");
+    assert_eq!(map["normal"], r#"[{"count":42,"note":null,"ok":true,"ratio":0.5,"zip":"007"}]"#);
+}
+
+#[cfg(feature = "csv")]
+#[test]
+fn csv_cells_are_typed_under_permissive() {
+    let input = b"count,ok,zip\n42,true,007\n";
+    let opts = llmkit::ConvertOptions::default().reject_plain_yaml(true);
+    let map = llmkit::convert_map_with(input, &opts);
+    assert_eq!(map["Format"], "csv");
+    assert_eq!(map["normal"], r#"[{"count":"42","ok":"true","zip":"007"}]"#);
+
+    let map = llmkit::convert_map_with(input, &opts.allow_permissive(true));
+    assert_eq!(map["Format"], "csv");
+    assert_eq!(map["normal"], r#"[{"count":42,"ok":true,"zip":"007"}]"#);
+}
+
+#[cfg(feature = "json5")]
+#[test]
+fn json5_parses_only_in_permissive_mode() {
+    let input = b"{x: 1, y: 'hi',}";
+    let map = llmkit::convert_map(input, Some(&[]), true, None);
+    assert_eq!(map["Format"], "json");
+    assert_eq!(map["normal"], r#"{"x":1,"y":"hi"}"#);
+
+    // Strict mode leaves it to the other parsers (a YAML flow mapping, if enabled).
+    let strict = llmkit::convert_map(input, Some(&[]), false, None);
+    assert_ne!(strict["Format"], "json");
+}
+
+#[test]
+fn permissive_ndjson_recovers_malformed_lines() {
+    let input = b"{\"id\":1}\n{\"id\":2,}\n{'id': 3}\n{\"id\":4}\nnot json\n";
+    let strict = llmkit::ConvertOptions::default().targets(&["json"]);
+    let map = llmkit::convert_map_with(input, &strict);
+    assert_eq!(map["Format"], "ndjson");
+    assert_eq!(map["normal"], r#"[{"id":1},{"id":4}]"#);
+    assert!(!map.contains_key("RecoveredLines"));
+
+    let map = llmkit::convert_map_with(input, &strict.allow_permissive(true));
+    assert_eq!(map["Format"], "ndjson");
+    assert_eq!(map["normal"], r#"[{"id":1},{"id":2},{"id":3},{"id":4}]"#);
+    assert_eq!(map["RecoveredLines"], 2);
+}
+
+#[test]
+fn convert_stream_recovers_malformed_ndjson_lines() {
+    let input = b"{\"id\":1}\n{\"id\":2,}\n{'id': 3}\n";
+    let mut out = Vec::new();
+    let strict = llmkit::ConvertOptions::default();
+    assert!(llmkit::convert_stream(&input[..], &mut out, &llmkit::TargetFormat::Ndjson, &strict).is_err());
+
+    let mut out = Vec::new();
+    let permissive = strict.allow_permissive(true);
+    let recovered = llmkit::convert_stream(&input[..], &mut out, &llmkit::TargetFormat::Ndjson, &permissive).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), "{\"id\":1}\n{\"id\":2}\n{\"id\":3}\n");
+    assert_eq!(recovered, 2);
+}
+
+#[test]
+fn convert_stream_reports_unrecognized_input() {
+    let err = llmkit::convert_stream(&b"\x00\x01"[..], Vec::new(), &llmkit::TargetFormat::Json, &Default::default())
+        .unwrap_err();
+    assert!(matches!(err, llmkit::ConvertError::Unrecognized { .. }), "{err}");
+}
+
+#[test]
+fn jsonc_comments_are_stripped_in_permissive_mode() {
+    let input = b"{\n  // retries\n  \"n\": 3, /* where to send it */ \"url\": \"http://x/*y*/\"\n}";
+    let map = llmkit::convert_map(input, Some(&[]), true, None);
+    assert_eq!(map["Format"], "json");
+    assert_eq!(map["normal"], r#"{"n":3,"url":"http://x/*y*/"}"#);
+
+    let strict = llmkit::convert_map(input, Some(&[]), false, None);
+    assert_ne!(strict["normal"], map["normal"]);
+}
+
+#[test]
+fn value_diff_reports_nested_object_and_array_differences() {
+    let expected = serde_json::json!({"user": {"email": "x@y", "id": 1, "tags": ["a", "b"]}, "ok": true});
+    let actual = serde_json::json!({"user": {"id": "1", "name": "ada", "tags": ["a", "c", "d"]}, "ok": true});
+    assert_eq!(
+        llmkit::value_diff(&expected, &actual),
+        [
+            "user.email: only in a",
+            "user.id: number vs string",
+            "user.tags[1]: \"b\" vs \"c\"",
+            "user.tags[2]: only in b",
+            "user.name: only in b",
+        ]
+    );
+    assert!(llmkit::value_diff(&expected, &expected).is_empty());
+    assert_eq!(llmkit::value_diff(&serde_json::json!(1), &serde_json::json!(2)), ["(root): 1 vs 2"]);
+}
+
+#[test]
+fn ini_sections_become_nested_objects() {
+    let map = llmkit::convert_map(b"[db]\nhost=localhost\nport=5432", Some(&[]), false, None);
+    assert_eq!(map["Format"], "ini");
+    assert_eq!(map["normal"], r#"{"db":{"host":"localhost","port":"5432"}}"#);
+
+    let input = b"; generated\nname = app\n\n[server]\n# listen address\nbind = \"0.0.0.0\"\n[storage]\nhost = x\n";
+    let map = llmkit::convert_map(input, Some(&[]), false, None);
+    assert_eq!(map["Format"], "ini");
+    assert_eq!(map["normal"], r#"{"name":"app","server":{"bind":"0.0.0.0"},"storage":{"host":"x"}}"#);
+
+    for input in [&b"[[[x]]]\nk=v"[..], b"[a]b]\nk=v", b"[[a]\nk=v"] {
+        let map = llmkit::convert_map(input, Some(&[]), false, None);
+        assert_ne!(map["Format"], "ini", "{}", String::from_utf8_lossy(input));
+    }
+}
+
+#[cfg(feature = "csv")]
+#[test]
+fn sort_by_orders_rows_before_tabular_output() {
+    let input = br#"[{"name":"b","score":10},{"name":"a","score":9.5},{"name":"c"},{"name":"d","score":"n/a"},{"name":"e","score":100}]"#;
+    let opts = llmkit::ConvertOptions::default()
+        .targets(&["csv", "json"])
+        .sort_by(Some(("score".into(), llmkit::SortOrder::Ascending)));
+    let map = llmkit::convert_map_with(input, &opts);
+    assert_eq!(map["csv"], "name,score\na,9.5\nb,10\ne,100\nd,n/a\nc,\n");
+    // Only tabular targets are reordered.
+    assert!(map["json"].as_str().unwrap().starts_with("[\n  {\n    \"name\": \"b\""));
+
+    let opts = opts.sort_by(Some(("score".into(), llmkit::SortOrder::Descending)));
+    let map = llmkit::convert_map_with(input, &opts);
+    assert_eq!(map["csv"], "name,score\nd,n/a\ne,100\nb,10\na,9.5\nc,\n");
+}
+
+#[cfg(feature = "csv")]
+#[test]
+fn warnings_count_rows_in_sorted_order() {
+    let opts = llmkit::ConvertOptions::default()
+        .targets(&["csv"])
+        .include_warnings(true)
+        .sort_by(Some(("id".into(), llmkit::SortOrder::Descending)));
+    let map = llmkit::convert_map_with(br#"[{"id":1,"meta":{"x":1}},{"id":2}]"#, &opts);
+    assert_eq!(map["csv"], "id,meta\n2,\n1,\"{\"\"x\"\":1}\"\n");
+    assert_eq!(
+        map.get("Warnings").unwrap(),
+        &serde_json::json!(["csv: nested object at row 2 col 'meta' stringified"])
+    );
+}
+
+#[test]
+fn dotenv_parses_and_round_trips() {
+    let input = b"# app settings\nexport APP_NAME=\"my app\"\nPORT=8080 # default\nSECRET='x#y'\n";
+    let opts = llmkit::ConvertOptions::default().targets(&["dotenv"]);
+    let map = llmkit::convert_map_with(input, &opts);
+    assert_eq!(map["Format"], "dotenv");
+    assert_eq!(map["normal"], r#"{"APP_NAME":"my app","PORT":"8080","SECRET":"x#y"}"#);
+    assert_eq!(map["dotenv"], "APP_NAME=\"my app\"\nPORT=8080\nSECRET=\"x#y\"\n");
+
+    let prose = llmkit::convert_map(b"Set PORT=8080 first\nthen restart", Some(&[]), false, None);
+    assert_ne!(prose["Format"], "dotenv");
+}
+
+#[test]
+fn dotenv_output_is_safe_to_source() {
+    let value = serde_json::json!({"A": "x;id", "B": "$(id) `id` $HOME", "C": "a&&id", "D": "say \"hi\" \\ done"});
+    let opts = llmkit::ConvertOptions::default();
+    let env = llmkit::convert_value_to_target(&value, &llmkit::TargetFormat::Dotenv, &opts).unwrap();
+    let env = env.as_str().unwrap();
+    assert_eq!(
+        env,
+        "A=\"x;id\"\nB=\"\\$(id) \\`id\\` \\$HOME\"\nC=\"a&&id\"\nD=\"say \\\"hi\\\" \\\\ done\"\n"
+    );
+
+    // Fence stripping would take the backticks for inline code.
+    let back = llmkit::convert_map_with(env.as_bytes(), &opts.clone().targets(&[] as &[&str]).strip_fences(false));
+    assert_eq!(back["Format"], "dotenv");
+    assert_eq!(serde_json::from_str::<serde_json::Value>(back["normal"].as_str().unwrap()).unwrap(), value);
+
+    #[cfg(unix)]
+    {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("t.env");
+        std::fs::write(&path, env).unwrap();
+        let script = format!(". '{}'; printf '%s|%s|%s|%s' \"$A\" \"$B\" \"$C\" \"$D\"", path.display());
+        let out = std::process::Command::new("sh").arg("-c").arg(script).output().unwrap();
+        assert_eq!(String::from_utf8(out.stdout).unwrap(), "x;id|$(id) `id` $HOME|a&&id|say \"hi\" \\ done");
+    }
+}
+
+#[cfg(feature = "toml")]
+#[test]
+fn dotenv_lines_toml_can_read_detect_as_toml() {
+    use llmkit::DataFormat;
+    let opts = llmkit::ConvertOptions::default().targets::<&str>(&[]);
+    let parsed = |map: &std::collections::BTreeMap<String, serde_json::Value>| {
+        serde_json::from_str::<serde_json::Value>(map["normal"].as_str().unwrap()).unwrap()
+    };
+    let map = llmkit::convert_map_with(b"PORT=5432\nDEBUG=true\n", &opts);
+    assert_eq!(map["Format"], "toml");
+    assert_eq!(parsed(&map), serde_json::json!({"PORT": 5432, "DEBUG": true}));
+
+    for input in [&b"export PORT=5432\nDEBUG=true\n"[..], b"PORT=5432\nHOST=localhost\n"] {
+        assert_eq!(llmkit::convert_map_with(input, &opts)["Format"], "dotenv");
+    }
+    let map = llmkit::convert_map_with(b"PORT=5432\nDEBUG=true\n", &opts.input_format(DataFormat::Dotenv));
+    assert_eq!(map["Format"], "dotenv");
+    assert_eq!(parsed(&map), serde_json::json!({"PORT": "5432", "DEBUG": "true"}));
+}
+
+#[test]
+fn one_line_dotenv_is_not_a_query_string() {
+    let map = llmkit::convert_map(b"FOO=bar\n", Some(&[]), false, None);
+    assert_eq!(map["Format"], "dotenv");
+    assert_eq!(map["normal"], r#"{"FOO":"bar"}"#);
+
+    #[cfg(feature = "form_urlencoded")]
+    assert_eq!(llmkit::convert_map(b"FOO=bar&baz=1", Some(&[]), false, None)["Format"], "query");
+}
+
+#[cfg(all(feature = "serde_yaml", feature = "toml"))]
+#[test]
+fn preserve_comments_carries_jsonc_comments_to_yaml_and_toml() {
+    let input = br#"{
+  // Service name shown in logs
+  "name": "api",
+  "server": {
+    /* Bind address;
+       use 0.0.0.0 in containers */
+    "host": "127.0.0.1",
+    "port": 8080 // default port
+  },
+  "tags": ["a", "b"]
+}"#;
+    let opts = llmkit::ConvertOptions::default()
+        .allow_permissive(true)
+        .preserve_comments(true)
+        .targets(&["yaml", "toml"]);
+    let map = llmkit::convert_map_with(input, &opts);
+    assert_eq!(map["Format"], "json");
+    assert_eq!(
+        map["yaml"],
+        "# Service name shown in logs\nname: api\nserver:\n  # Bind address;\n  # use 0.0.0.0 in containers\n  host: 127.0.0.1\n  # default port\n  port: 8080\ntags:\n- a\n- b\n"
+    );
+    assert_eq!(
+        map["toml"],
+        "# Service name shown in logs\nname = \"api\"\ntags = [\"a\", \"b\"]\n\n[server]\n# Bind address;\n# use 0.0.0.0 in containers\nhost = \"127.0.0.1\"\n# default port\nport = 8080\n"
+    );
+
+    let map = llmkit::convert_map_with(input, &opts.preserve_comments(false));
+    assert!(!map["yaml"].as_str().unwrap().contains('#'));
+}
+
+#[test]
+fn try_convert_map_reports_why_input_was_not_recognized() {
+    let ok = llmkit::try_convert_map(br#"{"a":1}"#, Some(&["json"]), false, None).unwrap();
+    assert_eq!(ok["Format"], "json");
+
+    let opts = llmkit::ConvertOptions::default().reject_plain_yaml(true);
+    let err = llmkit::try_convert_map_with(b"{\"a\": 1,, oops", &opts).unwrap_err();
+    let llmkit::ConvertError::Unrecognized { attempted, cause } = &err else {
+        panic!("unexpected {err:?}");
+    };
+    assert_eq!(attempted.first(), Some(&llmkit::DataFormat::Json));
+    assert!(matches!(cause.as_deref(), Some(llmkit::ConvertError::Parse { line: 1, .. })));
+    assert!(err.to_string().starts_with("input format not recognized (tried json"), "{err}");
+
+    let strict = llmkit::ConvertOptions::default().strict_utf8(true);
+    let err = llmkit::try_convert_map_with(b"\xff", &strict).unwrap_err();
+    assert_eq!(err, llmkit::ConvertError::Utf8 { valid_up_to: 0 });
+}
+
+#[cfg(feature = "csv")]
+#[test]
+fn csv_max_columns_rejects_wide_header_unions() {
+    let rows: Vec<serde_json::Value> = (0..5).map(|i| serde_json::json!({ format!("k{i}"): i })).collect();
+    let value = serde_json::Value::Array(rows);
+    let opts = llmkit::ConvertOptions::default().csv_max_columns(Some(4));
+    let err = llmkit::convert_value_to_target(&value, &llmkit::TargetFormat::Csv, &opts).unwrap_err();
+    assert_eq!(err.to_string(), "csv: 5 columns exceed csv_max_columns (4)");
+
+    let opts = opts.csv_max_columns(Some(5));
+    assert!(llmkit::convert_value_to_target(&value, &llmkit::TargetFormat::Csv, &opts).unwrap().is_string());
+}
+
+#[cfg(feature = "csv")]
+#[test]
+fn csv_empty_cells_follow_csv_empty_as() {
+    use llmkit::CsvEmpty;
+    let input = b"id,name\n1,\n2,bo\n";
+    let normal = |mode| {
+        let opts = llmkit::ConvertOptions::default().targets::<&str>(&[]).reject_plain_yaml(true).csv_empty_as(mode);
+        let map = llmkit::convert_map_with(input, &opts);
+        assert_eq!(map["Format"], "csv");
+        map["normal"].as_str().unwrap().to_string()
+    };
+    assert_eq!(normal(CsvEmpty::EmptyString), r#"[{"id":"1","name":""},{"id":"2","name":"bo"}]"#);
+    assert_eq!(normal(CsvEmpty::Null), r#"[{"id":"1","name":null},{"id":"2","name":"bo"}]"#);
+    assert_eq!(normal(CsvEmpty::Omit), r#"[{"id":"1"},{"id":"2","name":"bo"}]"#);
+}
+
+#[test]
+fn parse_errors_carry_line_and_column() {
+    let err = llmkit::try_convert_map(b"{\n  \"a\": 1,\n  \"b\" 2\n}", None, false, None).unwrap_err();
+    let llmkit::ConvertError::Unrecognized { cause: Some(cause), .. } = err else {
+        panic!("unexpected {err:?}");
+    };
+    assert_eq!(cause.to_string(), "json parse error at line 3, column 7: expected `:`");
+}
+
+#[cfg(feature = "toml")]
+#[test]
+fn toml_parse_errors_are_located() {
+    let opts = llmkit::ConvertOptions::default()
+        .disabled_formats([llmkit::DataFormat::Yaml, llmkit::DataFormat::Ini].into_iter().collect())
+        .targets::<&str>(&[]);
+    let err = llmkit::try_convert_map_with(b"[server]\nport = = 1\n", &opts).unwrap_err();
+    let llmkit::ConvertError::Unrecognized { cause: Some(cause), .. } = err else {
+        panic!("unexpected {err:?}");
+    };
+    assert!(matches!(*cause, llmkit::ConvertError::Parse { format: llmkit::DataFormat::Toml, line: 2, .. }), "{cause:?}");
+}
+
+#[test]
+fn ndjson_records_yields_lazily_and_reports_bad_lines() {
+    use std::io::Read;
+    let input = "{\"a\":1}\n\nnot json\n{\"a\":2}\n";
+    let records: Vec<_> = llmkit::ndjson_records(input.as_bytes()).collect();
+    assert_eq!(records.len(), 3);
+    assert_eq!(records[0], Ok(serde_json::json!({"a": 1})));
+    assert!(matches!(&records[1], Err(llmkit::ConvertError::Parse { format: llmkit::DataFormat::Ndjson, line: 3, .. })));
+    assert_eq!(records[2], Ok(serde_json::json!({"a": 2})));
+
+    // Only as much input as has been consumed is read.
+    let endless = std::io::repeat(b'\n').take(1 << 40);
+    let mut tail = llmkit::ndjson_records("{\"first\":true}\n".as_bytes().chain(endless));
+    assert_eq!(tail.next(), Some(Ok(serde_json::json!({"first": true}))));
+}
+
+#[test]
+fn dotenv_target_flattens_and_uppercases_on_request() {
+    let value = serde_json::json!({"app": {"name": "my app", "port": 8080}, "debug": true});
+    let target = llmkit::TargetFormat::Dotenv;
+    let opts = llmkit::ConvertOptions::default();
+    let err = llmkit::convert_value_to_target(&value, &target, &opts).unwrap_err();
+    assert_eq!(err.to_string(), "dotenv: nested value at 'app'");
+
+    let opts = opts.dotenv_nested(llmkit::NestedMode::Flatten).dotenv_uppercase_keys(true);
+    let env = llmkit::convert_value_to_target(&value, &target, &opts).unwrap();
+    assert_eq!(env, "APP__NAME=\"my app\"\nAPP__PORT=8080\nDEBUG=true\n");
+
+    let env = llmkit::convert_value_to_target(&value, &target, &opts.dotenv_separator("_")).unwrap();
+    assert_eq!(env, "APP_NAME=\"my app\"\nAPP_PORT=8080\nDEBUG=true\n");
+}
+
+#[test]
+fn convert_one_returns_only_the_requested_target() {
+    assert_eq!(llmkit::convert_one(br#"{"a":[1,2]}"#, "ndjson", false).unwrap(), "{\"a\":[1,2]}\n");
+    assert_eq!(llmkit::convert_one(b"{'a': 1}", "json", true).unwrap(), "{\n  \"a\": 1\n}");
+
+    let err = llmkit::convert_one(br#"{"a":1}"#, "csv", false).unwrap_err();
+    assert!(matches!(err, llmkit::ConvertError::Unsupported { ref target, .. } if target == "csv"), "{err:?}");
+    let err = llmkit::convert_one(b"", "json", false).unwrap_err();
+    assert!(matches!(err, llmkit::ConvertError::Unrecognized { .. }), "{err:?}");
+}
+
+#[test]
+fn converter_preprocessor_runs_before_fence_stripping_and_detection() {
+    // This model prefixes answers with "Answer:" and writes `True` for `true`.
+    let converter = llmkit::Converter::default()
+        .with_preprocessor(|text| text.trim_start_matches("Answer:").replace(": True", ": true"));
+    let map = converter.convert(b"\xef\xbb\xbfAnswer:```json\n{\"ok\": True}\n```");
+    assert_eq!(map["Format"], "json");
+    assert_eq!(map["normal"], r#"{"ok":true}"#);
+}
+
+#[test]
+fn converter_builder_applies_its_settings() {
+    let converter = llmkit::Converter::builder().permissive(true).indent(0).max_bytes(Some(64)).targets(&["json"]).build();
+    let map = converter.convert(b"{'a': [1]}");
+    assert_eq!(map["Format"], "json");
+    assert_eq!(map["Beautified"], "{\n\"a\": [\n1\n]\n}");
+    assert!(!map.contains_key("yaml"));
+    assert_eq!(converter.options().max_bytes, Some(64));
+
+    // Deep values take the iterative writer, which honours the same indent.
+    let opts = llmkit::ConvertOptions::default().iterative_json_depth(1);
+    let converter = llmkit::Converter::builder().options(opts).indent(1).build();
+    assert_eq!(converter.convert(b"[[[1]]]")["Beautified"], "[\n [\n  [\n   1\n  ]\n ]\n]");
+}
+
+#[test]
+fn converter_builder_sort_keys_alphabetizes_every_level() {
+    let converter = llmkit::Converter::builder().sort_keys(true).targets(&["json"]).build();
+    assert!(converter.options().sort_keys);
+    let map = converter.convert(br#"{"b":{"z":1,"y":2},"a":[{"d":1,"c":2}]}"#);
+    assert_eq!(map["normal"], r#"{"a":[{"c":2,"d":1}],"b":{"y":2,"z":1}}"#);
+}
+
+#[cfg(feature = "toml")]
+#[test]
+fn toml_root_array_key_wraps_top_level_arrays() {
+    let map = llmkit::convert_map(b"[1,2,3]", Some(&["toml"]), false, None);
+    assert!(map["toml"].is_null());
+
+    let opts = llmkit::ConvertOptions::default().targets(&["toml"]).toml_root_array_key(Some("values".into()));
+    let map = llmkit::convert_map_with(b"[1,2,3]", &opts);
+    let toml = map["toml"].as_str().unwrap();
+    assert_eq!(toml, "values = [1, 2, 3]\n");
+    assert!(toml.parse::<toml::Table>().is_ok());
+}
+
+#[cfg(feature = "serde_yaml")]
+#[test]
+fn beautified_native_keeps_yaml_as_yaml() {
+    let opts = llmkit::ConvertOptions::default().targets::<&str>(&[]).beautified_native(true);
+    let map = llmkit::convert_map_with(b"name:   app\nports: [80,   443]\n", &opts);
+    assert_eq!(map["Format"], "yaml");
+    assert_eq!(map["Beautified"], "name: app\nports:\n- 80\n- 443\n");
+
+    let map = llmkit::convert_map_with(br#"{"a":1}"#, &opts);
+    assert_eq!(map["Beautified"], "{\n  \"a\": 1\n}");
+}
+
+#[cfg(feature = "toml")]
+#[test]
+fn beautified_native_keeps_toml_as_toml() {
+    let opts = llmkit::ConvertOptions::default()
+        .targets::<&str>(&[])
+        .reject_plain_yaml(true)
+        .beautified_native(true);
+    let map = llmkit::convert_map_with(b"title=\"x\"\n[server]\nport=8080", &opts);
+    assert_eq!(map["Format"], "toml");
+    assert_eq!(map["Beautified"], "title = \"x\"\n\n[server]\nport = 8080\n");
+}
+
+#[cfg(feature = "preserve_order")]
+#[test]
+fn preserve_order_keeps_source_key_order() {
+    let opts = llmkit::ConvertOptions::default().targets(&["json"]);
+    let map = llmkit::convert_map_with(br#"{"z":1,"a":2,"m":{"y":true,"b":false}}"#, &opts);
+    assert_eq!(map["json"], "{\n  \"z\": 1,\n  \"a\": 2,\n  \"m\": {\n    \"y\": true,\n    \"b\": false\n  }\n}");
+}
+
+#[test]
+fn indent_tabs_applies_to_deep_values_too() {
+    let opts = llmkit::ConvertOptions::default().targets(&["json"]).indent_tabs(true);
+    let map = llmkit::convert_map_with(br#"{"a":{"b":1}}"#, &opts);
+    assert_eq!(map["json"], "{\n\t\"a\": {\n\t\t\"b\": 1\n\t}\n}");
+
+    let map = llmkit::convert_map_with(br#"{"a":{"b":1}}"#, &opts.iterative_json_depth(1));
+    assert_eq!(map["json"], "{\n\t\"a\": {\n\t\t\"b\": 1\n\t}\n}");
+}
+
+#[cfg(feature = "csv")]
+#[test]
+fn csv_stop_at_blank_ignores_trailing_prose() {
+    let input = b"id,name\n1,ana\n2,bo\n\nSource: the March export, minus refunds, per finance.\n";
+    let base = llmkit::ConvertOptions::default().targets::<&str>(&[]).reject_plain_yaml(true);
+    assert_ne!(llmkit::convert_map_with(input, &base)["Format"], "csv");
+
+    let map = llmkit::convert_map_with(input, &base.csv_stop_at_blank(true));
+    assert_eq!(map["Format"], "csv");
+    assert_eq!(map["normal"], r#"[{"id":"1","name":"ana"},{"id":"2","name":"bo"}]"#);
+}
+
+#[test]
+fn input_format_auto_matches_detection_and_forcing_skips_the_rest() {
+    use llmkit::DataFormat;
+    let auto = llmkit::ConvertOptions::default().input_format(DataFormat::Auto);
+    for input in [&br#"{"a":1}"#[..], b"{\"a\":1}\n{\"a\":2}\n", b"a=1&b=2", b"just prose"] {
+        assert_eq!(llmkit::convert_map_with(input, &auto), llmkit::convert_map(input, None, false, None));
+        assert_eq!(
+            llmkit::explain_detection(input, &auto),
+            llmkit::explain_detection(input, &llmkit::ConvertOptions::default())
+        );
+    }
+    assert_eq!(DataFormat::from_name("auto"), DataFormat::Auto);
+
+    // Forced: two JSON lines are NDJSON to detection, but can be read as a JSON stream.
+    let input = b"{\"a\":1}\n{\"b\":2}\n";
+    let forced = llmkit::ConvertOptions::default().input_format(DataFormat::JsonStream);
+    assert_eq!(llmkit::detected_format(&llmkit::convert_map_with(input, &auto)), DataFormat::Ndjson);
+    assert_eq!(llmkit::detected_format(&llmkit::convert_map_with(input, &forced)), DataFormat::JsonStream);
+    let steps = llmkit::explain_detection(input, &forced);
+    assert!(steps.iter().all(|s| (s.format == DataFormat::JsonStream) != (s.outcome == llmkit::DetectionOutcome::Disabled)));
+}
+
+#[cfg(feature = "ubjson")]
+#[test]
+fn ubjson_round_trips_through_bytes() {
+    let value = serde_json::json!({
+        "id": 70000, "neg": -3, "big": u64::MAX, "pi": 3.5, "ok": true, "none": null,
+        "tags": ["a", "é"], "nested": {"empty": [], "obj": {}}
+    });
+    let opts = llmkit::ConvertOptions::default();
+    let bytes = llmkit::convert_to_bytes(&value, &llmkit::TargetFormat::Ubjson, &opts).unwrap();
+    assert_eq!(bytes[0], b'{');
+    assert_eq!(llmkit::read_ubjson(&bytes).unwrap(), value);
+
+    // Counted, strongly typed containers from other encoders decode too.
+    let typed = b"[$U#U\x03\x01\x02\x03";
+    assert_eq!(llmkit::read_ubjson(typed).unwrap(), serde_json::json!([1, 2, 3]));
+    assert_eq!(
+        llmkit::read_ubjson(b"[U\x01").unwrap_err().to_string(),
+        "ubjson parse error at line 0, column 3: unexpected end of input"
+    );
+
+    let map = llmkit::convert_map(br#"{"a":1}"#, Some(&["ubjson"]), false, None);
+    assert!(map["ubjson"].is_string());
+}
+
+#[test]
+fn detect_format_strips_fences_and_skips_conversions() {
+    use llmkit::DataFormat;
+    assert_eq!(llmkit::detect_format(b"```json\n{\"a\":1}\n```", false), DataFormat::Json);
+    assert_eq!(llmkit::detect_format(b"{\"a\":1}\n{\"a\":2}\n", false), DataFormat::Ndjson);
+    assert_ne!(llmkit::detect_format(b"{'a': None}", false), DataFormat::Json);
+    assert_eq!(llmkit::detect_format(b"{'a': None}", true), DataFormat::Json);
+    assert_eq!(llmkit::detect_format(b"   ", true), DataFormat::Unknown);
+}
+
+#[cfg(all(feature = "csv", feature = "toml", feature = "serde_yaml"))]
+#[test]
+fn scalar_inputs_follow_scalar_mode() {
+    use llmkit::ScalarMode;
+    let targets = ["json", "yaml", "toml", "csv", "markdown_table", "dotenv"];
+    let run = |input: &[u8], mode| {
+        llmkit::convert_map_with(input, &llmkit::ConvertOptions::default().targets(&targets).scalar_mode(mode))
+    };
+
+    let map = run(b"42", ScalarMode::Null);
+    assert_eq!(map["json"], "42");
+    assert_eq!(map["yaml"], "42\n");
+    assert!(map["toml"].is_null() && map["csv"].is_null());
+    assert!(!map.contains_key("Errors"));
+
+    let map = run(b"42", ScalarMode::Wrap);
+    assert_eq!(map["json"], "42");
+    assert_eq!(map["toml"], "value = 42\n");
+    assert_eq!(map["csv"], "value\n42\n");
+    assert_eq!(map["dotenv"], "value=42\n");
+    assert!(map["markdown_table"].as_str().unwrap().contains("| value |"));
+
+    let map = run(br#""hi""#, ScalarMode::Error);
+    assert_eq!(map["yaml"], "hi\n");
+    assert!(map["toml"].is_null());
+    let errors = map["Errors"].as_object().unwrap();
+    let mut failed: Vec<_> = errors.keys().collect();
+    failed.sort();
+    assert_eq!(failed, ["csv", "dotenv", "markdown_table", "toml"]);
+    assert_eq!(errors["csv"], "csv: input is a bare scalar");
+}
+
+#[test]
+fn ndjson_blank_lines_are_not_records() {
+    let input = b"{\"id\":1}\n\n   \n{\"id\":2}\r\n\r\n{\"id\":3}\n\n\n";
+    let map = llmkit::convert_map(input, Some(&["json"]), false, None);
+    assert_eq!(map["Format"], "ndjson");
+    assert_eq!(map["normal"], r#"[{"id":1},{"id":2},{"id":3}]"#);
+
+    let records: Vec<_> = llmkit::ndjson_records(&input[..]).collect();
+    assert_eq!(records.len(), 3);
+    assert!(records.iter().all(Result::is_ok));
+
+    // A single record padded with blank lines is one document, not a stream.
+    let map = llmkit::convert_map(b"\n{\"id\":1}\n\n\n", Some(&["json"]), false, None);
+    assert_eq!(map["Format"], "json");
+}
+
+#[test]
+fn sniff_format_bytes_classifies_a_short_prefix() {
+    let rows: Vec<_> = (0..200).map(|i| serde_json::json!({"id": i, "name": format!("user{i}")})).collect();
+    let json = serde_json::to_vec_pretty(&rows).unwrap();
+    assert_eq!(llmkit::sniff_format_bytes(&json[..64]), llmkit::DataFormat::Json);
+
+    #[cfg(feature = "csv")]
+    {
+        let csv: String = std::iter::once("id,name,email\n".to_string())
+            .chain((0..200).map(|i| format!("{i},user{i},user{i}@example.com\n")))
+            .collect();
+        // The prefix gets the same answer as the whole input; YAML, when
+        // compiled in, reads unmarked CSV as a plain string first.
+        let whole = llmkit::detect_format(csv.as_bytes(), false);
+        assert_eq!(llmkit::sniff_format_bytes(&csv.as_bytes()[..64]), whole);
+        #[cfg(not(feature = "serde_yaml"))]
+        assert_eq!(whole, llmkit::DataFormat::Csv);
+    }
+}
+
+#[test]
+fn unquote_numbers_turns_numeric_strings_into_numbers() {
+    let input = br#"{"count":"42","huge":"123456789012345678901234","items":[{"n":"3"}],"neg":"-1.5","ok":"true","version":"1.2.3","zip":"007"}"#;
+    let opts = llmkit::ConvertOptions::default().targets(&["json"]);
+    let map = llmkit::convert_map_with(input, &opts.clone().unquote_numbers(true));
+    assert_eq!(
+        map["normal"],
+        r#"{"count":42,"huge":"123456789012345678901234","items":[{"n":3}],"neg":-1.5,"ok":"true","version":"1.2.3","zip":"007"}"#
+    );
+    let map = llmkit::convert_map_with(input, &opts);
+    assert!(map["normal"].as_str().unwrap().contains(r#""count":"42""#));
+}
+
+#[test]
+fn bundle_target_pairs_inferred_schema_with_data() {
+    let input = br#"[{"id":1,"tags":["a"]},{"id":2.5,"note":null}]"#;
+    let map = llmkit::convert_map(input, Some(&["bundle"]), false, None);
+    let bundle: serde_json::Value = serde_json::from_str(map["bundle"].as_str().unwrap()).unwrap();
+    assert_eq!(bundle["data"], serde_json::from_slice::<serde_json::Value>(input).unwrap());
+    assert_eq!(
+        bundle["schema"],
+        serde_json::json!({
+            "type": "array",
+            "items": {
+                "type": "object",
+                "properties": {
+                    "id": {"type": "number"},
+                    "note": {"type": "null"},
+                    "tags": {"type": "array", "items": {"type": "string"}}
+                },
+                "required": ["id"]
+            }
+        })
+    );
+    assert_eq!(
+        llmkit::infer_schema(&serde_json::json!([1, "x"])),
+        serde_json::json!({"type": "array", "items": {"type": ["integer", "string"]}})
+    );
+}
+
+#[cfg(feature = "csv")]
+#[test]
+fn csv_pads_ragged_arrays_of_arrays() {
+    let value = serde_json::json!([[1, 2], [3, 4, 5], ["x"]]);
+    let opts = llmkit::ConvertOptions::default();
+    let csv = llmkit::convert_to_bytes(&value, &llmkit::TargetFormat::Csv, &opts).unwrap();
+    assert_eq!(String::from_utf8(csv).unwrap(), "col0,col1,col2\n1,2,\n3,4,5\nx,,\n");
+
+    let strict = opts.csv_ragged_rows(llmkit::RaggedRows::Error);
+    let err = llmkit::convert_value_to_target(&value, &llmkit::TargetFormat::Csv, &strict).unwrap_err();
+    assert_eq!(err.to_string(), "csv: rows have different lengths");
+    let square = serde_json::json!([[1, 2], [3, 4]]);
+    let csv = llmkit::convert_value_to_target(&square, &llmkit::TargetFormat::Csv, &strict).unwrap();
+    assert_eq!(csv, "col0,col1\n1,2\n3,4\n");
+}
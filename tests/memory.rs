@@ -0,0 +1,104 @@
+//! Peak-allocation checks. Kept in their own binary because they install a
+//! counting global allocator.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::io::{self, Read, Write};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+struct Counting;
+
+static CURRENT: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+/// Tests share the counters, so they take turns.
+static SERIAL: Mutex<()> = Mutex::new(());
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let now = CURRENT.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+        PEAK.fetch_max(now, Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        CURRENT.fetch_sub(layout.size(), Ordering::Relaxed);
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static ALLOC: Counting = Counting;
+
+/// Bytes allocated at the high point of `f`, above what was live before it.
+fn peak_during(f: impl FnOnce()) -> usize {
+    let base = CURRENT.load(Ordering::Relaxed);
+    PEAK.store(base, Ordering::Relaxed);
+    f();
+    PEAK.load(Ordering::Relaxed) - base
+}
+
+/// `[{"id":0,"name":"item"}, ...]` generated on the fly.
+struct Records {
+    next: usize,
+    count: usize,
+    pending: Vec<u8>,
+}
+
+impl Read for Records {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pending.is_empty() && self.next <= self.count {
+            self.pending = match self.next {
+                0 => b"[".to_vec(),
+                n if n == self.count => b"]".to_vec(),
+                n => format!("{}{{\"id\":{n},\"name\":\"item\"}}", if n > 1 { "," } else { "" }).into_bytes(),
+            };
+            self.next += 1;
+        }
+        let n = buf.len().min(self.pending.len());
+        buf[..n].copy_from_slice(&self.pending[..n]);
+        self.pending.drain(..n);
+        Ok(n)
+    }
+}
+
+struct Sink(usize);
+
+impl Write for Sink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0 += buf.len();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn write_beautified_memory_does_not_grow_with_input() {
+    let _serial = SERIAL.lock().unwrap();
+    let mut sink = Sink(0);
+    let peak = peak_during(|| {
+        let input = Records { next: 0, count: 500_000, pending: Vec::new() };
+        llmkit::write_beautified(input, &mut sink).unwrap();
+    });
+    assert!(sink.0 > 20_000_000, "wrote {} bytes", sink.0);
+    assert!(peak < 256 * 1024, "peak {peak} bytes");
+}
+
+#[test]
+fn skipping_beautified_drops_the_pretty_copy() {
+    let _serial = SERIAL.lock().unwrap();
+    let mut input = Vec::new();
+    Records { next: 0, count: 50_000, pending: Vec::new() }.read_to_end(&mut input).unwrap();
+    let base = llmkit::ConvertOptions::default().targets::<&str>(&[]);
+
+    let with = peak_during(|| drop(llmkit::convert_map_with(&input, &base)));
+    let without = peak_during(|| {
+        let map = llmkit::convert_map_with(&input, &base.clone().include_beautified(false));
+        assert_eq!(map["Beautified"], "");
+        assert_eq!(map["Format"], "json");
+    });
+    // The pretty string is the largest of the three copies.
+    assert!(without + input.len() < with, "with {with}, without {without}");
+}
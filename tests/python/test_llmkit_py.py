@@ -0,0 +1,31 @@
+# Run after `maturin develop -F python`: `python -m pytest tests/python`.
+import llmkit_py as m
+
+
+def test_value_is_native_python():
+    out = m.convert_map(b'{"a": 1, "b": [true, null, 2.5], "c": {"d": "x"}}')
+    assert out["Format"] == "json"
+    assert out["value"] == {"a": 1, "b": [True, None, 2.5], "c": {"d": "x"}}
+
+
+def test_value_is_none_when_unrecognized():
+    out = m.convert_map(b"\x00\x01")
+    assert out["value"] is None
+
+
+def test_value_comes_from_parsed_input_not_normal():
+    out = m.convert_map(b'{"a": 1}', ["yaml"])
+    assert "normal" in out
+    assert out["value"] == {"a": 1}
+
+
+def test_detect_format_accepts_str_and_bytes():
+    assert m.detect_format('{"a":1}') == "json"
+    assert m.detect_format(b'{"a":1}', allow_permissive=False) == "json"
+
+
+if __name__ == "__main__":
+    for name, test in list(globals().items()):
+        if name.startswith("test_"):
+            test()
+    print("ok")